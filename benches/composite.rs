@@ -0,0 +1,111 @@
+//! Time the compositing of a generated 512x512 sprite with 20 layers
+//!
+//! Every layer covers the whole canvas with a mix of opaque, translucent and transparent
+//! pixels. Run with `cargo bench --bench composite`.
+
+use std::time::Instant;
+
+use aseprite_reader::{
+    raw::{
+        AsepriteBlendMode, AsepriteLayerType, PackedPixels, RawAseprite, RawAsepriteCel,
+        RawAsepriteChunk, RawAsepriteFrame, RawAsepriteHeader,
+    },
+    Aseprite, AsepriteColorDepth,
+};
+
+const SIZE: u16 = 512;
+const LAYERS: u16 = 20;
+const ITERATIONS: u32 = 50;
+
+#[allow(deprecated)]
+fn sprite() -> RawAseprite {
+    let mut chunks = Vec::new();
+    for layer_index in 0..LAYERS {
+        chunks.push(RawAsepriteChunk::Layer {
+            flags: 1,
+            layer_type: AsepriteLayerType::Normal,
+            layer_child: 0,
+            width: 0,
+            height: 0,
+            blend_mode: AsepriteBlendMode::Normal,
+            opacity: 255,
+            name: format!("Layer {}", layer_index),
+            uuid: None,
+        });
+    }
+    for layer_index in 0..LAYERS {
+        let mut bytes = Vec::with_capacity(SIZE as usize * SIZE as usize * 4);
+        for pixel in 0..SIZE as usize * SIZE as usize {
+            let seed = pixel.wrapping_mul(2_654_435_761) ^ (layer_index as usize * 97);
+            let alpha = match seed % 4 {
+                0 => 0,
+                1 => 255,
+                _ => (seed >> 8) as u8,
+            };
+            bytes.extend_from_slice(&[
+                (seed >> 3) as u8,
+                (seed >> 11) as u8,
+                layer_index as u8 * 12,
+                alpha,
+            ]);
+        }
+        chunks.push(RawAsepriteChunk::Cel {
+            layer_index,
+            x: 0,
+            y: 0,
+            opacity: 255,
+            z_index: 0,
+            cel: RawAsepriteCel::Raw {
+                width: SIZE,
+                height: SIZE,
+                pixels: PackedPixels::new(AsepriteColorDepth::RGBA, bytes)
+                    .expect("4 bytes per pixel"),
+            },
+        });
+    }
+    RawAseprite {
+        header: RawAsepriteHeader {
+            file_size: 0,
+            magic_number: 0xA5E0,
+            frames: 1,
+            width: SIZE,
+            height: SIZE,
+            color_depth: AsepriteColorDepth::RGBA,
+            flags: 1,
+            speed: 100,
+            transparent_palette: 0,
+            color_count: 0,
+            pixel_width: 1,
+            pixel_height: 1,
+            grid_x: 0,
+            grid_y: 0,
+            grid_width: 16,
+            grid_height: 16,
+        },
+        frames: vec![RawAsepriteFrame {
+            magic_number: 0xF1FA,
+            duration_ms: 100,
+            chunks,
+        }],
+    }
+}
+
+fn main() {
+    let aseprite = Aseprite::from_raw(sprite()).expect("generated sprite should load");
+    aseprite.prewarm([0]).expect("cels should decode");
+    let mut target = image::RgbaImage::default();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        aseprite
+            .render_frame_into(0, &mut target)
+            .expect("frame should render");
+    }
+    println!(
+        "{}x{} with {} layers: {:?} per frame",
+        SIZE,
+        SIZE,
+        LAYERS,
+        start.elapsed() / ITERATIONS
+    );
+}
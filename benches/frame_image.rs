@@ -0,0 +1,35 @@
+//! Time `Aseprite::get_frame_image` over every frame of the complex test file
+//!
+//! Run with `cargo bench --bench frame_image`.
+
+use std::time::Instant;
+
+use aseprite_reader::Aseprite;
+
+const ITERATIONS: u32 = 2000;
+
+fn main() {
+    let aseprite =
+        Aseprite::from_path("tests/test_cases/complex.aseprite").expect("test file should load");
+    let frames = aseprite.frame_count();
+    aseprite
+        .prewarm(0..frames)
+        .expect("every frame should decode");
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        for frame_index in 0..frames {
+            std::hint::black_box(
+                aseprite
+                    .get_frame_image(frame_index)
+                    .expect("frame should render"),
+            );
+        }
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "get_frame_image: {:?} per frame over {} frames",
+        elapsed / (ITERATIONS * frames as u32),
+        ITERATIONS * frames as u32
+    );
+}
@@ -0,0 +1,45 @@
+//! Time `Aseprite::get_image_by_layer_frame` over every cel of the test files
+//!
+//! Run with `cargo bench --bench layer_image`.
+
+use std::time::{Duration, Instant};
+
+use aseprite_reader::Aseprite;
+
+const ITERATIONS: u32 = 200;
+
+fn main() {
+    for path in [
+        "tests/test_cases/complex.aseprite",
+        "tests/test_cases/multiple_frames_layers.aseprite",
+    ] {
+        let aseprite = Aseprite::from_path(path).expect("test file should load");
+        let layers: Vec<usize> = aseprite.layers().map(|layer| layer.index()).collect();
+        let frames = aseprite.frame_count();
+
+        let mut elapsed = Duration::ZERO;
+        let mut images = 0u32;
+        for _ in 0..ITERATIONS {
+            let start = Instant::now();
+            for &layer_index in &layers {
+                for frame_index in 0..frames {
+                    if let Some(image) = aseprite
+                        .get_image_by_layer_frame(layer_index, frame_index)
+                        .expect("cel should render")
+                    {
+                        std::hint::black_box(image);
+                        images += 1;
+                    }
+                }
+            }
+            elapsed += start.elapsed();
+        }
+        println!(
+            "{}: {} layer images in {:?}, {:?} per image",
+            path,
+            images,
+            elapsed,
+            elapsed / images.max(1)
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! Count the allocations and time of `Aseprite::render_frame_into` and
+//! `Aseprite::render_layer_into` once the cels are decoded
+//!
+//! Run with `cargo bench --bench render_into`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use aseprite_reader::Aseprite;
+use image::RgbaImage;
+
+const ITERATIONS: usize = 1000;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn measure<F: FnMut()>(name: &str, mut render: F) {
+    // 第一次调用会解码 cel 并分配 target
+    render();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        render();
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!(
+        "{}: {:?} per call, {} allocations per call",
+        name,
+        elapsed / ITERATIONS as u32,
+        allocations as f64 / ITERATIONS as f64
+    );
+}
+
+fn main() {
+    let aseprite =
+        Aseprite::from_path("tests/test_cases/complex.aseprite").expect("test file should load");
+    let frames = aseprite.frame_count();
+    let layers: Vec<usize> = aseprite.layers().map(|layer| layer.index()).collect();
+    let mut target = RgbaImage::default();
+
+    measure("render_frame_into", || {
+        for frame_index in 0..frames {
+            aseprite
+                .render_frame_into(frame_index, &mut target)
+                .expect("frame should render");
+        }
+    });
+    measure("get_frame_image", || {
+        for frame_index in 0..frames {
+            std::hint::black_box(
+                aseprite
+                    .get_frame_image(frame_index)
+                    .expect("frame should render"),
+            );
+        }
+    });
+    measure("render_layer_into", || {
+        for &layer_index in &layers {
+            aseprite
+                .render_layer_into(layer_index, 0, &mut target)
+                .expect("layer should render");
+        }
+    });
+}
@@ -0,0 +1,148 @@
+//! Inspect aseprite files from the command line
+//!
+//! Built with `cargo install aseprite-reader --features cli`.
+
+use std::{error::Error, process::ExitCode};
+
+use aseprite_reader::{Aseprite, JsonExportOptions, LayerTreeNode, SpritesheetOptions};
+
+const USAGE: &str = "\
+Usage:
+    asepect info <file>
+    asepect export-frame <file> <frame> <output.png>
+    asepect export-layer <file> <layer name or index> <frame> <output.png>
+    asepect export-json <file> <sheet.png>
+
+export-json writes every frame to a grid spritesheet and prints its metadata in the
+JSON format of `aseprite --data`.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["info", file] => info(file),
+        ["export-frame", file, frame, output] => export_frame(file, frame, output),
+        ["export-layer", file, layer, frame, output] => export_layer(file, layer, frame, output),
+        ["export-json", file, sheet] => export_json(file, sheet),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn info(file: &str) -> Result<(), Box<dyn Error>> {
+    let aseprite = Aseprite::from_path(file)?;
+    let (width, height) = aseprite.size();
+    println!("size: {}x{}", width, height);
+    println!("color depth: {:?}", aseprite.color_depth());
+    println!(
+        "frames: {} ({} ms)",
+        aseprite.frame_count(),
+        aseprite.total_duration_ms()
+    );
+
+    println!("layers:");
+    for node in aseprite.layer_tree().values() {
+        node.walk(|node, depth| {
+            let Some(layer) = aseprite
+                .layers()
+                .find(|layer| layer.index() == node.index())
+            else {
+                return;
+            };
+            let kind = match node {
+                LayerTreeNode::Group(..) => "group",
+                LayerTreeNode::Normal(..) => "layer",
+            };
+            let mut line = format!(
+                "{:indent$}[{}] {} {:?}",
+                "",
+                layer.index(),
+                kind,
+                layer.name(),
+                indent = 2 + depth * 2
+            );
+            if !layer.is_visible() {
+                line.push_str(" hidden");
+            }
+            if node.is_normal() {
+                line.push_str(&format!(" {:?}", layer.blend_mode()));
+            }
+            if let Some(opacity) = layer.opacity() {
+                line.push_str(&format!(" opacity={}", opacity));
+            }
+            println!("{}", line);
+        });
+    }
+
+    println!("tags:");
+    for tag in aseprite.tags() {
+        println!(
+            "  {:?} frames {}..{} {:?}",
+            tag.name, tag.frames.start, tag.frames.end, tag.animation_direction
+        );
+    }
+
+    println!("slices:");
+    for slice in aseprite.slices() {
+        println!("  {:?}", slice.name);
+        for key in &slice.keys {
+            let bounds = key.bounds;
+            let mut line = format!(
+                "    from frame {}: {}x{} at ({}, {})",
+                key.from_frame, bounds.width, bounds.height, bounds.x, bounds.y
+            );
+            if key.nine_patch_info.is_some() {
+                line.push_str(" 9-patch");
+            }
+            if let Some(pivot) = &key.pivot {
+                line.push_str(&format!(" pivot=({}, {})", pivot.x_pivot, pivot.y_pivot));
+            }
+            println!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+fn export_frame(file: &str, frame: &str, output: &str) -> Result<(), Box<dyn Error>> {
+    let aseprite = Aseprite::from_path(file)?;
+    aseprite.get_frame_image(frame.parse()?)?.save(output)?;
+    Ok(())
+}
+
+fn export_layer(file: &str, layer: &str, frame: &str, output: &str) -> Result<(), Box<dyn Error>> {
+    let aseprite = Aseprite::from_path(file)?;
+    let layer_index = match layer.parse() {
+        Ok(index) => index,
+        Err(_) => aseprite
+            .layers()
+            .find(|candidate| candidate.name() == layer)
+            .ok_or_else(|| format!("no layer is named {:?}", layer))?
+            .index(),
+    };
+    let image = aseprite
+        .get_image_by_layer_frame_on_canvas(layer_index, frame.parse()?)?
+        .ok_or_else(|| format!("layer {} has no cel in frame {}", layer, frame))?;
+    image.save(output)?;
+    Ok(())
+}
+
+fn export_json(file: &str, sheet: &str) -> Result<(), Box<dyn Error>> {
+    let aseprite = Aseprite::from_path(file)?;
+    let (image, meta) = aseprite.to_spritesheet(&SpritesheetOptions::default())?;
+    image.save(sheet)?;
+    let options = JsonExportOptions {
+        image: sheet.to_string(),
+        ..JsonExportOptions::default()
+    };
+    println!("{}", aseprite.export_json(&meta, &options));
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use std::io::Write;
+
+use crate::error::{AseResult, AsepriteError};
+
+use super::Aseprite;
+
+impl Aseprite {
+    /// Export frames as an animated PNG, looping forever
+    ///
+    /// Frames are written in the given order with their full RGBA pixels and their own delays,
+    /// so a ping-pong tag can be exported with [`AsepriteTag::frame_sequence`]:
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let aseprite = aseprite_reader::Aseprite::from_path("sprite.aseprite")?;
+    /// if let Some(tag) = aseprite.tags().next() {
+    ///     aseprite.export_apng(tag.frame_sequence(), std::fs::File::create("tag.png")?)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`AsepriteTag::frame_sequence`]: crate::AsepriteTag::frame_sequence
+    pub fn export_apng<I, W>(&self, frames: I, writer: W) -> AseResult<()>
+    where
+        I: IntoIterator<Item = u16>,
+        W: Write,
+    {
+        let frames: Vec<usize> = frames.into_iter().map(usize::from).collect();
+        let images = self.render_frames(&frames)?;
+        let (width, height) = self.dimensions;
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .set_animated(frames.len() as u32, 0)
+            .map_err(encode_error)?;
+        let mut writer = encoder.write_header().map_err(encode_error)?;
+        for (frame_index, image) in frames.into_iter().zip(images) {
+            // aseprite 中的帧时长本来就是 u16
+            let delay = self.frame_infos[frame_index]
+                .delay_ms
+                .min(u16::MAX as usize) as u16;
+            writer.set_frame_delay(delay, 1000).map_err(encode_error)?;
+            writer.write_image_data(&image).map_err(encode_error)?;
+        }
+        writer.finish().map_err(encode_error)
+    }
+}
+
+fn encode_error(error: png::EncodingError) -> AsepriteError {
+    match error {
+        png::EncodingError::IoError(error) => AsepriteError::Io(error),
+        error => AsepriteError::Encode(error.to_string()),
+    }
+}
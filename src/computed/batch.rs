@@ -0,0 +1,144 @@
+use std::{
+    panic,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use image::RgbaImage;
+
+use crate::error::AseResult;
+
+use super::Aseprite;
+
+/// Extensions [`Aseprite::from_dir`] loads, compared without case
+const EXTENSIONS: [&str; 2] = ["ase", "aseprite"];
+
+impl Aseprite {
+    /// Load several files, returning each path with its result in the same order
+    ///
+    /// A file that fails to load only fails its own entry, the other files are still loaded.
+    /// Files are loaded on up to [`std::thread::available_parallelism`] threads, see
+    /// [`Aseprite::from_path`].
+    pub fn from_paths<I: IntoIterator<Item = PathBuf>>(
+        paths: I,
+    ) -> Vec<(PathBuf, AseResult<Aseprite>)> {
+        let paths: Vec<PathBuf> = paths.into_iter().collect();
+        let results = map_on_threads(&paths, available_threads(), |path| {
+            Aseprite::from_path(path)
+        });
+        paths.into_iter().zip(results).collect()
+    }
+
+    /// Set how many threads render frames at most, 0 is treated as 1
+    ///
+    /// Spritesheets, strips and the GIF, APNG and layer exports render their frames in
+    /// parallel, by default on [`std::thread::available_parallelism`] threads. With 1 every
+    /// frame is rendered on the calling thread.
+    pub fn set_render_threads(&mut self, threads: usize) {
+        self.render_threads = Some(threads.max(1));
+    }
+
+    /// 按 [`Aseprite::set_render_threads`] 的线程数对每一项调用 `f`，结果和 `items` 的顺序一致
+    pub(super) fn map_rendering<T, R, F>(&self, items: &[T], f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Sync,
+    {
+        let threads = self.render_threads.unwrap_or_else(available_threads);
+        map_on_threads(items, threads, f)
+    }
+
+    /// 并行渲染 frames，返回的图片和 frames 的顺序一致
+    pub(super) fn render_frames(&self, frames: &[usize]) -> AseResult<Vec<RgbaImage>> {
+        self.map_rendering(frames, |&frame_index| self.get_frame_image(frame_index))
+            .into_iter()
+            .collect()
+    }
+
+    /// Load every `.ase` and `.aseprite` file of a directory, like [`Aseprite::from_paths`]
+    ///
+    /// With `recursive` the sub-directories are searched too. Files are sorted by path so the
+    /// order does not depend on the filesystem. Only failing to list a directory is an error.
+    pub fn from_dir<P: AsRef<Path>>(
+        dir: P,
+        recursive: bool,
+    ) -> AseResult<Vec<(PathBuf, AseResult<Aseprite>)>> {
+        let mut paths = Vec::new();
+        find_files(dir.as_ref(), recursive, &mut paths)?;
+        paths.sort();
+        Ok(Aseprite::from_paths(paths))
+    }
+}
+
+/// 默认的线程数，拿不到时只用当前线程
+fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+/// 在 `threads` 个线程上对每一项调用 `f`，返回的结果和 `items` 的顺序一致
+///
+/// 每个线程完成一项后再取下一项，耗时不同的项也能均匀分配。`f` panic 时在调用的线程上继续
+/// panic。
+pub(super) fn map_on_threads<T, R, F>(items: &[T], threads: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let threads = threads.min(items.len());
+    if threads <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut indexed: Vec<(usize, R)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        match items.get(index) {
+                            Some(item) => done.push((index, f(item))),
+                            None => return done,
+                        }
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|payload| panic::resume_unwind(payload))
+            })
+            .collect()
+    });
+    indexed.sort_unstable_by_key(|&(index, _)| index);
+    indexed.into_iter().map(|(_, result)| result).collect()
+}
+
+fn find_files(dir: &Path, recursive: bool, paths: &mut Vec<PathBuf>) -> AseResult<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if recursive {
+                find_files(&path, recursive, paths)?;
+            }
+            continue;
+        }
+        let is_aseprite = path.extension().is_some_and(|extension| {
+            EXTENSIONS
+                .iter()
+                .any(|expected| extension.eq_ignore_ascii_case(expected))
+        });
+        if is_aseprite {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
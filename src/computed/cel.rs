@@ -1,18 +1,22 @@
-use crate::raw::{AsepriteColor, RawAsepriteCel};
+use std::{collections::BTreeMap, sync::OnceLock};
+
+use super::{AsepriteLayer, AsepriteRect};
+use crate::raw::{AsepriteColor, AsepritePropertiesMap, RawAsepriteCel};
 
 #[derive(Debug, Clone)]
 /// A single cel in a frame in a layer
+///
+/// Cels compare equal when their metadata and pixels are equal, see
+/// [`Aseprite::metadata_eq`](crate::Aseprite::metadata_eq) to ignore the pixels.
 pub struct AsepriteCel {
     /// 表示相对于整个 sprite 左上角的位置
     pub x: i16,
     /// 表示相对于整个 sprite 左上角的位置
     pub y: i16,
     /// 表示单个 cel 的透明度
-    #[allow(dead_code)]
     pub opacity: u8,
     /// 针对某一帧判断图层顺序时，需要比较 layer index + z-index 的结果
     /// 如果相同，再比较 z-index
-    #[allow(dead_code)]
     pub z_index: i16,
     /// 实际存储的 cel 数据
     pub raw_cel: RawAsepriteCel,
@@ -20,6 +24,185 @@ pub struct AsepriteCel {
     pub color: AsepriteColor,
     /// Cel Properties 中的 user data
     pub user_data: String,
+    /// Cel Properties 中的 properties maps
+    pub properties: Vec<AsepritePropertiesMap>,
+    /// Cel Extra chunk 中的精确位置和尺寸，只有设置了 precise bounds 时才有
+    pub precise_bounds: Option<AsepritePreciseBounds>,
+    /// 转换成 RGBA 后的像素缓存
+    pub(super) decoded: DecodedPixels,
+}
+
+impl PartialEq for AsepriteCel {
+    fn eq(&self, other: &Self) -> bool {
+        // decoded 只是缓存，不参与比较
+        self.metadata_eq(other) && self.raw_cel == other.raw_cel
+    }
+}
+
+/// A cel together with the cel holding its pixels, see [`Aseprite::resolve_cel`]
+///
+/// For a linked cel the pixels come from the cel of another frame, but the cel is drawn at
+/// its own position with its own opacity and z-index. For other cels both are the same cel.
+///
+/// [`Aseprite::resolve_cel`]: crate::Aseprite::resolve_cel
+#[derive(Debug, Clone, Copy)]
+pub struct ResolvedCel<'a> {
+    /// The cel holding the pixels, never a linked cel
+    pub source: &'a AsepriteCel,
+    /// x position of the cel on the canvas
+    pub x: i16,
+    /// y position of the cel on the canvas
+    pub y: i16,
+    /// Opacity of the cel
+    pub opacity: u8,
+    /// z-index of the cel
+    pub z_index: i16,
+}
+
+impl<'a> ResolvedCel<'a> {
+    pub(super) fn new(cel: &AsepriteCel, source: &'a AsepriteCel) -> Self {
+        ResolvedCel {
+            source,
+            x: cel.x,
+            y: cel.y,
+            opacity: cel.opacity,
+            z_index: cel.z_index,
+        }
+    }
+
+    /// Area covered by the cel on the canvas, with the size of the source cel
+    pub fn bounds(&self) -> AsepriteRect {
+        let [width, height] = self.source.get_size().unwrap_or_default();
+        AsepriteRect::new(self.x as i32, self.y as i32, width as u32, height as u32)
+    }
+}
+
+/// A cel with the layer and frame it belongs to, see [`Aseprite::iter_cels`]
+///
+/// [`Aseprite::iter_cels`]: crate::Aseprite::iter_cels
+#[derive(Debug, Clone, Copy)]
+pub struct CelEntry<'a> {
+    /// Index of the layer of the cel
+    pub layer_index: usize,
+    /// Index of the frame of the cel
+    pub frame_index: usize,
+    /// The cel itself, linked cels are not resolved
+    pub cel: &'a AsepriteCel,
+    layer: Option<&'a AsepriteLayer>,
+}
+
+impl<'a> CelEntry<'a> {
+    pub(super) fn new(
+        layer_index: usize,
+        frame_index: usize,
+        cel: &'a AsepriteCel,
+        layer: Option<&'a AsepriteLayer>,
+    ) -> Self {
+        CelEntry {
+            layer_index,
+            frame_index,
+            cel,
+            layer,
+        }
+    }
+
+    /// The layer of the cel
+    ///
+    /// Only `None` for malformed files with cels in a layer that does not exist.
+    pub fn layer(&self) -> Option<&'a AsepriteLayer> {
+        self.layer
+    }
+
+    /// The name of the layer of the cel, empty if the layer does not exist
+    pub fn layer_name(&self) -> &'a str {
+        self.layer.map_or("", AsepriteLayer::name)
+    }
+}
+
+/// Sub-pixel position and size of a cel, set by the transform tool of Aseprite
+///
+/// Read from the Cel Extra chunk following the cel. Only used when rendering with
+/// [`FrameRenderOptions::use_precise_bounds`](crate::FrameRenderOptions::use_precise_bounds).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AsepritePreciseBounds {
+    /// x position on the canvas
+    pub x: f64,
+    /// y position on the canvas
+    pub y: f64,
+    /// Width the pixels of the cel are scaled to
+    pub width: f64,
+    /// Height the pixels of the cel are scaled to
+    pub height: f64,
+}
+
+impl AsepritePreciseBounds {
+    /// 四舍五入到整数像素
+    pub(super) fn rounded(&self) -> AsepriteRect {
+        AsepriteRect::new(
+            self.x.round() as i32,
+            self.y.round() as i32,
+            self.width.round().max(0.0) as u32,
+            self.height.round().max(0.0) as u32,
+        )
+    }
+}
+
+/// Per-cel cache of the pixels converted to RGBA
+///
+/// Every cel owns its own [`OnceLock`], so rendering different frames from several
+/// threads never contends on a shared lock.
+#[derive(Clone, Default)]
+pub(super) struct DecodedPixels(OnceLock<Vec<[u8; 4]>>);
+
+impl DecodedPixels {
+    pub(super) fn get(&self) -> Option<&[[u8; 4]]> {
+        self.0.get().map(Vec::as_slice)
+    }
+
+    pub(super) fn get_or_init(&self, pixels: Vec<[u8; 4]>) -> &[[u8; 4]] {
+        self.0.get_or_init(|| pixels)
+    }
+}
+
+impl std::fmt::Debug for DecodedPixels {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecodedPixels")
+            .field("decoded", &self.0.get().is_some())
+            .finish()
+    }
+}
+
+/// A cel of a frame, telling apart cels that link to the cel of another frame
+#[derive(Debug, Clone, Copy)]
+pub enum CelRef<'a> {
+    /// A cel with its own pixels
+    Direct(&'a AsepriteCel),
+    /// A cel using the pixels of the cel in `target_frame` of the same layer
+    Linked {
+        /// The frame of the cel holding the pixels
+        target_frame: usize,
+        /// The linked cel itself, with its own position and opacity
+        cel: &'a AsepriteCel,
+    },
+}
+
+impl<'a> CelRef<'a> {
+    pub(super) fn new(cel: &'a AsepriteCel) -> Self {
+        match cel.raw_cel {
+            RawAsepriteCel::Linked { frame_position } => CelRef::Linked {
+                target_frame: frame_position as usize,
+                cel,
+            },
+            _ => CelRef::Direct(cel),
+        }
+    }
+
+    /// The cel of this frame, linked or not
+    pub fn cel(&self) -> &'a AsepriteCel {
+        match self {
+            CelRef::Direct(cel) | CelRef::Linked { cel, .. } => cel,
+        }
+    }
 }
 
 impl AsepriteCel {
@@ -32,15 +215,176 @@ impl AsepriteCel {
             raw_cel,
             color: AsepriteColor::default(),
             user_data: String::new(),
+            properties: Vec::new(),
+            precise_bounds: None,
+            decoded: DecodedPixels::default(),
         }
     }
 
+    /// 除像素外都相同，像素只比较尺寸和 linked cel 指向的帧
+    pub(super) fn metadata_eq(&self, other: &Self) -> bool {
+        let linked_frame = |cel: &AsepriteCel| match cel.raw_cel {
+            RawAsepriteCel::Linked { frame_position } => Some(frame_position),
+            _ => None,
+        };
+        self.x == other.x
+            && self.y == other.y
+            && self.opacity == other.opacity
+            && self.z_index == other.z_index
+            && self.get_size() == other.get_size()
+            && linked_frame(self) == linked_frame(other)
+            && self.color == other.color
+            && self.user_data == other.user_data
+            && self.properties == other.properties
+            && self.precise_bounds == other.precise_bounds
+    }
+
     /// 获取给 cel 的 sprite 尺寸，如果是 linked cel 则返回空
     pub fn get_size(&self) -> Option<[u16; 2]> {
         match self.raw_cel {
             RawAsepriteCel::Raw { width, height, .. } => Some([width, height]),
             RawAsepriteCel::Linked { .. } => None,
             RawAsepriteCel::Compressed { width, height, .. } => Some([width, height]),
+            RawAsepriteCel::Deferred { width, height, .. }
+            | RawAsepriteCel::Skipped { width, height }
+            | RawAsepriteCel::Dropped { width, height } => Some([width, height]),
+        }
+    }
+
+    /// Position of the top-left corner of this cel on the canvas
+    pub fn origin(&self) -> (i32, i32) {
+        (self.x as i32, self.y as i32)
+    }
+
+    /// Area covered by this cel on the canvas
+    ///
+    /// Returns `None` for linked cels, use [`Aseprite::cel_bounds`](crate::Aseprite::cel_bounds)
+    /// to resolve them.
+    pub fn bounds(&self) -> Option<AsepriteRect> {
+        let [width, height] = self.get_size()?;
+        Some(AsepriteRect::new(
+            self.x as i32,
+            self.y as i32,
+            width as u32,
+            height as u32,
+        ))
+    }
+}
+
+/// 所有 layer 的 cel，按 [layer][frame] 存储，避免每次访问都要查找两层 BTreeMap
+#[derive(Debug, Clone, Default)]
+pub(super) struct CelGrid(Vec<LayerCels>);
+
+/// 一个 layer 的所有 cel
+#[derive(Debug, Clone)]
+pub(super) enum LayerCels {
+    /// 每一帧一个位置，至少一半的帧有 cel 时使用
+    Dense(Vec<Option<AsepriteCel>>),
+    /// 按帧排序的 cel，大部分帧都没有 cel 时使用，避免空位占用内存
+    Sparse(Vec<(usize, AsepriteCel)>),
+}
+
+impl CelGrid {
+    pub(super) fn new(
+        cels: BTreeMap<usize, BTreeMap<usize, AsepriteCel>>,
+        frame_count: usize,
+    ) -> Self {
+        let layer_count = cels.keys().next_back().map_or(0, |last| last + 1);
+        let mut grid = vec![LayerCels::Sparse(Vec::new()); layer_count];
+        for (layer_index, layer_cels) in cels {
+            grid[layer_index] = if layer_cels.len() * 2 >= frame_count {
+                let mut dense = vec![None; frame_count];
+                for (frame_index, cel) in layer_cels {
+                    if frame_index >= dense.len() {
+                        dense.resize(frame_index + 1, None);
+                    }
+                    dense[frame_index] = Some(cel);
+                }
+                LayerCels::Dense(dense)
+            } else {
+                LayerCels::Sparse(layer_cels.into_iter().collect())
+            };
+        }
+        CelGrid(grid)
+    }
+
+    pub(super) fn get(&self, layer_index: usize, frame_index: usize) -> Option<&AsepriteCel> {
+        self.0.get(layer_index)?.get(frame_index)
+    }
+
+    /// 没有任何 cel 的 layer 返回 None
+    pub(super) fn layer(&self, layer_index: usize) -> Option<&LayerCels> {
+        self.0
+            .get(layer_index)
+            .filter(|layer_cels| !layer_cels.is_empty())
+    }
+
+    /// 有 cel 的 layer，按 layer 顺序
+    pub(super) fn iter(&self) -> impl Iterator<Item = (usize, &LayerCels)> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|(_, layer_cels)| !layer_cels.is_empty())
+    }
+
+    /// 所有 cel，按 layer 顺序
+    pub(super) fn cels_mut(&mut self) -> impl Iterator<Item = &mut AsepriteCel> + '_ {
+        self.0.iter_mut().flat_map(|layer_cels| {
+            let (dense, sparse) = match layer_cels {
+                LayerCels::Dense(cels) => (Some(cels), None),
+                LayerCels::Sparse(cels) => (None, Some(cels)),
+            };
+            let dense = dense.into_iter().flatten().flatten();
+            let sparse = sparse.into_iter().flatten().map(|(_, cel)| cel);
+            dense.chain(sparse)
+        })
+    }
+
+    /// 去掉 keep 返回 false 的 layer 的所有 cel
+    pub(super) fn retain_layers<F: Fn(usize) -> bool>(&mut self, keep: F) {
+        for (layer_index, layer_cels) in self.0.iter_mut().enumerate() {
+            if !keep(layer_index) {
+                *layer_cels = LayerCels::Sparse(Vec::new());
+            }
+        }
+    }
+}
+
+impl LayerCels {
+    pub(super) fn get(&self, frame_index: usize) -> Option<&AsepriteCel> {
+        match self {
+            LayerCels::Dense(cels) => cels.get(frame_index)?.as_ref(),
+            LayerCels::Sparse(cels) => {
+                let position = cels
+                    .binary_search_by_key(&frame_index, |(frame_index, _)| *frame_index)
+                    .ok()?;
+                Some(&cels[position].1)
+            }
+        }
+    }
+
+    /// 所有 cel 和它们所在的帧，按帧的顺序
+    pub(super) fn iter(&self) -> impl Iterator<Item = (usize, &AsepriteCel)> + '_ {
+        let (dense, sparse) = match self {
+            LayerCels::Dense(cels) => (Some(cels), None),
+            LayerCels::Sparse(cels) => (None, Some(cels)),
+        };
+        let dense = dense.into_iter().flat_map(|cels| {
+            cels.iter()
+                .enumerate()
+                .filter_map(|(frame_index, cel)| Some((frame_index, cel.as_ref()?)))
+        });
+        let sparse = sparse
+            .into_iter()
+            .flat_map(|cels| cels.iter().map(|(frame_index, cel)| (*frame_index, cel)));
+        dense.chain(sparse)
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            // 只有至少有一个 cel 时才会用 Dense
+            LayerCels::Dense(_) => false,
+            LayerCels::Sparse(cels) => cels.is_empty(),
         }
     }
 }
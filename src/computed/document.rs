@@ -0,0 +1,171 @@
+use crate::raw::{
+    AsepriteColor, AsepriteColorDepth, AsepritePropertiesMap, RawAsepriteIccProfile,
+    RawAsepriteUserData,
+};
+
+use super::{Aseprite, AsepriteRect, CelEntry};
+
+/// The document level metadata of a file, see [`Aseprite::info`]
+///
+/// Every field is also available through its own method of [`Aseprite`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsepriteDocumentInfo<'a> {
+    /// Canvas width
+    pub width: u16,
+    /// Canvas height
+    pub height: u16,
+    /// The color depth used
+    pub color_depth: AsepriteColorDepth,
+    /// Amount of frames
+    pub frame_count: usize,
+    /// The flags of the file header, see [`Aseprite::header_flags`]
+    pub header_flags: u32,
+    /// Width and height of one pixel, see [`Aseprite::pixel_ratio`]
+    pub pixel_ratio: (u8, u8),
+    /// The grid, see [`Aseprite::grid`]
+    pub grid: Option<AsepriteRect>,
+    /// The color profile, see [`Aseprite::color_profile`]
+    pub color_profile: Option<&'a AsepriteColorProfile>,
+    /// The user data of the sprite, see [`Aseprite::sprite_user_data`]
+    pub user_data: Option<&'a AsepriteUserData>,
+}
+
+/// The color profile of a file, see [`Aseprite::color_profile`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsepriteColorProfile {
+    /// The kind of profile
+    pub profile_type: AsepriteColorProfileType,
+    /// The fixed gamma, `None` if the file does not use one
+    pub gamma: Option<f64>,
+    /// The bytes of the embedded ICC profile, only set for [`AsepriteColorProfileType::Icc`]
+    pub icc_profile: Option<Vec<u8>>,
+}
+
+/// The kind of color profile of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AsepriteColorProfileType {
+    /// No color profile, the colors are used as they are
+    None,
+    /// sRGB
+    Srgb,
+    /// An embedded ICC profile
+    Icc,
+    /// A type this crate does not know
+    Unknown(u16),
+}
+
+/// User data attached to the sprite, see [`Aseprite::sprite_user_data`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsepriteUserData {
+    /// The text, empty if there is none
+    pub text: String,
+    /// The color, fully transparent if there is none
+    pub color: AsepriteColor,
+    /// The properties maps
+    pub properties: Vec<AsepritePropertiesMap>,
+}
+
+impl AsepriteColorProfile {
+    /// Color Profile chunk 中的数据，flags: 1 = 使用 fixed gamma
+    pub(super) fn from_raw(
+        profile_type: u16,
+        flags: u16,
+        gamma: f64,
+        icc_profile: Option<RawAsepriteIccProfile>,
+    ) -> Self {
+        AsepriteColorProfile {
+            profile_type: match profile_type {
+                0 => AsepriteColorProfileType::None,
+                1 => AsepriteColorProfileType::Srgb,
+                2 => AsepriteColorProfileType::Icc,
+                other => AsepriteColorProfileType::Unknown(other),
+            },
+            gamma: (flags & 0x1 != 0).then_some(gamma),
+            icc_profile: icc_profile.map(|icc| icc.icc_profile),
+        }
+    }
+}
+
+impl From<RawAsepriteUserData> for AsepriteUserData {
+    fn from(raw: RawAsepriteUserData) -> Self {
+        AsepriteUserData {
+            text: raw.text,
+            color: raw.color,
+            properties: raw.properties,
+        }
+    }
+}
+
+impl Aseprite {
+    /// All the document level metadata in one struct, e.g. to log it
+    pub fn info(&self) -> AsepriteDocumentInfo<'_> {
+        AsepriteDocumentInfo {
+            width: self.width(),
+            height: self.height(),
+            color_depth: self.color_depth,
+            frame_count: self.frame_count,
+            header_flags: self.header_flags,
+            pixel_ratio: self.pixel_ratio,
+            grid: self.grid,
+            color_profile: self.color_profile.as_ref(),
+            user_data: self.sprite_user_data.as_ref(),
+        }
+    }
+
+    /// The flags of the file header
+    ///
+    /// - 1 = Layer opacity has a valid value, see [`Aseprite::layer_opacity_valid`]
+    /// - 2 = Layer blend mode and opacity are valid for groups
+    /// - 4 = Layers have a UUID
+    pub fn header_flags(&self) -> u32 {
+        self.header_flags
+    }
+
+    /// Width and height of one pixel, `(1, 1)` for square pixels
+    pub fn pixel_ratio(&self) -> (u8, u8) {
+        self.pixel_ratio
+    }
+
+    /// Position of the first cell and size of the cells of the grid
+    ///
+    /// Returns `None` if the file has no grid.
+    pub fn grid(&self) -> Option<AsepriteRect> {
+        self.grid
+    }
+
+    /// The color profile of the file, `None` for files saved without one
+    pub fn color_profile(&self) -> Option<&AsepriteColorProfile> {
+        self.color_profile.as_ref()
+    }
+
+    /// The user data of the sprite itself, set in the sprite properties of Aseprite
+    pub fn sprite_user_data(&self) -> Option<&AsepriteUserData> {
+        self.sprite_user_data.as_ref()
+    }
+
+    /// Compare everything but the pixels with another file
+    ///
+    /// Documents, frames, layers, tags, slices and palettes are compared as a whole. Cels are
+    /// compared by position, opacity, size, the frame they link to and their properties. This
+    /// still works after [`Aseprite::shrink_pixels`] and does not decompress anything.
+    pub fn metadata_eq(&self, other: &Aseprite) -> bool {
+        let cels: Vec<CelEntry> = self.iter_cels().collect();
+        let other_cels: Vec<CelEntry> = other.iter_cels().collect();
+        self.info() == other.info()
+            && self.frame_infos == other.frame_infos
+            && self.default_delay_ms == other.default_delay_ms
+            && self.layer_opacity_valid == other.layer_opacity_valid
+            && self.layers == other.layers
+            && self.tags == other.tags
+            && self.slices == other.slices
+            && self.palette == other.palette
+            && self.transparent_palette == other.transparent_palette
+            && cels.len() == other_cels.len()
+            && cels.iter().zip(&other_cels).all(|(a, b)| {
+                a.layer_index == b.layer_index
+                    && a.frame_index == b.frame_index
+                    && a.cel.metadata_eq(b.cel)
+            })
+    }
+}
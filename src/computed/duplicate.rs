@@ -0,0 +1,154 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+};
+
+use image::RgbaImage;
+
+use crate::{
+    error::{AseResult, AsepriteInvalidError},
+    raw::RawAsepriteCel,
+};
+
+use super::Aseprite;
+
+/// 一个 cel 的像素来自哪个 (layer, frame)，以及它在这一帧的位置、透明度和 z-index
+type CelKey = (usize, usize, i16, i16, u8, i16);
+
+/// What a frame is made of, computed without reading pixels
+///
+/// Two frames of the same file with equal signatures render the same image: every cel shows
+/// the same pixels, through linked cels, at the same place with the same opacity and
+/// z-index. Frames with different signatures may still render the same image, e.g. when
+/// unlinked cels happen to hold the same pixels. Signatures of different files can not be
+/// compared.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FrameSignature(Vec<CelKey>);
+
+/// 按内容分组后的帧
+pub(super) struct UniqueFrames {
+    /// 每一组第一帧的图片
+    pub(super) images: Vec<RgbaImage>,
+    /// 每一帧所在的组，和传入的帧一一对应
+    pub(super) groups: Vec<usize>,
+}
+
+impl Aseprite {
+    /// Hash of the image of a frame with all visible layers merged
+    ///
+    /// Frames with the same pixels have the same hash, see [`Aseprite::duplicate_frames`].
+    /// The hash is computed once per frame and cached. It is not meant to be stored, the
+    /// hash function may change between versions.
+    pub fn frame_content_hash(&self, frame_index: usize) -> AseResult<u64> {
+        let cache = self
+            .frame_hashes
+            .get(frame_index)
+            .ok_or(AsepriteInvalidError::InvalidFrame(frame_index))?;
+        if let Some(&hash) = cache.get() {
+            return Ok(hash);
+        }
+        let image = self.get_frame_image(frame_index)?;
+        Ok(self.cache_frame_hash(frame_index, &image))
+    }
+
+    /// Group the frames showing the same pixels
+    ///
+    /// Every frame is in exactly one group, frames without duplicates have a group of their
+    /// own. Groups are sorted by their first frame. Frames with the same [`FrameSignature`] are
+    /// grouped without being rendered.
+    pub fn duplicate_frames(&self) -> AseResult<Vec<Vec<usize>>> {
+        let frames: Vec<usize> = (0..self.frame_count).collect();
+        let unique = self.unique_frames(&frames)?;
+        let mut groups = vec![Vec::new(); unique.images.len()];
+        for (frame_index, group) in unique.groups.into_iter().enumerate() {
+            groups[group].push(frame_index);
+        }
+        Ok(groups)
+    }
+
+    /// Signature of a frame, see [`FrameSignature`]
+    pub fn frame_signature(&self, frame_index: usize) -> AseResult<FrameSignature> {
+        if frame_index >= self.frame_count {
+            return Err(AsepriteInvalidError::InvalidFrame(frame_index).into());
+        }
+        Ok(self.signature(frame_index))
+    }
+
+    /// 按内容给 frames 分组，每组只渲染一次
+    /// signature 相同的帧直接归到同一组，其他帧并行渲染后按 hash 分组
+    pub(super) fn unique_frames(&self, frames: &[usize]) -> AseResult<UniqueFrames> {
+        let mut signatures = Vec::with_capacity(frames.len());
+        // 每个 signature 第一次出现的帧才需要渲染
+        let mut to_render = Vec::new();
+        let mut seen = HashSet::new();
+        for &frame_index in frames {
+            self.check_frame_loaded(frame_index)?;
+            let signature = self.signature(frame_index);
+            if seen.insert(signature.clone()) {
+                to_render.push(frame_index);
+            }
+            signatures.push(signature);
+        }
+        let mut rendered = self.render_frames(&to_render)?.into_iter();
+
+        let mut by_signature: HashMap<FrameSignature, usize> = HashMap::new();
+        // 同一个 hash 可能对应多个组，hash 冲突时比较像素
+        let mut by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+        let mut unique = UniqueFrames {
+            images: Vec::new(),
+            groups: Vec::with_capacity(frames.len()),
+        };
+        for (&frame_index, signature) in frames.iter().zip(signatures) {
+            if let Some(&group) = by_signature.get(&signature) {
+                unique.groups.push(group);
+                continue;
+            }
+            let image = rendered
+                .next()
+                .ok_or(AsepriteInvalidError::InvalidFrame(frame_index))?;
+            let hash = self.cache_frame_hash(frame_index, &image);
+            let candidates = by_hash.entry(hash).or_default();
+            let group = match candidates
+                .iter()
+                .find(|&&group| unique.images[group] == image)
+            {
+                Some(&group) => group,
+                None => {
+                    let group = unique.images.len();
+                    unique.images.push(image);
+                    candidates.push(group);
+                    group
+                }
+            };
+            by_signature.insert(signature, group);
+            unique.groups.push(group);
+        }
+        Ok(unique)
+    }
+
+    /// 这些都相同的两帧渲染结果一定相同
+    fn signature(&self, frame_index: usize) -> FrameSignature {
+        let cels = self
+            .cels
+            .iter()
+            .filter_map(|(layer_index, layer_cels)| {
+                let cel = layer_cels.get(frame_index)?;
+                let source = match cel.raw_cel {
+                    RawAsepriteCel::Linked { frame_position } => frame_position as usize,
+                    _ => frame_index,
+                };
+                Some((layer_index, source, cel.x, cel.y, cel.opacity, cel.z_index))
+            })
+            .collect();
+        FrameSignature(cels)
+    }
+
+    fn cache_frame_hash(&self, frame_index: usize, image: &RgbaImage) -> u64 {
+        let cache = &self.frame_hashes[frame_index];
+        *cache.get_or_init(|| {
+            let mut hasher = DefaultHasher::new();
+            image.as_raw().hash(&mut hasher);
+            hasher.finish()
+        })
+    }
+}
@@ -0,0 +1,211 @@
+use std::{collections::HashMap, io::Write};
+
+use image::RgbaImage;
+
+use crate::{error::AseResult, raw::AsepriteColor};
+
+use super::{Aseprite, AsepritePalette, AsepriteTag};
+
+/// GIF 的 LZW 编码最多使用 12 位的 code
+const MAX_CODE: u16 = (1 << 12) - 1;
+
+impl Aseprite {
+    /// Export the animation of a tag as an animated GIF
+    ///
+    /// Frames are played in the direction of the tag with their own delays, rounded to the
+    /// centiseconds GIF uses. Consecutive identical frames are written once with their delays
+    /// added up. The tag repeat count is kept, 0 loops forever.
+    ///
+    /// GIF only has 255 colors and binary transparency: pixels with an alpha below 128 are
+    /// transparent, and if the frames use more colors the most common ones are kept and the
+    /// other pixels use the closest of them.
+    pub fn export_gif<W: Write>(&self, tag: &AsepriteTag, mut writer: W) -> AseResult<()> {
+        let sequence: Vec<usize> = tag.frame_sequence_iter().map(usize::from).collect();
+        let mut unique = self.unique_frames(&sequence)?;
+        for image in &mut unique.images {
+            for pixel in image.pixels_mut() {
+                pixel.0[3] = if pixel.0[3] < 128 { 0 } else { 255 };
+            }
+        }
+        // 连续的相同帧合并成一帧，延迟相加
+        let mut frames: Vec<(usize, u32)> = Vec::new();
+        for (&frame_index, &group) in sequence.iter().zip(&unique.groups) {
+            let delay = self.frame_infos[frame_index].delay_ms as u32;
+            match frames.last_mut() {
+                Some((last, total)) if *last == group => *total += delay,
+                _ => frames.push((group, delay)),
+            }
+        }
+
+        let palette = gif_palette(unique.images.iter());
+        // color table 的大小必须是 2 的幂，至少 4 种颜色，方便 LZW 的最小 code 长度取 2
+        let table_bits = (palette.len().next_power_of_two().trailing_zeros() as u8).max(2);
+
+        let (width, height) = self.dimensions;
+        let mut gif = Vec::new();
+        gif.extend_from_slice(b"GIF89a");
+        gif.extend_from_slice(&width.to_le_bytes());
+        gif.extend_from_slice(&height.to_le_bytes());
+        // 使用全局 color table，颜色精度 8 位
+        gif.extend_from_slice(&[0xF0 | (table_bits - 1), 0, 0]);
+        for index in 0..1usize << table_bits {
+            let color = palette.get(index).unwrap_or_default();
+            gif.extend_from_slice(&[color.red, color.green, color.blue]);
+        }
+        // 只播放一次时不写 NETSCAPE 扩展，它的循环次数不包括第一次播放
+        if tag.repeat != 1 {
+            let loops = tag.repeat.saturating_sub(1);
+            gif.extend_from_slice(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01");
+            gif.extend_from_slice(&loops.to_le_bytes());
+            gif.push(0);
+        }
+
+        for &(group, delay_ms) in &frames {
+            let delay = ((delay_ms + 5) / 10).min(u16::MAX as u32) as u16;
+            // 每一帧都是完整的画布，显示下一帧前恢复成背景，透明色是 index 0
+            gif.extend_from_slice(&[0x21, 0xF9, 0x04, (2 << 2) | 1]);
+            gif.extend_from_slice(&delay.to_le_bytes());
+            gif.extend_from_slice(&[0, 0]);
+
+            gif.push(0x2C);
+            gif.extend_from_slice(&[0, 0, 0, 0]);
+            gif.extend_from_slice(&width.to_le_bytes());
+            gif.extend_from_slice(&height.to_le_bytes());
+            gif.push(0);
+
+            gif.push(table_bits);
+            let data = lzw_encode(&palette.quantize_image(&unique.images[group]), table_bits);
+            for block in data.chunks(255) {
+                gif.push(block.len() as u8);
+                gif.extend_from_slice(block);
+            }
+            gif.push(0);
+        }
+        gif.push(0x3B);
+
+        writer.write_all(&gif)?;
+        Ok(())
+    }
+}
+
+/// index 0 是透明色，后面是出现次数最多的颜色，次数相同时按颜色排序保证结果稳定
+fn gif_palette<'a>(images: impl Iterator<Item = &'a RgbaImage>) -> AsepritePalette {
+    let mut counts: HashMap<[u8; 4], usize> = HashMap::new();
+    for image in images {
+        for pixel in image.pixels().filter(|pixel| pixel.0[3] != 0) {
+            *counts.entry(pixel.0).or_default() += 1;
+        }
+    }
+    let mut colors: Vec<([u8; 4], usize)> = counts.into_iter().collect();
+    colors.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+
+    let mut entries = vec![AsepriteColor::default()];
+    entries.extend(
+        colors
+            .into_iter()
+            .take(255)
+            .map(|([red, green, blue, alpha], _)| AsepriteColor {
+                red,
+                green,
+                blue,
+                alpha,
+            }),
+    );
+    AsepritePalette { entries }
+}
+
+/// 变长 code 的 GIF LZW 编码，code 从低位开始写入
+pub(super) fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let mut encoder = LzwEncoder::new(min_code_size);
+    let mut pixels = indices.iter();
+    if let Some(&first) = pixels.next() {
+        let mut code = first as u16;
+        for &index in pixels {
+            if let Some(&known) = encoder.table.get(&(code, index)) {
+                code = known;
+                continue;
+            }
+            encoder.writer.write(code, encoder.width);
+            if encoder.advance() {
+                encoder.table.insert((code, index), encoder.next);
+            }
+            code = index as u16;
+        }
+        encoder.writer.write(code, encoder.width);
+        encoder.advance();
+    }
+    let end = encoder.clear + 1;
+    encoder.writer.write(end, encoder.width);
+    encoder.writer.finish()
+}
+
+struct LzwEncoder {
+    writer: BitWriter,
+    /// (前缀 code, 下一个 index) 到 code 的字典
+    table: HashMap<(u16, u8), u16>,
+    min_code_size: u8,
+    clear: u16,
+    /// 当前 code 的位数
+    width: u8,
+    /// 最后分配的 code
+    next: u16,
+}
+
+impl LzwEncoder {
+    fn new(min_code_size: u8) -> Self {
+        let clear = 1 << min_code_size;
+        let mut writer = BitWriter::default();
+        writer.write(clear, min_code_size + 1);
+        LzwEncoder {
+            writer,
+            table: HashMap::new(),
+            min_code_size,
+            clear,
+            width: min_code_size + 1,
+            next: clear + 1,
+        }
+    }
+
+    /// 分配下一个 code，和解码器同步：新 code 超出当前位数时加一位，code 用完时清空字典
+    /// 返回 false 表示字典被清空了
+    fn advance(&mut self) -> bool {
+        self.next += 1;
+        if self.next == 1 << self.width {
+            self.width += 1;
+        }
+        if self.next == MAX_CODE {
+            self.writer.write(self.clear, self.width);
+            self.table.clear();
+            self.width = self.min_code_size + 1;
+            self.next = self.clear + 1;
+            return false;
+        }
+        true
+    }
+}
+
+#[derive(Default)]
+struct BitWriter {
+    output: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += width;
+        while self.bits >= 8 {
+            self.output.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.output.push(self.buffer as u8);
+        }
+        self.output
+    }
+}
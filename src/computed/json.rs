@@ -0,0 +1,373 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Write},
+};
+
+use crate::raw::{AsepriteAnimationDirection, AsepriteBlendMode, AsepriteColor};
+
+use super::{
+    Aseprite, AsepriteLayer, AsepriteRect, JsonExportOptions, JsonFrameFormat, SpritesheetFrame,
+    SpritesheetMeta,
+};
+
+/// 导出用的 JSON 值，object 保留插入的顺序
+#[derive(Debug)]
+enum Json {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn object<K: Into<String>>(entries: impl IntoIterator<Item = (K, Json)>) -> Json {
+        Json::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.into(), value))
+                .collect(),
+        )
+    }
+
+    fn string(value: impl Into<String>) -> Json {
+        Json::String(value.into())
+    }
+
+    /// `{ "x", "y", "w", "h" }`
+    fn rect(rect: AsepriteRect) -> Json {
+        Json::object([
+            ("x", Json::Int(rect.x as i64)),
+            ("y", Json::Int(rect.y as i64)),
+            ("w", Json::Int(rect.width as i64)),
+            ("h", Json::Int(rect.height as i64)),
+        ])
+    }
+
+    /// `{ "w", "h" }`
+    fn size(width: u32, height: u32) -> Json {
+        Json::object([
+            ("w", Json::Int(width as i64)),
+            ("h", Json::Int(height as i64)),
+        ])
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Int(value) => write!(f, "{}", value),
+            Json::Float(value) => write!(f, "{}", value),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                f.write_char('[')?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(entries) => {
+                f.write_char('{')?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// 两种格式共用的帧信息，extra 返回每种格式额外的字段
+fn sheet_frames<F>(sheet: &SpritesheetMeta, options: &JsonExportOptions, extra: F) -> Json
+where
+    F: Fn(&SpritesheetFrame) -> Vec<(&'static str, Json)>,
+{
+    let (source_width, source_height) = sheet.source_size;
+    let mut names = HashSet::new();
+    let frames = sheet.frames.iter().map(|frame| {
+        let index = frame.frame_index.to_string();
+        let mut name = options.frame_name.replace("{frame}", &index);
+        // 名字里没有 {frame} 时每一帧同名，hash 中的 key 会重复
+        while !names.insert(name.clone()) {
+            name = format!("{} {}", name, index);
+        }
+        let trimmed = frame.rect.width != source_width || frame.rect.height != source_height;
+        let sprite_source = AsepriteRect::new(
+            frame.offset.0 as i32,
+            frame.offset.1 as i32,
+            frame.rect.width,
+            frame.rect.height,
+        );
+        let mut value = vec![
+            ("frame".to_string(), Json::rect(frame.rect)),
+            ("rotated".to_string(), Json::Bool(false)),
+            ("trimmed".to_string(), Json::Bool(trimmed)),
+            ("spriteSourceSize".to_string(), Json::rect(sprite_source)),
+            (
+                "sourceSize".to_string(),
+                Json::size(source_width, source_height),
+            ),
+        ];
+        value.extend(
+            extra(frame)
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value)),
+        );
+        (name, value)
+    });
+    match options.format {
+        JsonFrameFormat::Hash => Json::object(
+            frames
+                .map(|(name, value)| (name, Json::Object(value)))
+                .collect::<Vec<_>>(),
+        ),
+        JsonFrameFormat::Array => Json::Array(
+            frames
+                .map(|(name, mut value)| {
+                    value.insert(0, ("filename".to_string(), Json::String(name)));
+                    Json::Object(value)
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Aseprite 导出的颜色格式 `#rrggbbaa`
+fn color(color: &AsepriteColor) -> Json {
+    Json::String(format!(
+        "#{:02x}{:02x}{:02x}{:02x}",
+        color.red, color.green, color.blue, color.alpha
+    ))
+}
+
+fn direction(direction: AsepriteAnimationDirection) -> &'static str {
+    match direction {
+        AsepriteAnimationDirection::Forward => "forward",
+        AsepriteAnimationDirection::Reverse => "reverse",
+        AsepriteAnimationDirection::PingPong => "pingpong",
+        AsepriteAnimationDirection::PingPongReverse => "pingpong_reverse",
+    }
+}
+
+fn blend_mode(blend_mode: AsepriteBlendMode) -> &'static str {
+    match blend_mode {
+        AsepriteBlendMode::Normal => "normal",
+        AsepriteBlendMode::Multiply => "multiply",
+        AsepriteBlendMode::Screen => "screen",
+        AsepriteBlendMode::Overlay => "overlay",
+        AsepriteBlendMode::Darken => "darken",
+        AsepriteBlendMode::Lighten => "lighten",
+        AsepriteBlendMode::ColorDodge => "color_dodge",
+        AsepriteBlendMode::ColorBurn => "color_burn",
+        AsepriteBlendMode::HardLight => "hard_light",
+        AsepriteBlendMode::SoftLight => "soft_light",
+        AsepriteBlendMode::Difference => "difference",
+        AsepriteBlendMode::Exclusion => "exclusion",
+        AsepriteBlendMode::Hue => "hsl_hue",
+        AsepriteBlendMode::Saturation => "hsl_saturation",
+        AsepriteBlendMode::Color => "hsl_color",
+        AsepriteBlendMode::Luminosity => "hsl_luminosity",
+        AsepriteBlendMode::Addition => "addition",
+        AsepriteBlendMode::Subtract => "subtract",
+        AsepriteBlendMode::Divide => "divide",
+    }
+}
+
+impl Aseprite {
+    /// Export the metadata of a spritesheet in the JSON format of `aseprite --data`
+    ///
+    /// `sheet` comes from [`Aseprite::to_spritesheet`], the frame rects point into that sheet.
+    /// `meta` holds the tags, the layers with the group they belong to and the slices. The
+    /// JSON is written without any whitespace.
+    ///
+    /// Frames with an anchor, see [`Aseprite::anchor`] and [`JsonExportOptions::anchor_slices`],
+    /// also get an `anchor` with its position on the canvas. Aseprite itself does not write it.
+    pub fn export_json(&self, sheet: &SpritesheetMeta, options: &JsonExportOptions) -> String {
+        let frames = sheet_frames(sheet, options, |frame| {
+            let mut value = vec![("duration", Json::Int(frame.duration.as_millis() as i64))];
+            if let Some((x, y)) = self.anchor(frame.frame_index, &options.anchor_slices) {
+                value.push((
+                    "anchor",
+                    Json::object([("x", Json::Int(x as i64)), ("y", Json::Int(y as i64))]),
+                ));
+            }
+            value
+        });
+
+        let tags = self
+            .tags()
+            .map(|tag| {
+                let mut value = vec![
+                    ("name", Json::string(&tag.name)),
+                    ("from", Json::Int(tag.frames.start as i64)),
+                    // Aseprite 里的 to 是包含在内的最后一帧
+                    ("to", Json::Int(tag.frames.end as i64 - 1)),
+                    (
+                        "direction",
+                        Json::string(direction(tag.animation_direction)),
+                    ),
+                    ("color", color(&tag.color)),
+                ];
+                if tag.repeat != 0 {
+                    value.push(("repeat", Json::String(tag.repeat.to_string())));
+                }
+                if !tag.user_data.is_empty() {
+                    value.push(("data", Json::string(&tag.user_data)));
+                }
+                Json::object(value)
+            })
+            .collect();
+
+        let layers = self
+            .layers()
+            .map(|layer| {
+                let mut value = vec![("name", Json::string(layer.name()))];
+                if let Some(group) = self
+                    .find_layer_belong_groups(layer.index())
+                    .first()
+                    .and_then(|&group| self.layers.get(&group))
+                {
+                    value.push(("group", Json::string(group.name())));
+                }
+                if let AsepriteLayer::Normal(..) = layer {
+                    value.push(("opacity", Json::Int(layer.opacity().unwrap_or(255) as i64)));
+                    value.push(("blendMode", Json::string(blend_mode(layer.blend_mode()))));
+                }
+                if !layer.user_data().is_empty() {
+                    value.push(("data", Json::string(layer.user_data())));
+                }
+                Json::object(value)
+            })
+            .collect();
+
+        let slices = self
+            .slices()
+            .map(|slice| {
+                let keys = slice
+                    .keys
+                    .iter()
+                    .map(|key| {
+                        let mut value = vec![
+                            ("frame", Json::Int(key.from_frame as i64)),
+                            ("bounds", Json::rect(key.bounds)),
+                        ];
+                        if let Some(info) = &key.nine_patch_info {
+                            let center = AsepriteRect::new(
+                                info.x_center,
+                                info.y_center,
+                                info.width,
+                                info.height,
+                            );
+                            value.push(("center", Json::rect(center)));
+                        }
+                        if let Some(pivot) = &key.pivot {
+                            value.push((
+                                "pivot",
+                                Json::object([
+                                    ("x", Json::Int(pivot.x_pivot as i64)),
+                                    ("y", Json::Int(pivot.y_pivot as i64)),
+                                ]),
+                            ));
+                        }
+                        Json::object(value)
+                    })
+                    .collect();
+                let mut value = vec![
+                    ("name", Json::string(&slice.name)),
+                    ("color", color(&slice.color)),
+                ];
+                if !slice.user_data.is_empty() {
+                    value.push(("data", Json::string(&slice.user_data)));
+                }
+                value.push(("keys", Json::Array(keys)));
+                Json::object(value)
+            })
+            .collect();
+
+        let meta = Json::object([
+            ("app", Json::string("https://www.aseprite.org/")),
+            ("version", Json::string("1.3")),
+            ("image", Json::string(&options.image)),
+            ("format", Json::string("RGBA8888")),
+            ("size", Json::size(sheet.size.0, sheet.size.1)),
+            ("scale", Json::string("1")),
+            ("frameTags", Json::Array(tags)),
+            ("layers", Json::Array(layers)),
+            ("slices", Json::Array(slices)),
+        ]);
+        Json::object([("frames", frames), ("meta", meta)]).to_string()
+    }
+
+    /// Export the metadata of a spritesheet in the JSON format of TexturePacker
+    ///
+    /// `sheet` comes from [`Aseprite::to_spritesheet`]. The formats map as follows:
+    ///
+    /// - `frame` is the rect in the sheet, `spriteSourceSize` the same rect placed on the
+    ///   canvas and `sourceSize` the canvas size. All of them use a top-left origin.
+    /// - Frames are never rotated, `rotated` is always `false`.
+    /// - `pivot` is normalized to the canvas, `(0, 0)` being the top-left corner and `(1, 1)`
+    ///   the bottom-right one, whatever the trimming. It is the anchor of the frame, see
+    ///   [`Aseprite::anchor`] and [`JsonExportOptions::anchor_slices`]. Frames without an
+    ///   anchor use the center of the canvas, `(0.5, 0.5)`.
+    pub fn export_texture_packer_json(
+        &self,
+        sheet: &SpritesheetMeta,
+        options: &JsonExportOptions,
+    ) -> String {
+        let (source_width, source_height) = sheet.source_size;
+        let frames = sheet_frames(sheet, options, |frame| {
+            let (x, y) = self
+                .anchor(frame.frame_index, &options.anchor_slices)
+                .map_or(
+                    (source_width as f64 / 2.0, source_height as f64 / 2.0),
+                    |(x, y)| (x as f64, y as f64),
+                );
+            let normalize =
+                |value: f64, size: u32| if size == 0 { 0.0 } else { value / size as f64 };
+            vec![(
+                "pivot",
+                Json::object([
+                    ("x", Json::Float(normalize(x, source_width))),
+                    ("y", Json::Float(normalize(y, source_height))),
+                ]),
+            )]
+        });
+        let meta = Json::object([
+            (
+                "app",
+                Json::string("https://www.codeandweb.com/texturepacker"),
+            ),
+            ("version", Json::string("1.0")),
+            ("image", Json::string(&options.image)),
+            ("format", Json::string("RGBA8888")),
+            ("size", Json::size(sheet.size.0, sheet.size.1)),
+            ("scale", Json::string("1")),
+        ]);
+        Json::object([("frames", frames), ("meta", meta)]).to_string()
+    }
+}
@@ -1,103 +1,106 @@
-use crate::raw::{AsepriteBlendMode, AsepriteColor, AsepriteLayerType, RawAsepriteUserData};
+use crate::raw::{
+    AsepriteBlendMode, AsepriteColor, AsepriteLayerType, AsepritePropertiesMap, RawAsepriteUserData,
+};
 use std::collections::BTreeMap;
 
 /// Represent a layer, either layer or group
 #[derive(Debug, Clone)]
 pub enum LayerTreeNode<'a> {
-    ///
+    /// A group layer and its children, keyed by layer index
     Group(&'a GroupLayer, BTreeMap<usize, LayerTreeNode<'a>>),
-    ///
+    /// A normal layer
     Normal(&'a NormalLayer),
 }
 
 impl<'a> LayerTreeNode<'a> {
-    ///
+    /// Whether this node is a group layer
     pub fn is_group(&self) -> bool {
         match self {
             LayerTreeNode::Group(..) => true,
             LayerTreeNode::Normal(..) => false,
         }
     }
-    ///
+    /// Whether this node is a normal layer
     pub fn is_normal(&self) -> bool {
         match self {
             LayerTreeNode::Group(..) => false,
             LayerTreeNode::Normal(..) => true,
         }
     }
-    ///
+    /// Get the name of the layer
     pub fn name(&self) -> &'a str {
         match self {
             LayerTreeNode::Group(layer, _) => &layer.name,
             LayerTreeNode::Normal(layer) => &layer.name,
         }
     }
+    /// Get the index of the layer
+    pub fn index(&self) -> usize {
+        match self {
+            LayerTreeNode::Group(layer, _) => layer.index,
+            LayerTreeNode::Normal(layer) => layer.index,
+        }
+    }
+    /// Get the direct children of a group, in index order
+    ///
+    /// A normal layer has no children.
+    pub fn children(&self) -> impl Iterator<Item = &LayerTreeNode<'a>> {
+        let children = match self {
+            LayerTreeNode::Group(_, children) => Some(children.values()),
+            LayerTreeNode::Normal(..) => None,
+        };
+        children.into_iter().flatten()
+    }
+    /// Visit this node and all of its descendants depth-first
+    ///
+    /// The callback also receives the depth relative to this node, which starts at 0.
+    /// Nodes are visited in index order, i.e. Aseprite's bottom-to-top layer order.
+    pub fn walk<F: FnMut(&LayerTreeNode<'a>, usize)>(&self, mut f: F) {
+        self.walk_inner(&mut f, 0);
+    }
+
+    fn walk_inner(&self, f: &mut dyn FnMut(&LayerTreeNode<'a>, usize), depth: usize) {
+        f(self, depth);
+        for child in self.children() {
+            child.walk_inner(f, depth + 1);
+        }
+    }
 }
 
-///
+/// Build the layer hierarchy from layers ordered by index
 pub fn build_layer_tree<'a>(
     layers: impl Iterator<Item = &'a AsepriteLayer>,
 ) -> BTreeMap<usize, LayerTreeNode<'a>> {
     let mut tree: BTreeMap<usize, LayerTreeNode<'a>> = BTreeMap::new();
 
     for layer in layers {
-        match layer {
-            AsepriteLayer::Group(group_layer) => {
-                let tree = if group_layer.child_level == 0 {
-                    &mut tree
-                } else {
-                    let mut cur_child_level = group_layer.child_level;
-                    let mut cur_tree = &mut tree;
-                    while cur_child_level > 0 {
-                        let (&last_key, _) = cur_tree.last_key_value().unwrap();
-                        let tree = cur_tree.get_mut(&last_key).unwrap();
-                        match tree {
-                            LayerTreeNode::Group(_, inner_tree) => {
-                                cur_tree = inner_tree;
-                            }
-                            _ => {
-                                unreachable!()
-                            }
-                        }
-                        cur_child_level -= 1;
-                    }
-                    cur_tree
-                };
-                tree.insert(
-                    group_layer.index,
-                    LayerTreeNode::Group(group_layer, BTreeMap::new()),
-                );
-            }
-            AsepriteLayer::Normal(normal_layer) => {
-                let tree = if normal_layer.child_level == 0 {
-                    &mut tree
-                } else {
-                    let mut cur_child_level = normal_layer.child_level;
-                    let mut cur_tree = &mut tree;
-                    while cur_child_level > 0 {
-                        let (&last_key, _) = cur_tree.last_key_value().unwrap();
-                        let tree = cur_tree.get_mut(&last_key).unwrap();
-                        match tree {
-                            LayerTreeNode::Group(_, inner_tree) => {
-                                cur_tree = inner_tree;
-                            }
-                            _ => {
-                                unreachable!()
-                            }
-                        }
-                        cur_child_level -= 1;
-                    }
-                    cur_tree
-                };
-                tree.insert(normal_layer.index, LayerTreeNode::Normal(normal_layer));
+        // 根据 child level 找到父 group 所在的子树
+        // 格式有误、找不到父 group 时，挂在能找到的最深的一层下面
+        let mut cur_tree = &mut tree;
+        for _ in 0..layer.child_level() {
+            let parent_is_group = matches!(
+                cur_tree.values().next_back(),
+                Some(LayerTreeNode::Group(..))
+            );
+            if !parent_is_group {
+                break;
             }
+            cur_tree = match cur_tree.values_mut().next_back() {
+                Some(LayerTreeNode::Group(_, inner_tree)) => inner_tree,
+                _ => unreachable!(),
+            };
         }
+        let node = match layer {
+            AsepriteLayer::Group(group_layer) => LayerTreeNode::Group(group_layer, BTreeMap::new()),
+            AsepriteLayer::Normal(normal_layer) => LayerTreeNode::Normal(normal_layer),
+        };
+        cur_tree.insert(layer.index(), node);
     }
 
     tree
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 /// An aseprite layer
 pub enum AsepriteLayer {
     /// A layer group
@@ -107,7 +110,7 @@ pub enum AsepriteLayer {
 }
 
 /// 表示图层组
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct GroupLayer {
     /// Name of the layer
     pub name: String,
@@ -115,18 +118,28 @@ pub struct GroupLayer {
     ///
     /// 所有 Layer Chunk 都是存储在一起的，这是按照 Layer Chunk 存储的顺序来决定，从 0 开始，对应了 aseprite 文件中从下到上的 layer 顺序
     pub index: usize,
+    /// Opacity of the layer (if enabled)
+    ///
+    /// Older files don't store the opacity of groups.
+    pub opacity: Option<u8>,
     /// Visibility of the layer
     pub visible: bool,
+    /// Raw layer flags, see [`AsepriteLayer::flags`]
+    pub flags: u16,
+    /// UUID of the layer, only stored by files saved with Aseprite 1.3 and later
+    pub uuid: Option<[u8; 16]>,
     /// How deep it is nested in the layer hierarchy
     pub child_level: u16,
     /// Layer color
     pub color: AsepriteColor,
     /// Layer user data
     pub user_data: String,
+    /// Layer user data properties
+    pub properties: Vec<AsepritePropertiesMap>,
 }
 
 /// 表示普通图层
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct NormalLayer {
     /// Name of the layer
     pub name: String,
@@ -140,24 +153,39 @@ pub struct NormalLayer {
     pub opacity: Option<u8>,
     /// Visibility of this layer
     pub visible: bool,
+    /// Raw layer flags, see [`AsepriteLayer::flags`]
+    pub flags: u16,
+    /// UUID of the layer, only stored by files saved with Aseprite 1.3 and later
+    pub uuid: Option<[u8; 16]>,
     /// How deep it is nested in the layer hierarchy
     pub child_level: u16,
     /// Layer color
     pub color: AsepriteColor,
     /// Layer user data
     pub user_data: String,
+    /// Layer user data properties
+    pub properties: Vec<AsepritePropertiesMap>,
 }
 
 impl AsepriteLayer {
+    const FLAG_VISIBLE: u16 = 1;
+    const FLAG_EDITABLE: u16 = 2;
+    const FLAG_LOCK_MOVEMENT: u16 = 4;
+    const FLAG_BACKGROUND: u16 = 8;
+    const FLAG_CONTINUOUS: u16 = 16;
+    const FLAG_COLLAPSED: u16 = 32;
+    const FLAG_REFERENCE: u16 = 64;
+
     pub(super) fn new(
         index: usize,
         name: String,
         layer_type: AsepriteLayerType,
-        visible: bool,
+        flags: u16,
         blend_mode: AsepriteBlendMode,
         opacity: Option<u8>,
         child_level: u16,
     ) -> Self {
+        let visible = flags & Self::FLAG_VISIBLE != 0;
         match layer_type {
             AsepriteLayerType::Normal => AsepriteLayer::Normal(NormalLayer {
                 name,
@@ -165,17 +193,24 @@ impl AsepriteLayer {
                 blend_mode,
                 opacity,
                 visible,
+                flags,
+                uuid: None,
                 child_level,
                 color: AsepriteColor::default(),
                 user_data: String::new(),
+                properties: Vec::new(),
             }),
             AsepriteLayerType::Group => AsepriteLayer::Group(GroupLayer {
                 name,
                 index,
+                opacity,
                 visible,
+                flags,
+                uuid: None,
                 child_level,
                 color: AsepriteColor::default(),
                 user_data: String::new(),
+                properties: Vec::new(),
             }),
         }
     }
@@ -184,7 +219,7 @@ impl AsepriteLayer {
     pub fn name(&self) -> &str {
         match self {
             AsepriteLayer::Group(GroupLayer { name, .. })
-            | AsepriteLayer::Normal(NormalLayer { name, .. }) => &name,
+            | AsepriteLayer::Normal(NormalLayer { name, .. }) => name,
         }
     }
 
@@ -204,6 +239,71 @@ impl AsepriteLayer {
         }
     }
 
+    /// Get the UUID of the layer
+    ///
+    /// Unlike the index, it stays the same when layers are reordered. Only files saved
+    /// with Aseprite 1.3 and later store it.
+    pub fn uuid(&self) -> Option<[u8; 16]> {
+        match self {
+            AsepriteLayer::Group(GroupLayer { uuid, .. })
+            | AsepriteLayer::Normal(NormalLayer { uuid, .. }) => *uuid,
+        }
+    }
+
+    pub(super) fn with_uuid(mut self, value: Option<[u8; 16]>) -> Self {
+        match &mut self {
+            AsepriteLayer::Group(GroupLayer { uuid, .. })
+            | AsepriteLayer::Normal(NormalLayer { uuid, .. }) => *uuid = value,
+        }
+        self
+    }
+
+    /// Get the raw layer flags
+    ///
+    /// - 1 = Visible
+    /// - 2 = Editable
+    /// - 4 = Lock movement
+    /// - 8 = Background
+    /// - 16 = Prefer linked cels
+    /// - 32 = The layer group should be displayed collapsed
+    /// - 64 = The layer is a reference layer
+    pub fn flags(&self) -> u16 {
+        match self {
+            AsepriteLayer::Group(GroupLayer { flags, .. })
+            | AsepriteLayer::Normal(NormalLayer { flags, .. }) => *flags,
+        }
+    }
+
+    /// Whether this is the background layer
+    pub fn is_background(&self) -> bool {
+        self.flags() & Self::FLAG_BACKGROUND != 0
+    }
+
+    /// Whether this is a reference layer, which Aseprite never exports
+    pub fn is_reference(&self) -> bool {
+        self.flags() & Self::FLAG_REFERENCE != 0
+    }
+
+    /// Whether new cels in this layer are linked to the previous ones ("Continuous" in Aseprite)
+    pub fn is_continuous(&self) -> bool {
+        self.flags() & Self::FLAG_CONTINUOUS != 0
+    }
+
+    /// Whether this group is displayed collapsed
+    pub fn is_collapsed(&self) -> bool {
+        self.flags() & Self::FLAG_COLLAPSED != 0
+    }
+
+    /// Whether the layer is locked, i.e. not editable
+    pub fn is_locked(&self) -> bool {
+        self.flags() & Self::FLAG_EDITABLE == 0
+    }
+
+    /// Whether the layer can't be moved
+    pub fn is_movement_locked(&self) -> bool {
+        self.flags() & Self::FLAG_LOCK_MOVEMENT != 0
+    }
+
     /// Get child level of the layer
     pub fn child_level(&self) -> u16 {
         match self {
@@ -220,36 +320,51 @@ impl AsepriteLayer {
         }
     }
 
-    /// Get opacity of normal layer
+    /// Get opacity of the layer
+    ///
+    /// Normal layers of files without the "layer opacity valid" header flag return 255.
+    /// Groups return `None` unless the header marks group opacity as valid.
     pub fn opacity(&self) -> Option<u8> {
         match self {
-            AsepriteLayer::Group(..) => None,
-            AsepriteLayer::Normal(NormalLayer { opacity, .. }) => *opacity,
+            AsepriteLayer::Group(GroupLayer { opacity, .. })
+            | AsepriteLayer::Normal(NormalLayer { opacity, .. }) => *opacity,
         }
     }
 
     pub(super) fn apply_raw_user_data(&mut self, value: RawAsepriteUserData) {
         match self {
             AsepriteLayer::Group(GroupLayer {
-                color, user_data, ..
-            }) => {
-                *color = value.color;
-                *user_data = value.text;
-            }
-            AsepriteLayer::Normal(NormalLayer {
-                color, user_data, ..
+                color,
+                user_data,
+                properties,
+                ..
+            })
+            | AsepriteLayer::Normal(NormalLayer {
+                color,
+                user_data,
+                properties,
+                ..
             }) => {
                 *color = value.color;
                 *user_data = value.text;
+                *properties = value.properties;
             }
         }
     }
 
     /// Get user data of the layer
     pub fn user_data(&self) -> &str {
-        return match self {
+        match self {
             AsepriteLayer::Group(GroupLayer { user_data, .. }) => user_data.as_str(),
             AsepriteLayer::Normal(NormalLayer { user_data, .. }) => user_data.as_str(),
-        };
+        }
+    }
+
+    /// Get the user data properties maps of the layer
+    pub fn properties(&self) -> &[AsepritePropertiesMap] {
+        match self {
+            AsepriteLayer::Group(GroupLayer { properties, .. })
+            | AsepriteLayer::Normal(NormalLayer { properties, .. }) => properties,
+        }
     }
 }
@@ -0,0 +1,152 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use image::RgbaImage;
+
+use crate::error::{AseResult, AsepriteError};
+
+use super::{Aseprite, AsepriteLayer, GroupExport, LayerExportOptions, TagPriority};
+
+impl Aseprite {
+    /// Render every layer across all frames into separate files
+    ///
+    /// The path of each file is `template` relative to `dir`, with these placeholders:
+    /// - `{layer}`: the layer name
+    /// - `{layer_path}`: the names of the groups of the layer and its own name, as directories
+    /// - `{frame}`: the frame index, starting at 0. Like Aseprite, `{frame1}` starts at 1 and
+    ///   `{frame001}` also pads the number to 3 digits
+    /// - `{tag}`: the name of the first tag covering the frame, empty if there is none
+    ///
+    /// Names are sanitized for the filesystem, path separators and characters Windows refuses
+    /// become `_`. Images are in canvas size and the format follows the file extension, a
+    /// frame without a cel gives a transparent image. Missing directories are created.
+    ///
+    /// Every path is computed before anything is written: if two images would get the same
+    /// path, e.g. for layers named `A/B` and `A_B`, [`AsepriteError::ExportPathCollision`] is
+    /// returned and no file is written. Returns the written paths in layer then frame order.
+    pub fn export_layers<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        template: &str,
+        options: &LayerExportOptions,
+    ) -> AseResult<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        let layers: Vec<&AsepriteLayer> = self
+            .layers
+            .values()
+            .filter(|layer| {
+                options.include_hidden || self.is_layer_effectively_visible(layer.index())
+            })
+            .filter(|layer| match layer {
+                AsepriteLayer::Group(_) => options.groups == GroupExport::Merged,
+                AsepriteLayer::Normal(_) => true,
+            })
+            .collect();
+
+        let mut exports = Vec::with_capacity(layers.len() * self.frame_count);
+        let mut seen = HashSet::new();
+        for layer in &layers {
+            let mut path_names: Vec<String> = self
+                .find_layer_belong_groups(layer.index())
+                .into_iter()
+                .rev()
+                .filter_map(|index| Some(sanitize_file_name(self.layers.get(&index)?.name())))
+                .collect();
+            path_names.push(sanitize_file_name(layer.name()));
+            let layer_path = path_names.join("/");
+            for frame_index in 0..self.frame_count {
+                let tag = self
+                    .primary_tag_for_frame(frame_index, TagPriority::FirstByIndex)
+                    .map(|tag| sanitize_file_name(&tag.name))
+                    .unwrap_or_default();
+                let path = dir.join(fill_template(template, |placeholder| {
+                    Some(match placeholder {
+                        "layer" => sanitize_file_name(layer.name()),
+                        "layer_path" => layer_path.clone(),
+                        "tag" => tag.clone(),
+                        _ => frame_number(placeholder, frame_index)?,
+                    })
+                }));
+                if !seen.insert(path.clone()) {
+                    return Err(AsepriteError::ExportPathCollision(path));
+                }
+                exports.push((layer.index(), frame_index, path));
+            }
+        }
+
+        let (width, height) = self.dimensions;
+        let results = self.map_rendering(&exports, |(layer_index, frame_index, path)| {
+            let image = match self.layers.get(layer_index) {
+                Some(AsepriteLayer::Group(_)) => {
+                    self.get_group_image(*layer_index, *frame_index)?
+                }
+                _ => self.get_image_by_layer_frame_on_canvas(*layer_index, *frame_index)?,
+            }
+            .unwrap_or_else(|| RgbaImage::new(width as u32, height as u32));
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            image
+                .save(path)
+                .map_err(|err| AsepriteError::Encode(err.to_string()))
+        });
+        results.into_iter().collect::<AseResult<()>>()?;
+        Ok(exports.into_iter().map(|(_, _, path)| path).collect())
+    }
+}
+
+/// 替换模板中的 `{name}`，replace 返回 None 时保留原样
+fn fill_template<F: Fn(&str) -> Option<String>>(template: &str, replace: F) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        match replace(&rest[1..end]) {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// `frame` 从 0 开始，`frame1` 从 1 开始，`frame001` 从 1 开始并补齐到 3 位
+fn frame_number(placeholder: &str, frame_index: usize) -> Option<String> {
+    let digits = placeholder.strip_prefix("frame")?;
+    if digits.is_empty() {
+        return Some(frame_index.to_string());
+    }
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let start: usize = digits.parse().ok()?;
+    Some(format!(
+        "{:0width$}",
+        start + frame_index,
+        width = digits.len()
+    ))
+}
+
+/// 把路径分隔符、控制字符和 Windows 不允许的字符换成 `_`，去掉结尾的点和空格
+fn sanitize_file_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let sanitized = sanitized.trim_end_matches(['.', ' ']);
+    match sanitized {
+        "" => "_".to_string(),
+        sanitized => sanitized.to_string(),
+    }
+}
@@ -0,0 +1,57 @@
+use crate::raw::RawAsepriteCel;
+
+use super::Aseprite;
+
+impl Aseprite {
+    /// Estimate of the bytes used by pixel data
+    ///
+    /// Counts the pixels of every cel, compressed or not, the RGBA pixels decoded from them and
+    /// the frames kept by [`Aseprite::get_frame_image_cached`]. Metadata like layers, tags and
+    /// slices is not counted.
+    pub fn memory_usage(&self) -> usize {
+        let cels: usize = self
+            .cels
+            .iter()
+            .flat_map(|(_, layer_cels)| layer_cels.iter())
+            .map(|(_, cel)| {
+                let raw = match &cel.raw_cel {
+                    RawAsepriteCel::Raw { pixels, .. }
+                    | RawAsepriteCel::Compressed { pixels, .. } => pixels.as_bytes().len(),
+                    RawAsepriteCel::Deferred { data, .. } => data.compressed_len(),
+                    RawAsepriteCel::Linked { .. }
+                    | RawAsepriteCel::Skipped { .. }
+                    | RawAsepriteCel::Dropped { .. } => 0,
+                };
+                let decoded = cel.decoded.get().map_or(0, |pixels| pixels.len() * 4);
+                raw + decoded
+            })
+            .sum();
+        cels + self.render_cache.memory_usage()
+    }
+
+    /// Release the pixels of every cel, keeping the metadata
+    ///
+    /// Cels with pixels become [`RawAsepriteCel::Dropped`] and keep their size, linked cels are
+    /// unchanged. Rendered frames kept by [`Aseprite::get_frame_image_cached`] are dropped too.
+    /// Every method returning pixels then fails with [`AsepriteError::PixelsDropped`], the file
+    /// has to be parsed again to get them back.
+    ///
+    /// [`AsepriteError::PixelsDropped`]: crate::error::AsepriteError::PixelsDropped
+    pub fn shrink_pixels(&mut self) {
+        for cel in self.cels.cels_mut() {
+            let size = match cel.raw_cel {
+                RawAsepriteCel::Raw { width, height, .. }
+                | RawAsepriteCel::Compressed { width, height, .. }
+                | RawAsepriteCel::Deferred { width, height, .. } => Some((width, height)),
+                RawAsepriteCel::Linked { .. }
+                | RawAsepriteCel::Skipped { .. }
+                | RawAsepriteCel::Dropped { .. } => None,
+            };
+            if let Some((width, height)) = size {
+                cel.raw_cel = RawAsepriteCel::Dropped { width, height };
+            }
+            cel.decoded = Default::default();
+        }
+        self.clear_render_cache();
+    }
+}
@@ -1,53 +1,267 @@
-use std::{collections::BTreeMap, path::Path};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+    path::Path,
+    sync::OnceLock,
+    time::Duration,
+};
 
-use image::{Pixel, Rgba, RgbaImage};
+use image::{GrayImage, RgbaImage};
 use tracing::error;
 
+pub use blend::*;
 pub use cel::*;
+pub use document::*;
+pub use duplicate::*;
 pub use layer::*;
+pub use options::*;
 pub use palette::*;
+pub use rect::*;
+pub use slice::*;
+pub use spritesheet::*;
+pub use summary::*;
 pub use tag::*;
+pub use web::*;
 
 use crate::{
     error::{AseResult, AsepriteError, AsepriteInvalidError},
     raw::{
-        AsepriteColor, AsepriteColorDepth, AsepritePixel, RawAseprite, RawAsepriteCel,
-        RawAsepriteChunk, RawAsepriteChunkType,
+        AsepriteColor, AsepriteColorDepth, AsepriteLayerType, AsepritePixel, CelFilter, CelPixels,
+        PackedPixels, RawAseprite, RawAsepriteCel, RawAsepriteChunk, RawAsepriteChunkType,
     },
 };
 
+#[cfg(feature = "apng")]
+mod apng;
+mod batch;
+mod blend;
 mod cel;
+mod document;
+mod duplicate;
+#[cfg(feature = "gif")]
+mod gif;
+mod json;
 mod layer;
+mod layer_export;
+mod memory;
+mod onion_skin;
+mod options;
 mod palette;
+mod rect;
+mod render_cache;
+mod slice;
+mod spritesheet;
+mod summary;
 mod tag;
 #[cfg(test)]
 #[allow(deprecated)]
 mod test;
+mod web;
 
 #[derive(Debug, Clone)]
 /// Data structure representing an Aseprite file
 pub struct Aseprite {
     dimensions: (u16, u16),
+    color_depth: AsepriteColorDepth,
     tags: BTreeMap<usize, AsepriteTag>,
+    slices: BTreeMap<usize, AsepriteSlice>,
     layers: BTreeMap<usize, AsepriteLayer>,
-    cels: BTreeMap<usize, BTreeMap<usize, AsepriteCel>>,
+    cels: CelGrid,
     frame_count: usize,
     palette: Option<AsepritePalette>,
     transparent_palette: Option<u8>,
+    /// indexed 文件中每个 index 对应的 RGBA，无效的 index 为 None
+    /// 解码时直接查表，不用每个像素都重新查找 palette
+    indexed_colors: Option<Box<[Option<[u8; 4]>; 256]>>,
     frame_infos: Vec<AsepriteFrameInfo>,
+    default_delay_ms: u16,
+    layer_opacity_valid: bool,
+    /// 每一帧合成后的像素的 hash，第一次用到时计算
+    frame_hashes: Vec<OnceLock<u64>>,
+    /// 每一帧 cel 的绘制顺序，加载时排好，合成时不需要再排序
+    render_orders: Vec<Vec<usize>>,
+    /// 用 ParseOptions::frames 加载时选中的帧，为 None 时加载了所有帧
+    loaded_frames: Option<Vec<bool>>,
+    render_cache: render_cache::RenderCache,
+    /// 并行渲染时最多用几个线程，为 None 时用 available_parallelism
+    render_threads: Option<usize>,
+    header_flags: u32,
+    pixel_ratio: (u8, u8),
+    grid: Option<AsepriteRect>,
+    color_profile: Option<AsepriteColorProfile>,
+    sprite_user_data: Option<AsepriteUserData>,
 }
 
 impl Aseprite {
     /// Sprite Size
+    ///
+    /// This is the untrimmed canvas size, every rendered frame has this size while the
+    /// images of single cels are trimmed to the bounds of the cel.
     pub fn size(&self) -> (u16, u16) {
         self.dimensions
     }
 
+    /// Width of the canvas, see [`Aseprite::size`]
+    pub fn width(&self) -> u16 {
+        self.dimensions.0
+    }
+
+    /// Height of the canvas, see [`Aseprite::size`]
+    pub fn height(&self) -> u16 {
+        self.dimensions.1
+    }
+
+    /// Color depth the file was saved with
+    pub fn color_depth(&self) -> AsepriteColorDepth {
+        self.color_depth
+    }
+
+    /// Get the palette of this Aseprite, if it has one
+    pub fn palette(&self) -> Option<&AsepritePalette> {
+        self.palette.as_ref()
+    }
+
+    /// Get the palette index which is considered transparent
+    ///
+    /// This is only set for files using indexed colors.
+    pub fn transparent_index(&self) -> Option<u8> {
+        self.transparent_palette
+    }
+
     /// Get the [`AsepriteTag`]s defined in this Aseprite
     pub fn tags(&self) -> impl Iterator<Item = &AsepriteTag> {
         self.tags.values()
     }
 
+    /// Get a tag by its name
+    ///
+    /// Several tags can share a name, in that case the one with the lowest index is returned,
+    /// see [`Aseprite::get_tags_by_name`] to get all of them.
+    pub fn get_tag_by_name<N: AsRef<str>>(&self, name: N) -> Option<&AsepriteTag> {
+        let name = name.as_ref();
+        self.tags.values().find(|tag| tag.name == name)
+    }
+
+    /// Get all the tags with the given name, in index order
+    pub fn get_tags_by_name<N: AsRef<str>>(&self, name: N) -> Vec<&AsepriteTag> {
+        let name = name.as_ref();
+        self.tags.values().filter(|tag| tag.name == name).collect()
+    }
+
+    /// Get the [`AsepriteSlice`]s defined in this Aseprite
+    pub fn slices(&self) -> impl Iterator<Item = &AsepriteSlice> {
+        self.slices.values()
+    }
+
+    /// Get a slice by its name
+    pub fn get_slice_by_name<N: AsRef<str>>(&self, name: N) -> Option<&AsepriteSlice> {
+        let name = name.as_ref();
+        self.slices.values().find(|slice| slice.name == name)
+    }
+
+    /// Get all the slices containing a point of the canvas in the given frame
+    ///
+    /// The bounds of the slice key in effect at that frame are used. Slices are in index
+    /// order, so the topmost one is the last.
+    pub fn slices_at(&self, frame_index: usize, x: i32, y: i32) -> Vec<&AsepriteSlice> {
+        self.slices
+            .values()
+            .filter(|slice| {
+                slice
+                    .key_for_frame(frame_index)
+                    .is_some_and(|key| key.bounds.contains(x, y))
+            })
+            .collect()
+    }
+
+    /// Get the topmost slice containing a point of the canvas in the given frame
+    ///
+    /// See [`Aseprite::slices_at`].
+    pub fn slice_at(&self, frame_index: usize, x: i32, y: i32) -> Option<&AsepriteSlice> {
+        self.slices_at(frame_index, x, y).pop()
+    }
+
+    /// Get the slices whose name starts with `prefix` and their bounds in the given frame
+    ///
+    /// Meant for hitboxes authored as slices named e.g. `hit_*` and `hurt_*`: the name, user
+    /// data and properties of each box are on its slice. The bounds are those of the key in
+    /// effect at that frame, slices whose first key is later are left out. Slices are in index
+    /// order.
+    pub fn slice_rects_with_prefix(
+        &self,
+        prefix: &str,
+        frame_index: usize,
+    ) -> Vec<(&AsepriteSlice, AsepriteRect)> {
+        self.slices
+            .values()
+            .filter(|slice| slice.name.starts_with(prefix))
+            .filter_map(|slice| Some((slice, slice.key_for_frame(frame_index)?.bounds)))
+            .collect()
+    }
+
+    /// Get the anchor point of a frame on the canvas, e.g. the feet of a character
+    ///
+    /// The anchor is the pivot of the first slice key in effect at that frame with a pivot,
+    /// looking at the slices named in `slice_names` first, in that order, then at all the
+    /// other slices in index order. Without any pivot it is the center of the first slice
+    /// named in `slice_names`, rounded down. Returns `None` if none of these slices has a key
+    /// at that frame.
+    pub fn anchor<N: AsRef<str>>(
+        &self,
+        frame_index: usize,
+        slice_names: &[N],
+    ) -> Option<(i32, i32)> {
+        let named = slice_names
+            .iter()
+            .filter_map(|name| self.get_slice_by_name(name));
+        named
+            .clone()
+            .chain(self.slices())
+            .find_map(|slice| {
+                let key = slice.key_for_frame(frame_index)?;
+                let pivot = key.pivot.as_ref()?;
+                Some((key.bounds.x + pivot.x_pivot, key.bounds.y + pivot.y_pivot))
+            })
+            .or_else(|| {
+                named
+                    .filter_map(|slice| slice.key_for_frame(frame_index))
+                    .map(|key| {
+                        let bounds = key.bounds;
+                        (
+                            bounds.x + (bounds.width / 2) as i32,
+                            bounds.y + (bounds.height / 2) as i32,
+                        )
+                    })
+                    .next()
+            })
+    }
+
+    /// Get all the [`AsepriteTag`]s covering the given frame, in index order
+    pub fn tags_for_frame(&self, frame_index: usize) -> Vec<&AsepriteTag> {
+        self.tags
+            .values()
+            .filter(|tag| tag.contains_frame(frame_index))
+            .collect()
+    }
+
+    /// Pick a single [`AsepriteTag`] covering the given frame when several tags overlap
+    ///
+    /// See [`TagPriority`] for how ties are broken.
+    pub fn primary_tag_for_frame(
+        &self,
+        frame_index: usize,
+        policy: TagPriority,
+    ) -> Option<&AsepriteTag> {
+        let tags = self.tags_for_frame(frame_index).into_iter();
+        // 相等时 min_by_key 返回第一个而 max_by_key 返回最后一个，所以 Largest 也用 min_by_key
+        match policy {
+            TagPriority::FirstByIndex => tags.min_by_key(|tag| tag.index),
+            TagPriority::Smallest => tags.min_by_key(|tag| tag.frames.len()),
+            TagPriority::Largest => tags.min_by_key(|tag| std::cmp::Reverse(tag.frames.len())),
+        }
+    }
+
     /// Get the associated [`AsepriteLayer`]s defined in this Aseprite
     pub fn layers(&self) -> impl Iterator<Item = &AsepriteLayer> {
         self.layers.values()
@@ -59,36 +273,61 @@ impl Aseprite {
     }
 
     /// Get each frame duration
-    pub fn frame_infos(&self) -> Vec<AsepriteFrameInfo> {
-        self.frame_infos.clone()
+    pub fn frame_infos(&self) -> &[AsepriteFrameInfo] {
+        &self.frame_infos
+    }
+
+    /// Get the duration of the whole animation, in ms
+    pub fn total_duration_ms(&self) -> usize {
+        self.frame_infos.iter().map(|info| info.delay_ms).sum()
+    }
+
+    /// Get the duration of the whole animation
+    pub fn total_duration(&self) -> Duration {
+        self.frame_infos
+            .iter()
+            .map(AsepriteFrameInfo::duration)
+            .sum()
+    }
+
+    /// Whether the file header marks the layer opacity as valid
+    ///
+    /// When it is not set the stored layer opacities are ignored and normal layers are fully
+    /// opaque, like Aseprite does.
+    pub fn layer_opacity_valid(&self) -> bool {
+        self.layer_opacity_valid
+    }
+
+    /// Get the frame delay from the file header, in ms
+    ///
+    /// Frames stored with a delay of 0 use this delay instead.
+    pub fn default_delay_ms(&self) -> u16 {
+        self.default_delay_ms
     }
 
     /// 获取特定帧的所有 layer 和对应的 z-index
     /// 因为单个 cel 可以调整 z-index, 所以每一帧 layer 的显示顺序可能是不同的
     #[deprecated]
-    pub fn layers_by_frame(&self, frame_index: &usize) -> Vec<(AsepriteLayer, i16)> {
+    pub fn layers_by_frame(&self, frame_index: usize) -> Vec<(AsepriteLayer, i16)> {
         let mut layers: Vec<(AsepriteLayer, i16)> =
             self.layers.values().map(|l| (l.clone(), 0)).collect();
         for (layer, z_index) in layers.iter_mut() {
             let layer_index = layer.index();
-            if let Some(layer_cels) = self.cels.get(&layer_index) {
-                if let Some(cel) = layer_cels.get(frame_index) {
-                    *z_index = cel.z_index
-                }
+            if let Some(cel) = self.cels.get(layer_index, frame_index) {
+                *z_index = cel.z_index
             }
         }
         layers
     }
 
     /// Get frame count
-    #[deprecated]
     pub fn frame_count(&self) -> usize {
         self.frame_count
     }
 
     /// Get the frames inside this aseprite
     #[deprecated]
-    pub fn get_frame(&self, frame_index: usize) -> Option<AsepriteFrame> {
+    pub fn get_frame(&self, frame_index: usize) -> Option<AsepriteFrame<'_>> {
         if frame_index >= self.frame_count {
             return None;
         }
@@ -100,29 +339,82 @@ impl Aseprite {
 
     /// Get the cel of giving layer and frame
     /// If cel is empty return None
-    pub fn get_cel(&self, layer_index: &usize, frame_index: &usize) -> Option<&AsepriteCel> {
-        let Some(layer_cels) = self.cels.get(layer_index) else {
-            return None;
-        };
-        let Some(cel) = layer_cels.get(frame_index) else {
-            return None;
-        };
-        Some(cel)
+    pub fn get_cel(&self, layer_index: usize, frame_index: usize) -> Option<&AsepriteCel> {
+        self.cels.get(layer_index, frame_index)
+    }
+
+    /// Get the cels of a layer with their frame index, in frame order
+    ///
+    /// Frames where the layer has no cel are skipped.
+    pub fn cels_for_layer(
+        &self,
+        layer_index: usize,
+    ) -> impl Iterator<Item = (usize, &AsepriteCel)> + '_ {
+        self.cels
+            .layer(layer_index)
+            .into_iter()
+            .flat_map(LayerCels::iter)
+    }
+
+    /// Get the cels of a frame with their layer index, in layer order
+    ///
+    /// Layers without a cel in this frame are skipped.
+    pub fn cels_for_frame(
+        &self,
+        frame_index: usize,
+    ) -> impl Iterator<Item = (usize, &AsepriteCel)> + '_ {
+        self.cels
+            .iter()
+            .filter_map(move |(layer_index, layer_cels)| {
+                layer_cels.get(frame_index).map(|cel| (layer_index, cel))
+            })
+    }
+
+    /// Get every cel with its layer and frame
+    ///
+    /// Cels are in layer order, then in frame order within a layer. Linked cels are returned
+    /// as they are, see [`Aseprite::cel_bounds`] to resolve their size.
+    pub fn iter_cels(&self) -> impl Iterator<Item = CelEntry<'_>> + '_ {
+        self.cels.iter().flat_map(move |(layer_index, layer_cels)| {
+            let layer = self.layers.get(&layer_index);
+            layer_cels
+                .iter()
+                .map(move |(frame_index, cel)| CelEntry::new(layer_index, frame_index, cel, layer))
+        })
+    }
+
+    /// Get the cels with a non empty user data text, in the order of [`Aseprite::iter_cels`]
+    pub fn cels_with_user_data(&self) -> impl Iterator<Item = CelEntry<'_>> + '_ {
+        self.iter_cels()
+            .filter(|entry| !entry.cel.user_data.is_empty())
+    }
+
+    /// Get the area covered by the cel of a layer in a frame, `None` if there is no cel
+    ///
+    /// Linked cels keep their own position but take the size of the cel they link to.
+    pub fn cel_bounds(&self, layer_index: usize, frame_index: usize) -> Option<AsepriteRect> {
+        let layer_cels = self.cels.layer(layer_index)?;
+        let cel = layer_cels.get(frame_index)?;
+        let [width, height] = self
+            .linked_target(layer_index, frame_index, layer_cels, cel)
+            .ok()?
+            .get_size()?;
+        let (x, y) = cel.origin();
+        Some(AsepriteRect::new(x, y, width as u32, height as u32))
     }
 
     /// Get cels of giving frame
     #[deprecated]
-    pub fn get_cels_by_frame(&self, frame_index: &usize) -> Vec<&AsepriteCel> {
-        self.cels
-            .values()
-            .map(|layer_cels| layer_cels.get(frame_index).unwrap())
+    pub fn get_cels_by_frame(&self, frame_index: usize) -> Vec<&AsepriteCel> {
+        self.cels_for_frame(frame_index)
+            .map(|(_, cel)| cel)
             .collect()
     }
 
     /// Get a layer by its name
     ///
-    /// If you have its id, prefer fetching it using [`get_by_id`]
-    #[cfg(test)]
+    /// Several layers can share a name, in that case the one with the lowest index is
+    /// returned, see [`Aseprite::get_layers_by_name`] to get all of them.
     pub fn get_layer_by_name<N: AsRef<str>>(&self, name: N) -> Option<&AsepriteLayer> {
         let name = name.as_ref();
         self.layers
@@ -131,75 +423,640 @@ impl Aseprite {
             .map(|(_, layer)| layer)
     }
 
+    /// Get all the layers with the given name, in index order
+    pub fn get_layers_by_name<N: AsRef<str>>(&self, name: N) -> Vec<&AsepriteLayer> {
+        let name = name.as_ref();
+        self.layers
+            .values()
+            .filter(|layer| layer.name() == name)
+            .collect()
+    }
+
     /// Get a layer by its index
     #[deprecated]
-    pub fn get_layer_by_index(&self, index: &usize) -> Option<&AsepriteLayer> {
-        self.layers.get(index)
+    pub fn get_layer_by_index(&self, index: usize) -> Option<&AsepriteLayer> {
+        self.layers.get(&index)
     }
 
-    /// 找到提供的 index 的 layer 属于的所有 groups
-    pub fn find_layer_belong_groups(&self, index: usize) -> Vec<usize> {
+    /// Get the full path of a layer, the names of its groups and itself joined with `/`
+    ///
+    /// For example `Table/Col2/Col2Row1`, a layer at the root is just its own name.
+    pub fn layer_path(&self, index: usize) -> Option<String> {
+        let layer = self.layers.get(&index)?;
+        let mut names: Vec<&str> = self
+            .find_layer_belong_groups(index)
+            .into_iter()
+            .rev()
+            .filter_map(|group_index| self.layers.get(&group_index))
+            .map(|group| group.name())
+            .collect();
+        names.push(layer.name());
+        Some(names.join("/"))
+    }
+
+    /// Get a layer by its full path, see [`Aseprite::layer_path`]
+    ///
+    /// The path has to match exactly, including the case.
+    pub fn get_layer_by_path<P: AsRef<str>>(&self, path: P) -> Option<&AsepriteLayer> {
+        let path = path.as_ref();
+        self.layers
+            .values()
+            .find(|layer| self.layer_path(layer.index()).as_deref() == Some(path))
+    }
+
+    /// Whether a layer is visible, taking its groups into account
+    ///
+    /// Aseprite hides a layer as soon as any of its groups is hidden, even if the layer itself
+    /// is marked as visible.
+    pub fn is_layer_effectively_visible(&self, index: usize) -> bool {
         let Some(layer) = self.layers.get(&index) else {
+            return false;
+        };
+        layer.is_visible()
+            && self.belong_groups(index).all(|group_index| {
+                self.layers
+                    .get(&group_index)
+                    .is_some_and(AsepriteLayer::is_visible)
+            })
+    }
+
+    /// Opacity a cel is drawn with: the product of the cel, layer and group opacities
+    ///
+    /// Returns 0 if there is no cel for this layer and frame.
+    pub fn effective_cel_opacity(&self, layer_index: usize, frame_index: usize) -> u8 {
+        let Some(cel) = self.get_cel(layer_index, frame_index) else {
+            return 0;
+        };
+        std::iter::once(layer_index)
+            .chain(self.belong_groups(layer_index))
+            .filter_map(|index| self.layers.get(&index)?.opacity())
+            .fold(cel.opacity, mul_un8)
+    }
+
+    /// Get the layers having a cel in the given frame, in the order they are drawn
+    ///
+    /// Cels are sorted by `layer index + z-index`, ties are broken by the z-index, as
+    /// Aseprite does. Hidden and reference layers are included, it is up to the caller to
+    /// skip them.
+    pub fn cel_render_order(&self, frame_index: usize) -> Vec<usize> {
+        self.render_orders
+            .get(frame_index)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Get the layers inside a group, in index order
+    ///
+    /// With `recursive` all descendants are returned, otherwise only the direct children.
+    /// Returns an empty vec if the index is not a group.
+    pub fn layers_in_group(&self, group_index: usize, recursive: bool) -> Vec<&AsepriteLayer> {
+        let Some(AsepriteLayer::Group(group)) = self.layers.get(&group_index) else {
             return Vec::new();
         };
-        let mut cur_child_level = layer.child_level();
-        let mut cur_index = index;
-        let mut result = Vec::new();
-        'find_all_group: while cur_child_level > 0 {
-            cur_child_level -= 1;
-            loop {
-                cur_index -= 1;
-                if let Some(layer) = self.layers.get(&cur_index) {
-                    match layer {
-                        AsepriteLayer::Group(GroupLayer {
-                            index, child_level, ..
-                        }) => {
-                            if *child_level == cur_child_level {
-                                cur_index = *index;
-                                result.push(*index);
-                                continue 'find_all_group;
-                            }
-                        }
-                        _ => {}
+        // group 的成员紧跟在 group 后面，直到遇到 child level 不比 group 深的 layer
+        self.layers
+            .range(group_index + 1..)
+            .map(|(_, layer)| layer)
+            .take_while(|layer| layer.child_level() > group.child_level)
+            .filter(|layer| recursive || layer.child_level() == group.child_level + 1)
+            .collect()
+    }
+
+    /// 找到提供的 index 的 layer 属于的所有 groups
+    pub fn find_layer_belong_groups(&self, index: usize) -> Vec<usize> {
+        self.belong_groups(index).collect()
+    }
+
+    /// 帧没有被 ParseOptions::frames 选中时返回 FrameNotLoaded
+    fn check_frame_loaded(&self, frame_index: usize) -> AseResult<()> {
+        match &self.loaded_frames {
+            Some(loaded) if !loaded.get(frame_index).copied().unwrap_or(true) => {
+                Err(AsepriteError::FrameNotLoaded(frame_index))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// 和 find_layer_belong_groups 一样，但不需要分配 Vec
+    fn belong_groups(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        // 往前找，第一个 child level 比当前小的 group 就是父 group
+        // 格式有误时某一层可能没有 group，这样也只会找到还存在的那些 group
+        let mut cur_child_level = self
+            .layers
+            .get(&index)
+            .map_or(0, AsepriteLayer::child_level);
+        let mut previous = self.layers.range(..index).rev();
+        std::iter::from_fn(move || {
+            while cur_child_level > 0 {
+                let (group_index, group) = previous.next()?;
+                if let AsepriteLayer::Group(GroupLayer { child_level, .. }) = group {
+                    if *child_level < cur_child_level {
+                        cur_child_level = *child_level;
+                        return Some(*group_index);
                     }
                 }
             }
+            None
+        })
+    }
+
+    /// Decode the cels of the given frames ahead of time
+    ///
+    /// Cel pixels are decompressed and converted to RGBA the first time they are rendered,
+    /// and cached per cel.
+    /// Call this when predictable rendering latency matters more than load time.
+    /// Rendering different frames from several threads is safe with or without prewarming.
+    pub fn prewarm<I: IntoIterator<Item = usize>>(&self, frames: I) -> AseResult<()> {
+        for frame_index in frames {
+            if frame_index >= self.frame_count {
+                return Err(AsepriteInvalidError::InvalidFrame(frame_index).into());
+            }
+            for (layer_index, layer_cels) in self.cels.iter() {
+                let Some(cel) = layer_cels.get(frame_index) else {
+                    continue;
+                };
+                self.decoded_pixels(self.linked_target(
+                    layer_index,
+                    frame_index,
+                    layer_cels,
+                    cel,
+                )?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the cel of a layer in a frame with the cel holding its pixels, following linked cels
+    ///
+    /// The pixels of a linked cel come from the cel it links to, while its position, opacity
+    /// and z-index are its own, see [`ResolvedCel`].
+    /// Fails if there is no cel or the link is broken, a linked cel pointing to another linked
+    /// cel is reported as [`AsepriteInvalidError::LinkedCelCycle`].
+    pub fn resolve_cel(
+        &self,
+        layer_index: usize,
+        frame_index: usize,
+    ) -> AseResult<ResolvedCel<'_>> {
+        let layer_cels = self
+            .cels
+            .layer(layer_index)
+            .ok_or(AsepriteInvalidError::InvalidLayer(layer_index))?;
+        let cel = layer_cels
+            .get(frame_index)
+            .ok_or(AsepriteInvalidError::InvalidFrame(frame_index))?;
+        let source = self.linked_target(layer_index, frame_index, layer_cels, cel)?;
+        Ok(ResolvedCel::new(cel, source))
+    }
+
+    /// 如果是 linked cel 则返回它指向的 cel，否则返回自身
+    /// 规范里 linked cel 只会指向普通的 cel，指向另一个 linked cel 时（链或者循环）直接报错
+    fn linked_target<'a>(
+        &self,
+        layer_index: usize,
+        frame_index: usize,
+        layer_cels: &'a LayerCels,
+        cel: &'a AsepriteCel,
+    ) -> AseResult<&'a AsepriteCel> {
+        let RawAsepriteCel::Linked { frame_position } = cel.raw_cel else {
+            return Ok(cel);
+        };
+        let frame_position = frame_position as usize;
+        let target = layer_cels
+            .get(frame_position)
+            .ok_or(AsepriteInvalidError::InvalidFrame(frame_position))?;
+        if let RawAsepriteCel::Linked { .. } = target.raw_cel {
+            error!("Linked cel links to another linked cel.");
+            return Err(AsepriteInvalidError::LinkedCelCycle {
+                layer_index,
+                frame_index,
+            }
+            .into());
+        }
+        Ok(target)
+    }
+
+    /// 将 cel 的像素转换成 RGBA，结果缓存在 cel 上，linked cel 需要先找到实际的 cel
+    fn decoded_pixels<'a>(&self, cel: &'a AsepriteCel) -> AseResult<&'a [[u8; 4]]> {
+        if let Some(pixels) = cel.decoded.get() {
+            return Ok(pixels);
+        }
+        let raw = raw_pixels(&cel.raw_cel)?;
+        let bytes = raw.as_bytes();
+        let pixels = match (raw.color_depth(), &self.indexed_colors) {
+            (AsepriteColorDepth::RGBA, _) => bytes
+                .chunks_exact(4)
+                .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+                .collect(),
+            (AsepriteColorDepth::Grayscale, _) => bytes
+                .chunks_exact(2)
+                .map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+                .collect(),
+            (AsepriteColorDepth::Indexed, Some(colors)) => bytes
+                .iter()
+                .map(|&index| {
+                    colors[index as usize].ok_or(AsepriteError::InvalidConfiguration(
+                        AsepriteInvalidError::InvalidPaletteIndex(index as usize),
+                    ))
+                })
+                .collect::<AseResult<Vec<_>>>()?,
+            (AsepriteColorDepth::Indexed, None) => raw
+                .iter()
+                .map(|pixel| pixel.get_rgba(self.palette.as_ref(), self.transparent_palette))
+                .collect::<AseResult<Vec<_>>>()?,
+        };
+        Ok(cel.decoded.get_or_init(pixels))
+    }
+
+    /// Render the part of a frame inside `viewport` into `target`
+    ///
+    /// `target` is cleared first, and replaced if its size differs from the viewport.
+    /// Cels outside of the viewport are skipped without being decoded.
+    /// The result equals cropping the full frame to the viewport, areas outside of the
+    /// canvas stay transparent.
+    pub fn render_viewport(
+        &self,
+        frame_index: usize,
+        viewport: AsepriteRect,
+        target: &mut RgbaImage,
+    ) -> AseResult<()> {
+        if frame_index >= self.frame_count {
+            return Err(AsepriteInvalidError::InvalidFrame(frame_index).into());
+        }
+        reset_image(target, viewport.width, viewport.height);
+        self.composite_region(
+            frame_index,
+            viewport,
+            target,
+            false,
+            |layer_index, layer| {
+                !layer.is_reference() && self.is_layer_effectively_visible(layer_index)
+            },
+        )
+    }
+
+    /// Get the image of a frame with all visible layers merged, in canvas size
+    ///
+    /// Hidden layers, layers inside hidden groups and reference layers are left out.
+    /// See [`Aseprite::render_frame_into`] to reuse an image between calls.
+    pub fn get_frame_image(&self, frame_index: usize) -> AseResult<RgbaImage> {
+        let mut image = RgbaImage::default();
+        self.render_frame_into(frame_index, &mut image)?;
+        Ok(image)
+    }
+
+    /// Render a frame like [`Aseprite::get_frame_image`] into an existing image
+    ///
+    /// `target` is cleared first and only reallocated if it does not have the canvas size,
+    /// so rendering every tick into the same image does not allocate once the cels of the
+    /// frame have been decoded.
+    pub fn render_frame_into(&self, frame_index: usize, target: &mut RgbaImage) -> AseResult<()> {
+        let (width, height) = self.dimensions;
+        self.render_viewport(
+            frame_index,
+            AsepriteRect::new(0, 0, width as u32, height as u32),
+            target,
+        )
+    }
+
+    /// Get the pixels of a frame as RGBA8 bytes, with the width and height
+    ///
+    /// The frame is rendered like [`Aseprite::get_frame_image`]. Bytes are in row-major order
+    /// from the top row to the bottom one, 4 bytes per pixel with no padding between rows, so
+    /// the buffer is `width * height * 4` bytes and can be uploaded to a GPU texture as is.
+    pub fn frame_rgba_bytes(&self, frame_index: usize) -> AseResult<(Vec<u8>, u32, u32)> {
+        let image = self.get_frame_image(frame_index)?;
+        let (width, height) = image.dimensions();
+        Ok((image.into_raw(), width, height))
+    }
+
+    /// Get the pixels of a layer in a frame as RGBA8 bytes, with the width and height
+    ///
+    /// The layer is rendered in canvas size like
+    /// [`Aseprite::get_image_by_layer_frame_on_canvas`], with the byte layout of
+    /// [`Aseprite::frame_rgba_bytes`]. Returns `None` if the layer has no cel in this frame.
+    pub fn layer_rgba_bytes(
+        &self,
+        layer_index: usize,
+        frame_index: usize,
+    ) -> AseResult<Option<(Vec<u8>, u32, u32)>> {
+        let image = self.get_image_by_layer_frame_on_canvas(layer_index, frame_index)?;
+        Ok(image.map(|image| {
+            let (width, height) = image.dimensions();
+            (image.into_raw(), width, height)
+        }))
+    }
+
+    /// Get the image of a frame with only the given layers merged, in canvas size
+    ///
+    /// Layers are drawn in their stacking order whatever the order of `layers`, with the same
+    /// blending as [`Aseprite::get_frame_image`]. Visibility flags are ignored so hidden layers
+    /// can be picked too, layers without a cel in this frame are skipped.
+    pub fn get_frame_image_with_layers(
+        &self,
+        frame_index: usize,
+        layers: &[usize],
+    ) -> AseResult<RgbaImage> {
+        if frame_index >= self.frame_count {
+            return Err(AsepriteInvalidError::InvalidFrame(frame_index).into());
+        }
+        if let Some(&layer_index) = layers
+            .iter()
+            .find(|layer_index| !self.layers.contains_key(layer_index))
+        {
+            return Err(AsepriteInvalidError::InvalidLayer(layer_index).into());
+        }
+        self.composite_frame(frame_index, |layer_index, _| layers.contains(&layer_index))
+    }
+
+    /// Same as [`Aseprite::get_frame_image_with_layers`], with the layers given by name
+    ///
+    /// When several layers share a name, all of them are drawn.
+    pub fn get_frame_image_with_layer_names(
+        &self,
+        frame_index: usize,
+        names: &[&str],
+    ) -> AseResult<RgbaImage> {
+        let mut layers = vec![];
+        for name in names {
+            let found = self.get_layers_by_name(name);
+            if found.is_empty() {
+                return Err(AsepriteInvalidError::InvalidLayerName(name.to_string()).into());
+            }
+            layers.extend(found.into_iter().map(|layer| layer.index()));
+        }
+        self.get_frame_image_with_layers(frame_index, &layers)
+    }
+
+    /// Get the image of a group in a frame with all its visible layers merged, in canvas size
+    ///
+    /// Layers in nested groups are included. Returns `None` if no visible layer of the group
+    /// has a cel in this frame.
+    pub fn get_group_image(
+        &self,
+        group_index: usize,
+        frame_index: usize,
+    ) -> AseResult<Option<RgbaImage>> {
+        match self.layers.get(&group_index) {
+            Some(AsepriteLayer::Group(_)) => {}
+            Some(AsepriteLayer::Normal(_)) => {
+                return Err(AsepriteInvalidError::NotAGroup(group_index).into())
+            }
+            None => return Err(AsepriteInvalidError::InvalidLayer(group_index).into()),
+        }
+        if frame_index >= self.frame_count {
+            return Err(AsepriteInvalidError::InvalidFrame(frame_index).into());
         }
-        result
+        let members = self.layers_in_group(group_index, true);
+        let include = |layer_index: usize, layer: &AsepriteLayer| {
+            members.iter().any(|member| member.index() == layer_index)
+                && !layer.is_reference()
+                && self.is_layer_effectively_visible(layer_index)
+        };
+        let drawn = members.iter().any(|layer| {
+            include(layer.index(), layer)
+                && self.effective_cel_opacity(layer.index(), frame_index) > 0
+        });
+        if !drawn {
+            return Ok(None);
+        }
+        self.composite_frame(frame_index, include).map(Some)
+    }
+
+    /// Get the smallest area containing every non transparent pixel of a frame
+    ///
+    /// Returns `None` if the whole frame is transparent.
+    pub fn frame_content_bounds(&self, frame_index: usize) -> AseResult<Option<AsepriteRect>> {
+        Ok(content_bounds(&self.get_frame_image(frame_index)?))
     }
 
-    /// 根据传入的像素数据生成对应图像，其中如果传入 cel 则生成完整尺寸的图像，否则生成当前 sprite trim 后的图像
-    fn write_image(
+    /// Render a frame in canvas size with the layers picked by `options`
+    ///
+    /// Reference layers are never drawn. Layers are drawn in their stacking order with the
+    /// same blending as [`Aseprite::get_frame_image`].
+    pub fn render_frame(
         &self,
-        cel: Option<&AsepriteCel>,
-        width: u16,
-        height: u16,
-        pixels: &[AsepritePixel],
+        frame_index: usize,
+        options: &FrameRenderOptions,
     ) -> AseResult<RgbaImage> {
+        if frame_index >= self.frame_count {
+            return Err(AsepriteInvalidError::InvalidFrame(frame_index).into());
+        }
+        if let Some(layer_index) = options
+            .layer_indices()
+            .find(|layer_index| !self.layers.contains_key(layer_index))
+        {
+            return Err(AsepriteInvalidError::InvalidLayer(layer_index).into());
+        }
+        let (width, height) = self.dimensions;
         let mut image = RgbaImage::new(width as u32, height as u32);
-        for x in 0..width {
-            for y in 0..height {
-                let mut pix_x = x as i16;
-                let mut pix_y = y as i16;
-                if let Some(cel) = &cel {
-                    pix_x += cel.x;
-                    pix_y += cel.y;
-                }
+        if let Some(color) = options.background {
+            image.pixels_mut().for_each(|pixel| {
+                pixel.0 = color.into();
+            });
+        }
+        self.composite_region(
+            frame_index,
+            AsepriteRect::new(0, 0, width as u32, height as u32),
+            &mut image,
+            options.use_precise_bounds,
+            |layer_index, layer| {
+                options.selects(layer_index)
+                    && !layer.is_reference()
+                    && (options.include_hidden || self.is_layer_effectively_visible(layer_index))
+            },
+        )?;
+        Ok(image)
+    }
 
-                if pix_x < 0 || pix_y < 0 {
-                    continue;
+    /// Get frames as [`image::Frames`], to feed the encoders of the `image` crate directly
+    ///
+    /// Every frame is merged like [`Aseprite::get_frame_image`] and keeps its delay.
+    /// `None` takes every frame, a tag range can be passed as `Some(tag.frames.clone())`.
+    /// The frames are rendered eagerly, so errors are returned here and never by the iterator.
+    pub fn to_image_frames(&self, range: Option<Range<u16>>) -> AseResult<image::Frames<'static>> {
+        let range = match range {
+            Some(range) => range.start as usize..range.end as usize,
+            None => 0..self.frame_count,
+        };
+        let frames = range
+            .map(|frame_index| {
+                let image = self.get_frame_image(frame_index)?;
+                let delay = image::Delay::from_numer_denom_ms(
+                    self.frame_infos[frame_index].delay_ms as u32,
+                    1,
+                );
+                Ok(Ok(image::Frame::from_parts(image, 0, 0, delay)))
+            })
+            .collect::<AseResult<Vec<_>>>()?;
+        Ok(image::Frames::new(Box::new(frames.into_iter())))
+    }
+
+    /// Get the image of a slice in a frame, using the slice key in effect at that frame
+    ///
+    /// Visible layers are merged like [`Aseprite::get_frame_image`]. The image always has the
+    /// size of the slice bounds, parts outside of the canvas are transparent. Returns `None` if
+    /// there is no slice with this name or it only starts at a later frame.
+    pub fn get_slice_image_at_frame(
+        &self,
+        name: &str,
+        frame_index: usize,
+    ) -> AseResult<Option<AsepriteSliceImage>> {
+        if frame_index >= self.frame_count {
+            return Err(AsepriteInvalidError::InvalidFrame(frame_index).into());
+        }
+        let Some(key) = self
+            .get_slice_by_name(name)
+            .and_then(|slice| slice.key_for_frame(frame_index))
+        else {
+            return Ok(None);
+        };
+        let mut image = RgbaImage::new(key.bounds.width, key.bounds.height);
+        self.composite_region(
+            frame_index,
+            key.bounds,
+            &mut image,
+            false,
+            |layer_index, layer| {
+                !layer.is_reference() && self.is_layer_effectively_visible(layer_index)
+            },
+        )?;
+        Ok(Some(AsepriteSliceImage {
+            image,
+            nine_patch_info: key.nine_patch_info.clone(),
+            pivot: key.pivot.clone(),
+        }))
+    }
+
+    /// 合成整个画布大小的一帧，只绘制 include 返回 true 的图层
+    fn composite_frame<F>(&self, frame_index: usize, include: F) -> AseResult<RgbaImage>
+    where
+        F: Fn(usize, &AsepriteLayer) -> bool,
+    {
+        let (width, height) = self.dimensions;
+        let mut image = RgbaImage::new(width as u32, height as u32);
+        self.composite_region(
+            frame_index,
+            AsepriteRect::new(0, 0, width as u32, height as u32),
+            &mut image,
+            false,
+            include,
+        )?;
+        Ok(image)
+    }
+
+    /// 把某一帧在 region 内的部分合成到 target 上，target 的左上角对应 region 的左上角
+    /// precise 为 true 时 cel 按 precise bounds 缩放和放置
+    /// 只绘制 include 返回 true 的图层
+    fn composite_region<F>(
+        &self,
+        frame_index: usize,
+        region: AsepriteRect,
+        target: &mut RgbaImage,
+        precise: bool,
+        include: F,
+    ) -> AseResult<()>
+    where
+        F: Fn(usize, &AsepriteLayer) -> bool,
+    {
+        self.check_frame_loaded(frame_index)?;
+        let (width, height) = self.dimensions;
+        let canvas = AsepriteRect::new(0, 0, width as u32, height as u32);
+        let Some(clip) = region.intersection(&canvas) else {
+            return Ok(());
+        };
+        let order = self
+            .render_orders
+            .get(frame_index)
+            .map_or(&[][..], Vec::as_slice);
+        for &layer_index in order {
+            let Some(layer) = self.layers.get(&layer_index) else {
+                continue;
+            };
+            if !include(layer_index, layer) {
+                continue;
+            }
+            let Some(layer_cels) = self.cels.layer(layer_index) else {
+                continue;
+            };
+            let Some(cel) = layer_cels.get(frame_index) else {
+                continue;
+            };
+            let source = self.linked_target(layer_index, frame_index, layer_cels, cel)?;
+            let Some([cel_width, cel_height]) = source.get_size() else {
+                error!("Tried to read a linked cel, it should be resolved first.");
+                return Err(AsepriteError::InvalidConfiguration(
+                    AsepriteInvalidError::InvalidFrame(frame_index),
+                ));
+            };
+            let precise_bounds = cel.precise_bounds.or(source.precise_bounds);
+            let cel_rect = match precise_bounds.filter(|_| precise) {
+                Some(bounds) => bounds.rounded(),
+                None => AsepriteRect::new(
+                    cel.x as i32,
+                    cel.y as i32,
+                    cel_width as u32,
+                    cel_height as u32,
+                ),
+            };
+            // 先用 cel 的范围判断，不相交的 cel 不需要解码
+            let Some(visible) = cel_rect.intersection(&clip) else {
+                continue;
+            };
+            let opacity = self.effective_cel_opacity(layer_index, frame_index);
+            if opacity == 0 {
+                continue;
+            }
+            let pixels = self.decoded_pixels(source)?;
+            let target_width = target.width() as usize;
+            let target_bytes: &mut [u8] = target;
+            let scaled = cel_rect.width != cel_width as u32 || cel_rect.height != cel_height as u32;
+            // 缩放时用最近邻取样，先把一行取样到 row 里
+            let mut row = Vec::new();
+            for y in visible.y..visible.bottom() {
+                let target_start =
+                    ((y - region.y) as usize * target_width + (visible.x - region.x) as usize) * 4;
+                let src = if scaled {
+                    let src_y =
+                        (y - cel_rect.y) as usize * cel_height as usize / cel_rect.height as usize;
+                    row.clear();
+                    row.extend((visible.x..visible.right()).map(|x| {
+                        let src_x = (x - cel_rect.x) as usize * cel_width as usize
+                            / cel_rect.width as usize;
+                        pixels[src_y * cel_width as usize + src_x]
+                    }));
+                    &row[..]
+                } else {
+                    let src_start = (y - cel_rect.y) as usize * cel_width as usize
+                        + (visible.x - cel_rect.x) as usize;
+                    &pixels[src_start..src_start + visible.width as usize]
+                };
+                blend_row(
+                    &mut target_bytes[target_start..target_start + visible.width as usize * 4],
+                    src,
+                    opacity,
+                    layer.blend_mode(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// 把 cel 的像素按 opacity 画到和 cel 一样大的透明图像上
+    fn write_image(width: u16, height: u16, pixels: &[[u8; 4]], opacity: u8) -> RgbaImage {
+        // 完全不透明的行直接复制，不需要逐个像素计算
+        let mut buffer = Vec::with_capacity(pixels.len() * 4);
+        for row in pixels.chunks(width.max(1) as usize) {
+            if opacity == 255 && row.iter().all(|pixel| pixel[3] == 255) {
+                buffer.extend_from_slice(row.as_flattened());
+            } else {
+                for pixel in row {
+                    buffer.extend_from_slice(&blend_onto_transparent(*pixel, opacity));
                 }
-                // NOTE 这里如果不转成 u32 后计算的话，会导致栈溢出，u16 最多只能到 65_535
-                let raw_pixel = &pixels[(x as u32 + y as u32 * width as u32) as usize];
-                let pixel =
-                    Rgba(raw_pixel.get_rgba(self.palette.as_ref(), self.transparent_palette)?);
-
-                image
-                    .get_pixel_mut(pix_x as u32, pix_y as u32)
-                    .blend(&pixel);
             }
         }
-        Ok(image)
+        buffer.resize(width as usize * height as usize * 4, 0);
+        RgbaImage::from_raw(width as u32, height as u32, buffer)
+            .unwrap_or_else(|| RgbaImage::new(width as u32, height as u32))
     }
 
     /// Get images of each layer in this frame
@@ -207,48 +1064,135 @@ impl Aseprite {
     /// The key of return map is layer id
     pub fn get_image_by_layer_frame(
         &self,
-        layer_index: &usize,
-        frame_index: &usize,
+        layer_index: usize,
+        frame_index: usize,
     ) -> AseResult<Option<RgbaImage>> {
-        let Some(cel) = self.get_cel(layer_index, frame_index) else {
+        self.check_frame_loaded(frame_index)?;
+        if self.get_cel(layer_index, frame_index).is_none() {
             return Ok(None);
+        }
+        let resolved = self.resolve_cel(layer_index, frame_index)?;
+        // source 一定不是 linked cel
+        let [width, height] = resolved.source.get_size().unwrap_or_default();
+        Ok(Some(Self::write_image(
+            width,
+            height,
+            self.decoded_pixels(resolved.source)?,
+            resolved.opacity,
+        )))
+    }
+
+    /// Get the image of a layer in this frame, in canvas size with the cel at its position
+    ///
+    /// Parts of the cel outside of the canvas are clipped. Use
+    /// [`Aseprite::get_image_by_layer_frame`] to get the image trimmed to the cel instead.
+    pub fn get_image_by_layer_frame_on_canvas(
+        &self,
+        layer_index: usize,
+        frame_index: usize,
+    ) -> AseResult<Option<RgbaImage>> {
+        let mut image = RgbaImage::default();
+        let drawn = self.render_layer_into(layer_index, frame_index, &mut image)?;
+        Ok(drawn.then_some(image))
+    }
+
+    /// Render a layer like [`Aseprite::get_image_by_layer_frame_on_canvas`] into an existing
+    /// image
+    ///
+    /// `target` is cleared first and only reallocated if it does not have the canvas size.
+    /// Returns `false`, leaving `target` transparent, if the layer has no cel in this frame.
+    pub fn render_layer_into(
+        &self,
+        layer_index: usize,
+        frame_index: usize,
+        target: &mut RgbaImage,
+    ) -> AseResult<bool> {
+        let (width, height) = self.dimensions;
+        reset_image(target, width as u32, height as u32);
+        self.check_frame_loaded(frame_index)?;
+        if self.get_cel(layer_index, frame_index).is_none() {
+            return Ok(false);
+        }
+        let resolved = self.resolve_cel(layer_index, frame_index)?;
+        let cel_rect = resolved.bounds();
+        let canvas = AsepriteRect::new(0, 0, width as u32, height as u32);
+        let Some(visible) = cel_rect.intersection(&canvas) else {
+            return Ok(true);
         };
-        match &cel.raw_cel {
-            RawAsepriteCel::Raw {
-                width,
-                height,
-                pixels,
+        let pixels = self.decoded_pixels(resolved.source)?;
+        for y in visible.y..visible.bottom() {
+            let row = (y - cel_rect.y) as usize * cel_rect.width as usize;
+            for x in visible.x..visible.right() {
+                let pixel = pixels[row + (x - cel_rect.x) as usize];
+                target.get_pixel_mut(x as u32, y as u32).0 =
+                    blend_onto_transparent(pixel, resolved.opacity);
             }
-            | RawAsepriteCel::Compressed {
-                width,
-                height,
-                pixels,
-            } => Ok(Some(self.write_image(None, *width, *height, pixels)?)),
-            RawAsepriteCel::Linked { frame_position } => {
-                let frame_index = (*frame_position as usize) - 1;
-                let Some(linked_cel) = self.get_cel(layer_index, &frame_index) else {
-                    unimplemented!("不应该出现这种情况")
-                };
-                match &linked_cel.raw_cel {
-                    RawAsepriteCel::Raw {
-                        width,
-                        height,
-                        pixels,
-                    }
-                    | RawAsepriteCel::Compressed {
-                        width,
-                        height,
-                        pixels,
-                    } => Ok(Some(self.write_image(None, *width, *height, pixels)?)),
-                    RawAsepriteCel::Linked { frame_position } => {
-                        error!("Tried to draw a linked cel twice! This should not happen, linked cel should not link to a linked cel.");
-                        Err(AsepriteError::InvalidConfiguration(
-                            AsepriteInvalidError::InvalidFrame(*frame_position as usize),
-                        ))
-                    }
-                }
+        }
+        Ok(true)
+    }
+
+    /// Get the palette indices of a layer in this frame, without converting them to RGBA
+    ///
+    /// Like [`Aseprite::get_image_by_layer_frame`] the image has the size of the cel.
+    /// The transparent index is passed through untouched.
+    /// Returns [`AsepriteError::NotIndexed`] if the file does not use indexed colors.
+    pub fn get_indexed_image_by_layer_frame(
+        &self,
+        layer_index: usize,
+        frame_index: usize,
+    ) -> AseResult<Option<GrayImage>> {
+        if self.color_depth != AsepriteColorDepth::Indexed {
+            return Err(AsepriteError::NotIndexed);
+        }
+        self.check_frame_loaded(frame_index)?;
+        let Some(layer_cels) = self.cels.layer(layer_index) else {
+            return Ok(None);
+        };
+        let Some(cel) = layer_cels.get(frame_index) else {
+            return Ok(None);
+        };
+        let source = self.linked_target(layer_index, frame_index, layer_cels, cel)?;
+        let pixels = raw_pixels(&source.raw_cel)?;
+        if pixels.color_depth() != AsepriteColorDepth::Indexed {
+            return Err(AsepriteError::NotIndexed);
+        }
+        let [width, height] = source.get_size().unwrap_or_default();
+        Ok(GrayImage::from_raw(
+            width as u32,
+            height as u32,
+            pixels.as_bytes().to_vec(),
+        ))
+    }
+
+    /// Get the palette indices used by the cels of any layer and frame
+    ///
+    /// The transparent index is only reported if `include_transparent` is set.
+    /// Returns [`AsepriteError::NotIndexed`] if the file does not use indexed colors.
+    pub fn used_palette_indices(&self, include_transparent: bool) -> AseResult<BTreeSet<u8>> {
+        if self.color_depth != AsepriteColorDepth::Indexed {
+            return Err(AsepriteError::NotIndexed);
+        }
+        let mut used = BTreeSet::new();
+        // linked cel 和它指向的 cel 像素相同，只需要统计一次
+        for (_, cel) in self
+            .cels
+            .iter()
+            .flat_map(|(_, layer_cels)| layer_cels.iter())
+        {
+            if let RawAsepriteCel::Linked { .. } = cel.raw_cel {
+                continue;
+            }
+            let pixels = raw_pixels(&cel.raw_cel)?;
+            if pixels.color_depth() == AsepriteColorDepth::Indexed {
+                used.extend(pixels.as_bytes());
+            }
+        }
+        if !include_transparent {
+            if let Some(transparent) = self.transparent_palette {
+                used.remove(&transparent);
             }
         }
+        Ok(used)
     }
 }
 
@@ -256,20 +1200,30 @@ impl Aseprite {
     /// Construct a [`Aseprite`] from a [`RawAseprite`]
     pub fn from_raw(raw: RawAseprite) -> AseResult<Self> {
         let mut tags = BTreeMap::new();
+        let mut slices: BTreeMap<usize, AsepriteSlice> = BTreeMap::new();
         let mut layers = BTreeMap::new();
         let mut cels = BTreeMap::new();
         let mut palette = None;
         let mut frame_infos = vec![];
+        let mut color_profile = None;
+        let mut sprite_user_data = None;
 
         let frame_count = raw.frames.len();
 
         // 记录上一个处理过的 chunk 类型，处理 user data 时需要知道他跟随在哪个 chunk 后面
         let mut last_chunk_type = RawAsepriteChunkType::ColorProfile;
 
-        let mut cur_frame_index = 0;
-        for frame in raw.frames {
+        // 和 Aseprite 一样，帧时长为 0 时使用 header 中的默认速度
+        #[allow(deprecated)]
+        let default_delay_ms = raw.header.speed;
+
+        for (cur_frame_index, frame) in raw.frames.into_iter().enumerate() {
+            let delay_ms = match frame.duration_ms {
+                0 => default_delay_ms,
+                duration_ms => duration_ms,
+            };
             frame_infos.push(AsepriteFrameInfo {
-                delay_ms: frame.duration_ms as usize,
+                delay_ms: delay_ms as usize,
             });
 
             for chunk in frame.chunks {
@@ -283,21 +1237,31 @@ impl Aseprite {
                         blend_mode,
                         opacity,
                         name,
+                        uuid,
                     } => {
                         let layer_index = layers.len();
+                        // header flags: 1 = layer opacity 有效, 2 = group 的 opacity 也有效
+                        // 没有标记时 normal layer 按 Aseprite 的默认值 255 处理
+                        let opacity = match layer_type {
+                            AsepriteLayerType::Normal if raw.header.flags & 0x1 != 0 => {
+                                Some(opacity)
+                            }
+                            AsepriteLayerType::Normal => Some(255),
+                            AsepriteLayerType::Group if raw.header.flags & 0x2 != 0 => {
+                                Some(opacity)
+                            }
+                            AsepriteLayerType::Group => None,
+                        };
                         let layer = AsepriteLayer::new(
                             layer_index,
                             name,
                             layer_type,
-                            flags & 0x1 != 0,
+                            flags,
                             blend_mode,
-                            if raw.header.flags & 0x1 != 0 {
-                                Some(opacity)
-                            } else {
-                                None
-                            },
+                            opacity,
                             layer_child,
-                        );
+                        )
+                        .with_uuid(uuid);
                         layers.insert(layer_index, layer);
                         last_chunk_type = RawAsepriteChunkType::Layer;
                     }
@@ -322,45 +1286,50 @@ impl Aseprite {
                     }
                     RawAsepriteChunk::Tags { tags: raw_tags } => {
                         let start_index = tags.len();
-                        let mut cur_index = start_index;
-                        for raw_tag in raw_tags {
+                        for (cur_index, raw_tag) in (start_index..).zip(raw_tags) {
                             tags.insert(
                                 cur_index,
                                 AsepriteTag {
                                     index: cur_index,
-                                    frames: raw_tag.from..raw_tag.to,
+                                    frames: AsepriteTag::frame_range(&raw_tag, frame_count)?,
                                     animation_direction: raw_tag.anim_direction,
+                                    repeat: raw_tag.repeat,
                                     name: raw_tag.name,
                                     color: AsepriteColor::default(),
                                     user_data: String::new(),
+                                    properties: Vec::new(),
                                 },
                             );
-                            cur_index += 1;
                         }
                         last_chunk_type = RawAsepriteChunkType::Tags(start_index);
                     }
                     RawAsepriteChunk::Palette {
                         palette_size,
                         from_color,
-                        to_color: _,
+                        to_color,
                         entries,
                     } => {
-                        palette =
-                            Some(AsepritePalette::from_raw(palette_size, from_color, entries));
+                        palette
+                            .get_or_insert_with(AsepritePalette::default)
+                            .apply_raw(palette_size, from_color, to_color, entries);
                         last_chunk_type = RawAsepriteChunkType::Palette;
                     }
                     RawAsepriteChunk::UserData { data } => {
                         match &mut last_chunk_type {
                             RawAsepriteChunkType::Layer => {
-                                let id = layers.len() - 1;
-                                let layer = layers.get_mut(&id).unwrap();
+                                let id = layers.len().saturating_sub(1);
+                                let layer = layers
+                                    .get_mut(&id)
+                                    .ok_or(AsepriteInvalidError::InvalidLayer(id))?;
                                 layer.apply_raw_user_data(data);
                             }
                             // [Aseprite File Specs](https://github.com/aseprite/aseprite/blob/main/docs/ase-file-specs.md)
                             // After a Tags chunk, there will be several user data chunks, one for each tag,
                             // you should associate the user data in the same order as the tags are in the Tags chunk.
                             RawAsepriteChunkType::Tags(cur_index) => {
-                                let tag = tags.get_mut(&cur_index).unwrap();
+                                let tag = tags
+                                    .get_mut(cur_index)
+                                    .ok_or(AsepriteInvalidError::InvalidTag(*cur_index))?;
                                 tag.apply_raw_user_data(data);
                                 *cur_index += 1;
                             }
@@ -373,38 +1342,131 @@ impl Aseprite {
                                     .ok_or(AsepriteInvalidError::InvalidFrame(*frame_index))?;
                                 cel.color = data.color;
                                 cel.user_data = data.text;
+                                cel.properties = data.properties;
+                            }
+                            RawAsepriteChunkType::Slice(slice_index) => {
+                                let slice = slices
+                                    .get_mut(slice_index)
+                                    .ok_or(AsepriteInvalidError::InvalidSlice(*slice_index))?;
+                                slice.apply_raw_user_data(data);
+                            }
+                            // 第一帧中 palette 之后的 user data 属于整个 sprite
+                            RawAsepriteChunkType::Palette
+                                if cur_frame_index == 0 && sprite_user_data.is_none() =>
+                            {
+                                sprite_user_data = Some(data.into());
                             }
                             _ => {}
                         }
                     }
-                    RawAsepriteChunk::Slice { .. } => {
-                        todo!("Not yet implemented slice")
+                    RawAsepriteChunk::Slice {
+                        flags: _,
+                        name,
+                        slices: keys,
+                    } => {
+                        let slice_index = slices.len();
+                        slices.insert(slice_index, AsepriteSlice::new(slice_index, name, keys));
+                        last_chunk_type = RawAsepriteChunkType::Slice(slice_index);
                     }
-                    RawAsepriteChunk::CelExtra { .. } => {
-                        todo!("Not yet implemented cel extra")
+                    RawAsepriteChunk::CelExtra {
+                        flags,
+                        x,
+                        y,
+                        width,
+                        height,
+                    } => {
+                        // flags: 1 = precise bounds are set，属于前面的 cel
+                        if let RawAsepriteChunkType::Cel(layer_index, frame_index) = last_chunk_type
+                        {
+                            if let Some(cel) = cels
+                                .get_mut(&layer_index)
+                                .and_then(|layer_cels| layer_cels.get_mut(&frame_index))
+                                .filter(|_| flags & 0x1 != 0)
+                            {
+                                cel.precise_bounds = Some(AsepritePreciseBounds {
+                                    x,
+                                    y,
+                                    width,
+                                    height,
+                                });
+                            }
+                        }
                     }
-                    RawAsepriteChunk::ColorProfile { .. } => {
-                        error!("Not yet implemented color profile")
-                        // todo!("Not yet implemented color profile")
+                    RawAsepriteChunk::ColorProfile {
+                        profile_type,
+                        flags,
+                        gamma,
+                        icc_profile,
+                    } => {
+                        color_profile.get_or_insert(AsepriteColorProfile::from_raw(
+                            profile_type,
+                            flags,
+                            gamma,
+                            icc_profile,
+                        ));
                     }
+                    // 不会有 user data 跟在后面，last_chunk_type 不变
+                    RawAsepriteChunk::Deprecated { .. } => {}
                 }
             }
-            cur_frame_index += 1;
         }
 
+        let transparent_palette = if raw.header.color_depth == AsepriteColorDepth::Indexed {
+            Some(raw.header.transparent_palette)
+        } else {
+            None
+        };
+        let indexed_colors = transparent_palette.map(|_| {
+            Box::new(std::array::from_fn(|index| {
+                AsepritePixel::Indexed(index as u8)
+                    .get_rgba(palette.as_ref(), transparent_palette)
+                    .ok()
+            }))
+        });
+
+        let cels = CelGrid::new(cels, frame_count);
+        let render_orders = (0..frame_count)
+            .map(|frame_index| render_order(&cels, frame_index))
+            .collect();
+
+        let header = &raw.header;
+        let grid = (header.grid_width != 0 && header.grid_height != 0).then(|| {
+            AsepriteRect::new(
+                header.grid_x as i32,
+                header.grid_y as i32,
+                header.grid_width as u32,
+                header.grid_height as u32,
+            )
+        });
+
         Ok(Aseprite {
             dimensions: (raw.header.width, raw.header.height),
-            transparent_palette: if raw.header.color_depth == AsepriteColorDepth::Indexed {
-                Some(raw.header.transparent_palette)
-            } else {
-                None
-            },
+            color_depth: raw.header.color_depth,
+            transparent_palette,
+            indexed_colors,
             tags,
+            slices,
             layers,
             cels,
             frame_count,
             palette,
             frame_infos,
+            default_delay_ms,
+            layer_opacity_valid: raw.header.flags & 0x1 != 0,
+            frame_hashes: (0..frame_count).map(|_| OnceLock::new()).collect(),
+            render_orders,
+            loaded_frames: None,
+            render_cache: Default::default(),
+            render_threads: None,
+            header_flags: raw.header.flags,
+            // 为 0 时和 1:1 相同
+            pixel_ratio: match (raw.header.pixel_width, raw.header.pixel_height) {
+                (0, _) | (_, 0) => (1, 1),
+                ratio => ratio,
+            },
+            grid,
+            color_profile,
+            sprite_user_data,
         })
     }
 
@@ -412,26 +1474,105 @@ impl Aseprite {
     pub fn from_path<S: AsRef<Path>>(path: S) -> AseResult<Self> {
         let buffer = std::fs::read(path)?;
 
-        let raw_aseprite = crate::raw::read_aseprite(&buffer)?;
-
-        Ok(Self::from_raw(raw_aseprite)?)
+        Self::from_bytes(buffer)
     }
 
     /// Construct a [`Aseprite`] from a `&[u8]`
     pub fn from_bytes<S: AsRef<[u8]>>(buffer: S) -> AseResult<Self> {
-        let raw_aseprite = crate::raw::read_aseprite(buffer.as_ref())?;
+        let raw_aseprite = crate::raw::read_aseprite_with(
+            buffer.as_ref(),
+            CelPixels::Defer,
+            CelFilter::default(),
+        )?;
 
-        Ok(Self::from_raw(raw_aseprite)?)
+        Self::from_raw(raw_aseprite)
+    }
+
+    /// Construct a [`Aseprite`] from a `&[u8]`, reporting the loading progress
+    ///
+    /// The callback receives the current [`LoadPhase`] and the fraction of it that is done,
+    /// once per frame and every few cels. Every phase ends with a fraction of `1.0`.
+    pub fn from_bytes_with_progress<S, F>(buffer: S, mut progress: F) -> AseResult<Self>
+    where
+        S: AsRef<[u8]>,
+        F: FnMut(LoadPhase, f32),
+    {
+        let raw_aseprite = crate::raw::read_aseprite_with_progress(buffer.as_ref(), &mut progress)?;
+
+        Self::from_raw(raw_aseprite)
+    }
+
+    /// Construct a [`Aseprite`] from a `&[u8]`, refusing files bigger than the given limits
+    ///
+    /// The limits are checked before any pixel data is allocated, use this for untrusted files.
+    /// With [`ParseOptions::skip_pixels`] only the metadata is loaded, with
+    /// [`ParseOptions::frames`] and [`ParseOptions::layers`] only the pixels of some frames
+    /// and layers.
+    pub fn from_bytes_with<S: AsRef<[u8]>>(buffer: S, options: ParseOptions) -> AseResult<Self> {
+        let buffer = buffer.as_ref();
+        let header = crate::raw::read_header(buffer)?;
+        options.check(buffer, &header)?;
+
+        let cel_pixels = if options.skip_pixels {
+            CelPixels::Skip
+        } else {
+            CelPixels::Defer
+        };
+        let requested = options.frames.resolve(buffer, header.frames as usize)?;
+        // linked cel 的像素在别的帧，那些帧的 cel 也要读取
+        let loaded = requested.as_ref().map(|requested| {
+            let mut loaded = requested.clone();
+            for source in crate::raw::linked_cel_sources(buffer, requested) {
+                if let Some(frame) = loaded.get_mut(source) {
+                    *frame = true;
+                }
+            }
+            loaded
+        });
+        let layers = options.layers.resolve(buffer)?;
+        let cel_filter = CelFilter {
+            frames: loaded,
+            layers: layers.clone(),
+        };
+        let raw_aseprite = crate::raw::read_aseprite_with(buffer, cel_pixels, cel_filter)?;
+        let mut aseprite = Self::from_raw(raw_aseprite)?;
+        aseprite.loaded_frames = requested;
+        if let Some(layers) = layers {
+            // 没选中的 layer 当作没有 cel
+            aseprite
+                .cels
+                .retain_layers(|layer_index| layers.get(layer_index).copied().unwrap_or(false));
+            aseprite.render_orders = (0..aseprite.frame_count)
+                .map(|frame_index| render_order(&aseprite.cels, frame_index))
+                .collect();
+        }
+        Ok(aseprite)
+    }
+
+    /// Construct a [`Aseprite`] from a [`Path`] with the given [`ParseOptions`]
+    ///
+    /// See [`Aseprite::from_bytes_with`].
+    pub fn from_path_with<S: AsRef<Path>>(path: S, options: ParseOptions) -> AseResult<Self> {
+        let buffer = std::fs::read(path)?;
+
+        Self::from_bytes_with(buffer, options)
     }
 }
 
 /// Information about a single animation frame
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AsepriteFrameInfo {
     /// The delay of this frame in milliseconds
     pub delay_ms: usize,
 }
 
+impl AsepriteFrameInfo {
+    /// The delay of this frame
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.delay_ms as u64)
+    }
+}
+
 /// Single frame in an aseprite
 /// TODO 目前看没必要存在这个结构，有空都统一到 Aseprite 上
 pub struct AsepriteFrame<'a> {
@@ -440,107 +1581,136 @@ pub struct AsepriteFrame<'a> {
 }
 
 impl<'a> AsepriteFrame<'a> {
+    /// Size of the canvas this frame is drawn on, see [`Aseprite::size`]
+    pub fn size(&self) -> (u16, u16) {
+        self.aseprite.size()
+    }
+
     /// Get the timings
-    pub fn get_infos(&self) -> AseResult<&AsepriteFrameInfo> {
-        Ok(&self.aseprite.frame_infos[self.frame_index])
+    pub fn get_infos(&self) -> &AsepriteFrameInfo {
+        // 构造 AsepriteFrame 时已经检查过 frame_index
+        &self.aseprite.frame_infos[self.frame_index]
     }
 
-    /// Get images of each layer in this frame
+    /// Get the cels of this frame keyed by layer index
     ///
-    /// The key of return map is layer id
-    pub fn get_image_by_layer(&self, layer_index: &usize) -> AseResult<Option<RgbaImage>> {
+    /// Linked cels are not resolved, they are returned as [`CelRef::Linked`].
+    pub fn cels(&self) -> BTreeMap<usize, CelRef<'a>> {
         self.aseprite
-            .get_image_by_layer_frame(layer_index, &self.frame_index)
+            .cels_for_frame(self.frame_index)
+            .map(|(layer_index, cel)| (layer_index, CelRef::new(cel)))
+            .collect()
     }
-}
 
-/// 这个方法是获取某一帧所有可见图层合并后的图片
-///
-/// TODO 没有处理透明度和混合模式的效果
-#[allow(unused)]
-fn image_for_frame(aseprite: &Aseprite, frame_index: u16) -> AseResult<RgbaImage> {
-    let dim = aseprite.dimensions;
-    let frame_index = frame_index as usize;
-    let mut image = RgbaImage::new(dim.0 as u32, dim.1 as u32);
-    for (layer_index, layer) in &aseprite.layers {
-        if !layer.is_visible() {
-            continue;
-        }
+    /// Get the delay of this frame
+    pub fn duration(&self) -> Duration {
+        self.aseprite.frame_infos[self.frame_index].duration()
+    }
 
-        let Some(cel) = aseprite.get_cel(layer_index, &frame_index) else {
-            continue;
-        };
+    /// Get the image of this frame with all visible layers merged, see [`Aseprite::get_frame_image`]
+    pub fn get_image(&self) -> AseResult<RgbaImage> {
+        self.aseprite.get_frame_image(self.frame_index)
+    }
 
-        let mut write_to_image = |cel: &AsepriteCel,
-                                  width: u16,
-                                  height: u16,
-                                  pixels: &[AsepritePixel]|
-         -> AseResult<()> {
-            for x in 0..width {
-                for y in 0..height {
-                    let pix_x = cel.x + x as i16;
-                    let pix_y = cel.y + y as i16;
-
-                    if pix_x < 0 || pix_y < 0 {
-                        continue;
-                    }
-                    let raw_pixel = &pixels[(x + y * width) as usize];
-                    let pixel = Rgba(
-                        raw_pixel
-                            .get_rgba(aseprite.palette.as_ref(), aseprite.transparent_palette)?,
-                    );
-
-                    image
-                        .get_pixel_mut(pix_x as u32, pix_y as u32)
-                        .blend(&pixel);
-                }
-            }
-            Ok(())
+    /// Get the image of this frame cropped to its non transparent pixels
+    ///
+    /// The offset is the position of the cropped image on the canvas.
+    /// Returns `None` if the whole frame is transparent.
+    pub fn get_trimmed_image(&self) -> AseResult<Option<(RgbaImage, (u32, u32))>> {
+        let image = self.get_image()?;
+        let Some(bounds) = content_bounds(&image) else {
+            return Ok(None);
         };
+        let (x, y) = (bounds.x as u32, bounds.y as u32);
+        let trimmed =
+            image::imageops::crop_imm(&image, x, y, bounds.width, bounds.height).to_image();
+        Ok(Some((trimmed, (x, y))))
+    }
 
-        match &cel.raw_cel {
-            RawAsepriteCel::Raw {
-                width,
-                height,
-                pixels,
-            }
-            | RawAsepriteCel::Compressed {
-                width,
-                height,
-                pixels,
-            } => {
-                write_to_image(&cel, *width, *height, pixels)?;
-            }
-            RawAsepriteCel::Linked { frame_position } => {
-                let frame_index = *frame_position as usize - 1;
+    /// Get images of each layer in this frame
+    ///
+    /// The key of return map is layer id
+    pub fn get_image_by_layer(&self, layer_index: usize) -> AseResult<Option<RgbaImage>> {
+        self.aseprite
+            .get_image_by_layer_frame(layer_index, self.frame_index)
+    }
+}
 
-                let Some(linked_cel) = &aseprite.get_cel(layer_index, &frame_index) else {
-                    unimplemented!("不应该出现这种情况")
-                };
+/// 把 target 改成给定大小并清空，容量足够时不会重新分配
+fn reset_image(target: &mut RgbaImage, width: u32, height: u32) {
+    if target.dimensions() == (width, height) {
+        target.fill(0);
+        return;
+    }
+    let mut buffer = std::mem::take(target).into_raw();
+    buffer.clear();
+    buffer.resize(width as usize * height as usize * 4, 0);
+    *target =
+        RgbaImage::from_raw(width, height, buffer).unwrap_or_else(|| RgbaImage::new(width, height));
+}
 
-                match &linked_cel.raw_cel {
-                    RawAsepriteCel::Raw {
-                        width,
-                        height,
-                        pixels,
-                    }
-                    | RawAsepriteCel::Compressed {
-                        width,
-                        height,
-                        pixels,
-                    } => {
-                        write_to_image(&cel, *width, *height, pixels)?;
-                    }
-                    RawAsepriteCel::Linked { frame_position } => {
-                        error!("Tried to draw a linked cel twice!");
-                        return Err(AsepriteError::InvalidConfiguration(
-                            AsepriteInvalidError::InvalidFrame(*frame_position as usize),
-                        ));
-                    }
-                }
-            }
+/// 按 layer index + z-index 排序，相同时 z-index 小的先画，和 Aseprite 一致
+fn render_order(cels: &CelGrid, frame_index: usize) -> Vec<usize> {
+    let mut order: Vec<(usize, i16)> = cels
+        .iter()
+        .filter_map(|(layer_index, layer_cels)| {
+            let cel = layer_cels.get(frame_index)?;
+            Some((layer_index, cel.z_index))
+        })
+        .collect();
+    order.sort_by_key(|&(layer_index, z_index)| (layer_index as i64 + z_index as i64, z_index));
+    order
+        .into_iter()
+        .map(|(layer_index, _)| layer_index)
+        .collect()
+}
+
+/// 把像素画到全透明的背景上，和 Aseprite 的 normal 混合结果一致
+/// 完全透明的像素统一为 [0; 4]
+pub(super) fn blend_onto_transparent(pixel: [u8; 4], opacity: u8) -> [u8; 4] {
+    match mul_un8(pixel[3], opacity) {
+        0 => [0; 4],
+        alpha => [pixel[0], pixel[1], pixel[2], alpha],
+    }
+}
+
+/// cel 的原始像素，还没解压的 cel 在这里解压，linked cel 需要先找到实际的 cel
+fn raw_pixels(raw_cel: &RawAsepriteCel) -> AseResult<Cow<'_, PackedPixels>> {
+    match raw_cel {
+        RawAsepriteCel::Raw { pixels, .. } | RawAsepriteCel::Compressed { pixels, .. } => {
+            Ok(Cow::Borrowed(pixels))
+        }
+        RawAsepriteCel::Deferred { data, .. } => Ok(Cow::Owned(data.decompress()?)),
+        RawAsepriteCel::Skipped { .. } => Err(AsepriteError::PixelsNotLoaded),
+        RawAsepriteCel::Dropped { .. } => Err(AsepriteError::PixelsDropped),
+        RawAsepriteCel::Linked { frame_position } => {
+            error!("Tried to decode a linked cel, it should be resolved first.");
+            Err(AsepriteError::InvalidConfiguration(
+                AsepriteInvalidError::InvalidFrame(*frame_position as usize),
+            ))
         }
     }
+}
 
-    Ok(image)
+/// 找到图片中所有 alpha 不为 0 的像素的范围
+fn content_bounds(image: &RgbaImage) -> Option<AsepriteRect> {
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel.0[3] == 0 {
+            continue;
+        }
+        bounds = Some(match bounds {
+            None => (x, y, x, y),
+            Some((left, top, right, bottom)) => {
+                (left.min(x), top.min(y), right.max(x), bottom.max(y))
+            }
+        });
+    }
+    let (left, top, right, bottom) = bounds?;
+    Some(AsepriteRect::new(
+        left as i32,
+        top as i32,
+        right - left + 1,
+        bottom - top + 1,
+    ))
 }
@@ -0,0 +1,77 @@
+use image::RgbaImage;
+
+use crate::{error::AseResult, raw::AsepriteBlendMode};
+
+use super::{blend_row, Aseprite, OnionSkinOptions};
+
+/// 之前的帧染成红色，之后的帧染成蓝色
+const TINT_BEFORE: [u8; 3] = [255, 0, 0];
+const TINT_AFTER: [u8; 3] = [0, 0, 255];
+
+impl Aseprite {
+    /// Render a frame over faded copies of the frames around it, like the onion skin of Aseprite
+    ///
+    /// The other frames are drawn behind the current one, the furthest first, with the opacity
+    /// of [`OnionSkinOptions::opacity_falloff`]. Frames outside of the animation are skipped
+    /// unless [`OnionSkinOptions::looping`] is set. Each frame is rendered like
+    /// [`Aseprite::get_frame_image`].
+    pub fn render_onion_skin(
+        &self,
+        frame_index: usize,
+        options: &OnionSkinOptions,
+    ) -> AseResult<RgbaImage> {
+        let current = self.get_frame_image(frame_index)?;
+        let mut image = RgbaImage::new(current.width(), current.height());
+        let mut ghost = RgbaImage::default();
+        let mut pixels = Vec::new();
+        for step in (1..=options.before.max(options.after)).rev() {
+            let opacity = options.opacity_falloff.clamp(0.0, 1.0).powi(step as i32);
+            let opacity = (opacity * 255.0).round() as u8;
+            if opacity == 0 {
+                continue;
+            }
+            let ghosts = [
+                (step <= options.before, -(step as isize), TINT_BEFORE),
+                (step <= options.after, step as isize, TINT_AFTER),
+            ];
+            for (shown, offset, tint) in ghosts {
+                if !shown {
+                    continue;
+                }
+                let Some(ghost_index) = self.onion_skin_frame(frame_index, offset, options.looping)
+                else {
+                    continue;
+                };
+                self.render_frame_into(ghost_index, &mut ghost)?;
+                pixels.clear();
+                pixels.extend(ghost.pixels().map(|pixel| {
+                    let [r, g, b, a] = pixel.0;
+                    if !options.tint {
+                        return [r, g, b, a];
+                    }
+                    // 颜色和染色各占一半，保留透明度
+                    let mix = |channel: u8, tint: u8| ((channel as u16 + tint as u16) / 2) as u8;
+                    [mix(r, tint[0]), mix(g, tint[1]), mix(b, tint[2]), a]
+                }));
+                blend_row(&mut image, &pixels, opacity, AsepriteBlendMode::Normal);
+            }
+        }
+        pixels.clear();
+        pixels.extend(current.pixels().map(|pixel| pixel.0));
+        blend_row(&mut image, &pixels, 255, AsepriteBlendMode::Normal);
+        Ok(image)
+    }
+
+    /// 当前帧之前或之后 offset 帧，超出动画范围时 looping 决定是否从另一端继续
+    fn onion_skin_frame(&self, frame_index: usize, offset: isize, looping: bool) -> Option<usize> {
+        let target = if looping {
+            (frame_index as isize + offset).rem_euclid(self.frame_count as isize) as usize
+        } else {
+            frame_index
+                .checked_add_signed(offset)
+                .filter(|&target| target < self.frame_count)?
+        };
+        // 循环时可能绕回当前帧
+        (target != frame_index).then_some(target)
+    }
+}
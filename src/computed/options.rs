@@ -0,0 +1,398 @@
+use std::ops::Range;
+
+use crate::{
+    error::{AseResult, AsepriteError, AsepriteInvalidError, AsepriteLimit},
+    raw::{AsepriteColor, RawAsepriteChunk, RawAsepriteHeader},
+};
+
+/// The phases reported by [`Aseprite::from_bytes_with_progress`](crate::Aseprite::from_bytes_with_progress)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LoadPhase {
+    /// Reading the file header
+    Header,
+    /// Parsing the chunks of each frame, the fraction is `frames parsed / total frames`
+    Frames,
+    /// Decompressing the pixel data of the cels, weighted by their size
+    ///
+    /// This happens while the frames are parsed, so it is reported interleaved with
+    /// [`LoadPhase::Frames`].
+    Cels,
+}
+
+/// Which frames [`Aseprite::from_bytes_with`](crate::Aseprite::from_bytes_with) loads the
+/// pixels of
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FrameFilter {
+    /// Every frame
+    #[default]
+    All,
+    /// The frames in this range
+    Range(Range<usize>),
+    /// The listed frames
+    List(Vec<usize>),
+    /// The frames covered by the tags with this name
+    Tag(String),
+}
+
+impl FrameFilter {
+    /// 每一帧是否需要读取像素，`FrameFilter::All` 时为 None
+    pub(super) fn resolve(
+        &self,
+        buffer: &[u8],
+        frame_count: usize,
+    ) -> AseResult<Option<Vec<bool>>> {
+        let mut loaded = vec![false; frame_count];
+        let mut load = |frame_index: usize| {
+            if let Some(frame) = loaded.get_mut(frame_index) {
+                *frame = true;
+            }
+        };
+        match self {
+            FrameFilter::All => return Ok(None),
+            FrameFilter::Range(range) => range.clone().for_each(load),
+            FrameFilter::List(frames) => frames.iter().copied().for_each(load),
+            FrameFilter::Tag(name) => {
+                // tag 在第一帧，不用解析其他 chunk 就能找到
+                let raw = crate::raw::skim_aseprite(buffer)?;
+                let mut found = false;
+                for chunk in raw.frames.iter().flat_map(|frame| &frame.chunks) {
+                    let RawAsepriteChunk::Tags { tags } = chunk else {
+                        continue;
+                    };
+                    for tag in tags.iter().filter(|tag| &tag.name == name) {
+                        found = true;
+                        (tag.from as usize..=tag.to as usize).for_each(&mut load);
+                    }
+                }
+                if !found {
+                    return Err(AsepriteInvalidError::InvalidTagName(name.clone()).into());
+                }
+            }
+        }
+        Ok(Some(loaded))
+    }
+}
+
+/// Which layers [`Aseprite::from_bytes_with`](crate::Aseprite::from_bytes_with) loads the
+/// cels of
+///
+/// Group layers have no cels, selecting a group does not select the layers inside it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LayerFilter {
+    /// Every layer
+    #[default]
+    All,
+    /// The layers with these names
+    Names(Vec<String>),
+    /// The layers whose name starts with this prefix
+    Prefix(String),
+    /// The layers with these indices
+    Indices(Vec<usize>),
+}
+
+impl LayerFilter {
+    /// 每个 layer 是否需要读取 cel，`LayerFilter::All` 时为 None
+    pub(super) fn resolve(&self, buffer: &[u8]) -> AseResult<Option<Vec<bool>>> {
+        if *self == LayerFilter::All {
+            return Ok(None);
+        }
+        // layer chunk 在第一帧，不用解析其他 chunk 就能找到
+        let raw = crate::raw::skim_aseprite(buffer)?;
+        let names: Vec<&str> = raw
+            .frames
+            .iter()
+            .flat_map(|frame| &frame.chunks)
+            .filter_map(|chunk| match chunk {
+                RawAsepriteChunk::Layer { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        let mut loaded = vec![false; names.len()];
+        match self {
+            LayerFilter::All => return Ok(None),
+            LayerFilter::Names(selected) => {
+                for name in selected {
+                    let mut found = false;
+                    for (layer_index, _) in names.iter().enumerate().filter(|(_, n)| *n == name) {
+                        loaded[layer_index] = true;
+                        found = true;
+                    }
+                    if !found {
+                        return Err(AsepriteInvalidError::InvalidLayerName(name.clone()).into());
+                    }
+                }
+            }
+            LayerFilter::Prefix(prefix) => {
+                for (layer_index, name) in names.iter().enumerate() {
+                    loaded[layer_index] = name.starts_with(prefix.as_str());
+                }
+            }
+            LayerFilter::Indices(indices) => {
+                for &layer_index in indices {
+                    *loaded
+                        .get_mut(layer_index)
+                        .ok_or(AsepriteInvalidError::InvalidLayer(layer_index))? = true;
+                }
+            }
+        }
+        Ok(Some(loaded))
+    }
+}
+
+/// Limits applied by [`Aseprite::from_bytes_with`](crate::Aseprite::from_bytes_with)
+///
+/// The defaults are generous enough for real art, but keep a crafted file from
+/// allocating gigabytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Maximum `width * height` of the canvas
+    pub max_canvas_area: u64,
+    /// Maximum amount of frames
+    pub max_frames: u64,
+    /// Maximum amount of bytes the pixel data of all cels may decompress to
+    ///
+    /// Not checked when `skip_pixels` is set.
+    pub max_decompressed_bytes: u64,
+    /// Only load the metadata, cels keep their size and position but not their pixels
+    ///
+    /// Every method returning pixels fails with [`AsepriteError::PixelsNotLoaded`].
+    pub skip_pixels: bool,
+    /// Only load the pixels of these frames
+    ///
+    /// Frames keep their index and duration, and tags still refer to the same frames, but
+    /// rendering another frame fails with [`AsepriteError::FrameNotLoaded`].
+    pub frames: FrameFilter,
+    /// Only load the cels of these layers
+    ///
+    /// Layers keep their index and place in the layer tree, the other layers are treated
+    /// as if they had no cels.
+    pub layers: LayerFilter,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            max_canvas_area: 16384 * 16384,
+            max_frames: 10_000,
+            max_decompressed_bytes: 1 << 30,
+            skip_pixels: false,
+            frames: FrameFilter::All,
+            layers: LayerFilter::All,
+        }
+    }
+}
+
+/// Which layers [`Aseprite::render_frame`](crate::Aseprite::render_frame) draws, and onto what
+///
+/// The same options can be reused for every frame of an export.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameRenderOptions {
+    /// Only draw these layers, `None` draws every layer
+    pub include: Option<Vec<usize>>,
+    /// Never draw these layers, even if they are in `include`
+    pub exclude: Vec<usize>,
+    /// Also draw hidden layers and layers inside hidden groups
+    pub include_hidden: bool,
+    /// Fill the canvas with this color before drawing, transparent if `None`
+    pub background: Option<AsepriteColor>,
+    /// Place cels at their [`AsepritePreciseBounds`](crate::AsepritePreciseBounds) when
+    /// they have some
+    ///
+    /// The cel is scaled to the rounded precise size with nearest neighbor and drawn at the
+    /// rounded precise position, instead of at its integer position in its own size.
+    pub use_precise_bounds: bool,
+}
+
+/// What [`Aseprite::export_layers`](crate::Aseprite::export_layers) does with group layers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GroupExport {
+    /// Only export normal layers
+    #[default]
+    Skip,
+    /// Also export every group, with its visible layers merged
+    Merged,
+}
+
+/// Options for [`Aseprite::export_layers`](crate::Aseprite::export_layers)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayerExportOptions {
+    /// Also export hidden layers and layers inside hidden groups
+    pub include_hidden: bool,
+    /// Whether group layers are exported
+    pub groups: GroupExport,
+}
+
+/// The order frames are placed in by [`Aseprite::to_spritesheet`](crate::Aseprite::to_spritesheet)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpritesheetOrder {
+    /// Fill a row before going to the next one
+    #[default]
+    RowMajor,
+    /// Fill a column before going to the next one
+    ColumnMajor,
+}
+
+/// How [`Aseprite::to_spritesheet`](crate::Aseprite::to_spritesheet) places the frames
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpritesheetPacking {
+    /// Every frame gets a cell of the canvas size in a grid
+    #[default]
+    Grid,
+    /// Frames are trimmed to their content and packed as tightly as possible
+    ///
+    /// Identical frames share the same pixels in the sheet. `columns` and `order` are
+    /// ignored.
+    Packed {
+        /// Round the size of the sheet up to powers of two
+        power_of_two: bool,
+    },
+}
+
+/// How [`Aseprite::to_spritesheet`](crate::Aseprite::to_spritesheet) lays out the frames
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpritesheetOptions {
+    /// Only put the frames of the tag with this name, `None` puts every frame
+    pub tag: Option<String>,
+    /// Grid or tightly packed frames
+    pub packing: SpritesheetPacking,
+    /// Amount of columns of the grid, `None` puts every frame on a single row
+    pub columns: Option<usize>,
+    /// Order of the frames in the grid
+    pub order: SpritesheetOrder,
+    /// Transparent pixels between two frames
+    pub padding: u32,
+    /// Pixels the edges of every frame are repeated outwards
+    ///
+    /// This avoids texture bleeding when the sheet is sampled with filtering or mipmaps.
+    /// The padding is added between the extruded frames.
+    pub extrude: u32,
+    /// Put identical frames in a single cell of the grid
+    ///
+    /// Their [`SpritesheetFrame`](crate::SpritesheetFrame)s share the same rect. Packed sheets
+    /// always merge identical frames.
+    pub merge_duplicates: bool,
+}
+
+/// How [`Aseprite::export_json`](crate::Aseprite::export_json) lists the frames
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonFrameFormat {
+    /// An object keyed by frame name, like `aseprite --format json-hash`
+    #[default]
+    Hash,
+    /// An array with a `filename` in every frame, like `aseprite --format json-array`
+    Array,
+}
+
+/// Options for [`Aseprite::export_json`](crate::Aseprite::export_json)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonExportOptions {
+    /// Name of every frame, `{frame}` is replaced by the frame index
+    ///
+    /// A name already used by an earlier frame, e.g. when there is no `{frame}`, gets the
+    /// frame index appended after a space.
+    pub frame_name: String,
+    /// File name of the sheet, written to `meta.image`
+    pub image: String,
+    /// Object or array of frames
+    pub format: JsonFrameFormat,
+    /// Names of the slices defining the anchor of the frames, see [`Aseprite::anchor`]
+    ///
+    /// [`Aseprite::anchor`]: crate::Aseprite::anchor
+    pub anchor_slices: Vec<String>,
+}
+
+impl Default for JsonExportOptions {
+    fn default() -> Self {
+        JsonExportOptions {
+            frame_name: "{frame}".to_string(),
+            image: "sheet.png".to_string(),
+            format: JsonFrameFormat::Hash,
+            anchor_slices: vec!["pivot".to_string(), "anchor".to_string()],
+        }
+    }
+}
+
+/// Which frames [`Aseprite::render_onion_skin`](crate::Aseprite::render_onion_skin) shows
+/// around the current one, and how
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OnionSkinOptions {
+    /// Amount of previous frames drawn
+    pub before: usize,
+    /// Amount of next frames drawn
+    pub after: usize,
+    /// Opacity of the frames next to the current one, from 0 to 1
+    ///
+    /// Each step further away multiplies the opacity again, with 0.5 the frames 1, 2 and 3
+    /// steps away are drawn at 50%, 25% and 12.5%.
+    pub opacity_falloff: f32,
+    /// Tint previous frames red and next frames blue, like Aseprite does
+    pub tint: bool,
+    /// Wrap around the ends of the animation instead of skipping frames outside of it
+    pub looping: bool,
+}
+
+impl Default for OnionSkinOptions {
+    fn default() -> Self {
+        OnionSkinOptions {
+            before: 1,
+            after: 1,
+            opacity_falloff: 0.5,
+            tint: false,
+            looping: false,
+        }
+    }
+}
+
+impl FrameRenderOptions {
+    /// 判断某个图层是否需要绘制，不考虑图层是否可见
+    pub(super) fn selects(&self, layer_index: usize) -> bool {
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.contains(&layer_index))
+            && !self.exclude.contains(&layer_index)
+    }
+
+    /// 所有提到的图层 index
+    pub(super) fn layer_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.include.iter().flatten().chain(&self.exclude).copied()
+    }
+}
+
+impl ParseOptions {
+    /// Only load the pixels of the given frames, see [`ParseOptions::frames`]
+    pub fn frames(mut self, frames: FrameFilter) -> Self {
+        self.frames = frames;
+        self
+    }
+
+    /// Only load the cels of the given layers, see [`ParseOptions::layers`]
+    pub fn layers(mut self, layers: LayerFilter) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    pub(super) fn check(&self, buffer: &[u8], header: &RawAsepriteHeader) -> AseResult<()> {
+        let check = |limit, value, max| {
+            if value > max {
+                Err(AsepriteError::LimitExceeded { limit, value, max })
+            } else {
+                Ok(())
+            }
+        };
+
+        check(
+            AsepriteLimit::CanvasArea,
+            header.width as u64 * header.height as u64,
+            self.max_canvas_area,
+        )?;
+        check(AsepriteLimit::Frames, header.frames as u64, self.max_frames)?;
+        if self.skip_pixels {
+            return Ok(());
+        }
+        check(
+            AsepriteLimit::DecompressedBytes,
+            crate::raw::declared_cel_bytes(buffer, header),
+            self.max_decompressed_bytes,
+        )
+    }
+}
@@ -1,32 +1,160 @@
+use std::{collections::HashMap, fmt::Write};
+
+use image::{Rgba, RgbaImage};
+
 use crate::raw::{AsepriteColor, RawAsepritePaletteEntry};
 
 /// The palette entries in the aseprite file
 #[allow(missing_docs)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct AsepritePalette {
     pub entries: Vec<AsepriteColor>,
 }
 
 impl AsepritePalette {
-    pub(super) fn from_raw(
+    /// 应用一个 palette chunk，chunk 只会更新 from_color 到 to_color 之间的颜色，
+    /// 后面帧中的 palette chunk 是在之前的 palette 基础上修改的
+    pub(super) fn apply_raw(
+        &mut self,
         palette_size: u32,
         from_color: u32,
+        to_color: u32,
         raw_entries: Vec<RawAsepritePaletteEntry>,
-    ) -> Self {
-        let mut entries = vec![
-            AsepriteColor {
-                red: 0,
-                green: 0,
-                blue: 0,
-                alpha: 0,
-            };
-            palette_size as usize
-        ];
-
-        for (raw_idx, idx) in ((from_color as usize)..entries.len()).enumerate() {
-            entries[idx] = raw_entries[raw_idx].color;
+    ) {
+        // palette_size 来自文件，新增的颜色都在这个 chunk 里，所以最多增长到它带的最后一个颜色
+        let parsed_end = (from_color as usize).saturating_add(raw_entries.len());
+        let size = (palette_size as usize).min(self.entries.len().max(parsed_end));
+        self.entries.resize(size, AsepriteColor::default());
+
+        let last = (to_color as usize).min(self.entries.len().saturating_sub(1));
+        for (idx, raw_entry) in ((from_color as usize)..=last).zip(raw_entries) {
+            self.entries[idx] = raw_entry.color;
+        }
+    }
+
+    /// Amount of entries in the palette
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the palette has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get the color at the given index, or `None` if it is outside of the palette
+    pub fn get(&self, index: usize) -> Option<AsepriteColor> {
+        self.entries.get(index).copied()
+    }
+
+    /// Get the index of the entry closest to the given color
+    ///
+    /// Colors are compared with the squared distance of their RGBA channels, ties resolve to
+    /// the lowest index. Returns 0 for an empty palette.
+    pub fn nearest(&self, color: AsepriteColor) -> usize {
+        nearest_index(&self.entries, color, None)
+    }
+
+    /// Map every pixel of an image to the index of its closest entry, row by row
+    ///
+    /// Only the first 256 entries are used. Fully transparent pixels are mapped to the first
+    /// fully transparent entry, or to 0 if there is none, like the default transparent index
+    /// of Aseprite. Other pixels are never mapped to that transparent entry, so they can not
+    /// become holes, unless it is the only entry.
+    pub fn quantize_image(&self, image: &RgbaImage) -> Vec<u8> {
+        let entries = &self.entries[..self.entries.len().min(256)];
+        let transparent_entry = entries.iter().position(|color| color.alpha == 0);
+        let transparent = transparent_entry.unwrap_or(0) as u8;
+        // 图片里通常只有少量不同的颜色，缓存查找结果
+        let mut cache: HashMap<[u8; 4], u8> = HashMap::new();
+        image
+            .pixels()
+            .map(|&Rgba([red, green, blue, alpha])| {
+                if alpha == 0 {
+                    return transparent;
+                }
+                *cache.entry([red, green, blue, alpha]).or_insert_with(|| {
+                    let color = AsepriteColor {
+                        red,
+                        green,
+                        blue,
+                        alpha,
+                    };
+                    nearest_index(entries, color, transparent_entry) as u8
+                })
+            })
+            .collect()
+    }
+
+    /// Export the palette as a GIMP palette (`.gpl`)
+    ///
+    /// GPL has no alpha, fully transparent entries are preceded by a `# transparent` comment.
+    pub fn to_gpl(&self, name: &str) -> String {
+        let mut gpl = format!("GIMP Palette\nName: {}\n#\n", name);
+        for (index, color) in self.entries.iter().enumerate() {
+            if color.alpha == 0 {
+                gpl.push_str("# transparent\n");
+            }
+            // 写入 String 不会失败
+            let _ = writeln!(
+                gpl,
+                "{:3} {:3} {:3}\tIndex {}",
+                color.red, color.green, color.blue, index
+            );
+        }
+        gpl
+    }
+
+    /// Export the palette as an Adobe Color Table (`.act`)
+    ///
+    /// The table always holds 256 RGB triplets, missing entries are black and entries after
+    /// the 256th are dropped. It is followed by the number of entries and the index of the
+    /// first fully transparent entry (`0xFFFF` if there is none), both big endian `u16`.
+    pub fn to_act(&self) -> Vec<u8> {
+        let mut act = vec![0; 256 * 3];
+        for (triplet, color) in act.chunks_exact_mut(3).zip(&self.entries) {
+            triplet.copy_from_slice(&[color.red, color.green, color.blue]);
         }
+        let count = self.entries.len().min(256) as u16;
+        let transparent = self
+            .entries
+            .iter()
+            .take(256)
+            .position(|color| color.alpha == 0)
+            .map_or(0xFFFF, |index| index as u16);
+        act.extend_from_slice(&count.to_be_bytes());
+        act.extend_from_slice(&transparent.to_be_bytes());
+        act
+    }
 
-        AsepritePalette { entries }
+    /// Export the palette as a one pixel tall image, one pixel per entry
+    pub fn to_image(&self) -> RgbaImage {
+        let mut image = RgbaImage::new(self.entries.len() as u32, 1);
+        for (pixel, color) in image.pixels_mut().zip(&self.entries) {
+            *pixel = (*color).into();
+        }
+        image
     }
-}
\ No newline at end of file
+}
+
+/// 按 RGBA 的平方距离找最接近的颜色，距离相同时取较小的 index
+/// skip 指定的 entry 不参与比较，除非只有它一个
+fn nearest_index(entries: &[AsepriteColor], color: AsepriteColor, skip: Option<usize>) -> usize {
+    let distance = |entry: &AsepriteColor| {
+        [
+            (entry.red, color.red),
+            (entry.green, color.green),
+            (entry.blue, color.blue),
+            (entry.alpha, color.alpha),
+        ]
+        .iter()
+        .map(|&(a, b)| (a as i32 - b as i32).pow(2) as u32)
+        .sum::<u32>()
+    };
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| Some(*index) != skip)
+        .min_by_key(|(_, entry)| distance(entry))
+        .map_or(skip.unwrap_or(0), |(index, _)| index)
+}
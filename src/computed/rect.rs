@@ -0,0 +1,68 @@
+/// A rectangle in canvas coordinates
+///
+/// `x`/`y` is the top-left corner, the right and bottom edges are exclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct AsepriteRect {
+    /// x position of the left edge
+    pub x: i32,
+    /// y position of the top edge
+    pub y: i32,
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+}
+
+impl AsepriteRect {
+    /// Create a rectangle from its top-left corner and size
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        AsepriteRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// x position of the right edge (exclusive)
+    pub fn right(&self) -> i32 {
+        self.x + self.width as i32
+    }
+
+    /// y position of the bottom edge (exclusive)
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+
+    /// Whether the rectangle covers no pixels
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Whether the point is inside the rectangle, the right and bottom edges are excluded
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (self.x..self.right()).contains(&x) && (self.y..self.bottom()).contains(&y)
+    }
+
+    /// The overlapping part of two rectangles, `None` if they do not overlap
+    pub fn intersection(&self, other: &AsepriteRect) -> Option<AsepriteRect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        if right <= x || bottom <= y {
+            return None;
+        }
+        Some(AsepriteRect::new(
+            x,
+            y,
+            (right - x) as u32,
+            (bottom - y) as u32,
+        ))
+    }
+
+    /// Whether two rectangles overlap
+    pub fn intersects(&self, other: &AsepriteRect) -> bool {
+        self.intersection(other).is_some()
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use image::RgbaImage;
+
+use crate::error::AseResult;
+
+use super::Aseprite;
+
+/// 默认最多缓存的帧数
+const DEFAULT_CAPACITY: usize = 16;
+
+/// Rendered frames kept by [`Aseprite::get_frame_image_cached`]
+///
+/// Cloning an [`Aseprite`] gives an empty cache with the same capacity.
+pub(super) struct RenderCache {
+    capacity: usize,
+    /// 最近用到的帧在最后
+    frames: Mutex<Vec<(usize, Arc<RgbaImage>)>>,
+}
+
+impl Default for RenderCache {
+    fn default() -> Self {
+        RenderCache {
+            capacity: DEFAULT_CAPACITY,
+            frames: Mutex::default(),
+        }
+    }
+}
+
+impl Clone for RenderCache {
+    fn clone(&self) -> Self {
+        RenderCache {
+            capacity: self.capacity,
+            frames: Mutex::default(),
+        }
+    }
+}
+
+impl std::fmt::Debug for RenderCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RenderCache")
+            .field("capacity", &self.capacity)
+            .field("cached", &self.lock().len())
+            .finish()
+    }
+}
+
+impl RenderCache {
+    /// 缓存只是加速，锁被 poison 时里面的数据依然可用
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<(usize, Arc<RgbaImage>)>> {
+        self.frames.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 缓存的帧占用的字节数
+    pub(super) fn memory_usage(&self) -> usize {
+        self.lock()
+            .iter()
+            .map(|(_, image)| image.as_raw().len())
+            .sum()
+    }
+}
+
+impl Aseprite {
+    /// Get the image of a frame like [`Aseprite::get_frame_image`], keeping it for later calls
+    ///
+    /// The most recently used frames are kept, 16 by default, see
+    /// [`Aseprite::set_render_cache_capacity`]. Every cached frame holds a full canvas of RGBA
+    /// pixels, i.e. `width * height * 4` bytes, for as long as it stays in the cache or an
+    /// [`Arc`] to it is alive. Call [`Aseprite::clear_render_cache`] to release them.
+    pub fn get_frame_image_cached(&self, frame_index: usize) -> AseResult<Arc<RgbaImage>> {
+        let cache = &self.render_cache;
+        {
+            let mut frames = cache.lock();
+            if let Some(position) = frames.iter().position(|(index, _)| *index == frame_index) {
+                let entry = frames.remove(position);
+                let image = entry.1.clone();
+                frames.push(entry);
+                return Ok(image);
+            }
+        }
+
+        // 渲染时不持有锁，其他线程可以同时读取缓存
+        let image = Arc::new(self.get_frame_image(frame_index)?);
+        if cache.capacity > 0 {
+            let mut frames = cache.lock();
+            if !frames.iter().any(|(index, _)| *index == frame_index) {
+                if frames.len() >= cache.capacity {
+                    frames.remove(0);
+                }
+                frames.push((frame_index, image.clone()));
+            }
+        }
+        Ok(image)
+    }
+
+    /// Drop every frame kept by [`Aseprite::get_frame_image_cached`]
+    pub fn clear_render_cache(&self) {
+        self.render_cache.lock().clear();
+    }
+
+    /// Set how many frames [`Aseprite::get_frame_image_cached`] keeps, 0 disables the cache
+    ///
+    /// The least recently used frames are dropped if there are too many.
+    pub fn set_render_cache_capacity(&mut self, frames: usize) {
+        self.render_cache.capacity = frames;
+        let mut cached = self.render_cache.lock();
+        let excess = cached.len().saturating_sub(frames);
+        cached.drain(..excess);
+    }
+}
@@ -0,0 +1,231 @@
+use image::{imageops, RgbaImage};
+
+use crate::raw::{
+    AsepriteColor, AsepriteNinePatchInfo, AsepritePivot, AsepritePropertiesMap, RawAsepriteSlice,
+    RawAsepriteUserData,
+};
+
+use super::AsepriteRect;
+
+#[derive(Debug, Clone, PartialEq)]
+/// A single Aseprite slice
+pub struct AsepriteSlice {
+    /// The slice index
+    pub index: usize,
+    /// The slice name
+    pub name: String,
+    /// The keys of this slice, each one is valid from its frame until the next key
+    pub keys: Vec<AsepriteSliceKey>,
+    /// Slice color
+    pub color: AsepriteColor,
+    /// Slice user data
+    pub user_data: String,
+    /// Slice user data properties
+    pub properties: Vec<AsepritePropertiesMap>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The bounds of a slice starting from a given frame
+pub struct AsepriteSliceKey {
+    /// The first frame this key is valid for
+    pub from_frame: u32,
+    /// Bounds of the slice on the canvas
+    pub bounds: AsepriteRect,
+    /// 9-Patch info, if any
+    pub nine_patch_info: Option<AsepriteNinePatchInfo>,
+    /// Pivot, relative to the slice origin, if any
+    pub pivot: Option<AsepritePivot>,
+}
+
+impl AsepriteSliceKey {
+    /// The nine parts of a 9-patch slice on the canvas, `None` without 9-patch info
+    ///
+    /// The parts are in row-major order, from the top-left corner to the bottom-right one.
+    /// The center is clamped to the slice bounds, so parts falling outside the slice, e.g. the
+    /// sides of a center spanning the full width, are empty rectangles.
+    pub fn nine_patch_rects(&self) -> Option<[AsepriteRect; 9]> {
+        let info = self.nine_patch_info.as_ref()?;
+        let columns = clamped_edges(self.bounds.x, self.bounds.width, info.x_center, info.width);
+        let rows = clamped_edges(
+            self.bounds.y,
+            self.bounds.height,
+            info.y_center,
+            info.height,
+        );
+        let mut rects = [AsepriteRect::default(); 9];
+        for (index, rect) in rects.iter_mut().enumerate() {
+            let (row, column) = (index / 3, index % 3);
+            *rect = AsepriteRect::new(
+                columns[column],
+                rows[row],
+                (columns[column + 1] - columns[column]) as u32,
+                (rows[row + 1] - rows[row]) as u32,
+            );
+        }
+        Some(rects)
+    }
+}
+
+/// How the sides and the center of a 9-patch fill the space between the corners
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NinePatchMode {
+    /// Scale the pieces to fill the space
+    #[default]
+    Stretch,
+    /// Repeat the pieces at their original size, cutting the last repetition
+    Tile,
+}
+
+/// The pixels of a slice, with the 9-patch info needed to resize it
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsepriteSliceImage {
+    /// The pixels inside the slice bounds
+    pub image: RgbaImage,
+    /// 9-Patch info relative to the image, if any
+    pub nine_patch_info: Option<AsepriteNinePatchInfo>,
+    /// Pivot relative to the image, if any
+    pub pivot: Option<AsepritePivot>,
+}
+
+impl AsepriteSliceImage {
+    /// Resize the slice image to the target size, keeping the 9-patch corners intact
+    ///
+    /// Without 9-patch info the whole image is treated as the center. When the target is
+    /// smaller than the corners, the corners are scaled down proportionally.
+    pub fn render(&self, target_width: u32, target_height: u32, mode: NinePatchMode) -> RgbaImage {
+        let (width, height) = self.image.dimensions();
+        let (x_center, y_center, center_width, center_height) = match &self.nine_patch_info {
+            Some(info) => (info.x_center, info.y_center, info.width, info.height),
+            None => (0, 0, width, height),
+        };
+        let source_columns = clamped_edges(0, width, x_center, center_width);
+        let source_rows = clamped_edges(0, height, y_center, center_height);
+        let target_columns = target_edges(source_columns, target_width);
+        let target_rows = target_edges(source_rows, target_height);
+
+        let mut target = RgbaImage::new(target_width, target_height);
+        for row in 0..3 {
+            for column in 0..3 {
+                let source = AsepriteRect::new(
+                    source_columns[column],
+                    source_rows[row],
+                    (source_columns[column + 1] - source_columns[column]) as u32,
+                    (source_rows[row + 1] - source_rows[row]) as u32,
+                );
+                let destination = AsepriteRect::new(
+                    target_columns[column],
+                    target_rows[row],
+                    (target_columns[column + 1] - target_columns[column]) as u32,
+                    (target_rows[row + 1] - target_rows[row]) as u32,
+                );
+                if source.is_empty() || destination.is_empty() {
+                    continue;
+                }
+                let piece = imageops::crop_imm(
+                    &self.image,
+                    source.x as u32,
+                    source.y as u32,
+                    source.width,
+                    source.height,
+                )
+                .to_image();
+                // 平铺模式下，中间的列/行保持原尺寸重复，角落和两端仍然缩放
+                let tile = mode == NinePatchMode::Tile;
+                let tile_width = if tile && column == 1 {
+                    source.width
+                } else {
+                    destination.width
+                };
+                let tile_height = if tile && row == 1 {
+                    source.height
+                } else {
+                    destination.height
+                };
+                let piece = if piece.dimensions() == (tile_width, tile_height) {
+                    piece
+                } else {
+                    imageops::resize(
+                        &piece,
+                        tile_width,
+                        tile_height,
+                        imageops::FilterType::Nearest,
+                    )
+                };
+                for y in 0..destination.height {
+                    for x in 0..destination.width {
+                        target.put_pixel(
+                            destination.x as u32 + x,
+                            destination.y as u32 + y,
+                            *piece.get_pixel(x % tile_width, y % tile_height),
+                        );
+                    }
+                }
+            }
+        }
+        target
+    }
+}
+
+/// 按目标尺寸重新分配四条边，两端保持原尺寸，放不下时按比例缩小
+fn target_edges(source: [i32; 4], target: u32) -> [i32; 4] {
+    let start = (source[1] - source[0]) as u64;
+    let end = (source[3] - source[2]) as u64;
+    let target = target as u64;
+    let (start, end) = if start + end > target {
+        let scaled = target * start / (start + end);
+        (scaled, target - scaled)
+    } else {
+        (start, end)
+    };
+    [0, start, target - end, target].map(|edge| edge as i32)
+}
+
+/// 把 9-patch 中心在一个方向上的两条边限制在 slice 范围内，返回从左到右的四条边
+fn clamped_edges(start: i32, len: u32, center: i32, center_len: u32) -> [i32; 4] {
+    let start = start as i64;
+    let end = start + len as i64;
+    let center_start = (start + center as i64).clamp(start, end);
+    let center_end = (start + center as i64 + center_len as i64).clamp(center_start, end);
+    [start, center_start, center_end, end].map(|edge| edge as i32)
+}
+
+impl AsepriteSlice {
+    pub(super) fn new(index: usize, name: String, raw_keys: Vec<RawAsepriteSlice>) -> Self {
+        let mut keys: Vec<AsepriteSliceKey> = raw_keys
+            .into_iter()
+            .map(|key| AsepriteSliceKey {
+                from_frame: key.frame,
+                bounds: AsepriteRect::new(key.x_origin, key.y_origin, key.width, key.height),
+                nine_patch_info: key.nine_patch_info,
+                pivot: key.pivot,
+            })
+            .collect();
+        // 按起始帧排序，方便查找某一帧生效的 key
+        keys.sort_by_key(|key| key.from_frame);
+        AsepriteSlice {
+            index,
+            name,
+            keys,
+            color: AsepriteColor::default(),
+            user_data: String::new(),
+            properties: Vec::new(),
+        }
+    }
+
+    /// The key in effect at the given frame
+    ///
+    /// That is the last key starting at or before the frame, `None` if the slice only
+    /// appears later in the animation.
+    pub fn key_for_frame(&self, frame_index: usize) -> Option<&AsepriteSliceKey> {
+        self.keys
+            .iter()
+            .take_while(|key| key.from_frame as usize <= frame_index)
+            .last()
+    }
+
+    pub(super) fn apply_raw_user_data(&mut self, value: RawAsepriteUserData) {
+        self.color = value.color;
+        self.user_data = value.text;
+        self.properties = value.properties;
+    }
+}
@@ -0,0 +1,371 @@
+use std::{collections::HashMap, time::Duration};
+
+use image::{imageops, RgbaImage};
+
+use crate::error::{AseResult, AsepriteInvalidError};
+
+use super::{
+    content_bounds, Aseprite, AsepriteRect, AsepriteTag, SpritesheetOptions, SpritesheetOrder,
+    SpritesheetPacking,
+};
+
+/// Where a frame was placed by [`Aseprite::to_spritesheet`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpritesheetFrame {
+    /// The frame in the Aseprite file
+    pub frame_index: usize,
+    /// The pixels of the frame in the sheet, without the extruded edges
+    ///
+    /// Identical frames of a packed sheet, or of a grid with
+    /// [`SpritesheetOptions::merge_duplicates`], share the same rect. A fully transparent
+    /// frame of a packed sheet has an empty rect.
+    pub rect: AsepriteRect,
+    /// Position of the top-left corner of `rect` on the canvas
+    ///
+    /// This is always `(0, 0)` in a grid, a packed sheet only keeps the content of the frame.
+    pub offset: (u32, u32),
+    /// The delay of the frame
+    pub duration: Duration,
+}
+
+/// Everything needed to rebuild the animation from a sheet made by [`Aseprite::to_spritesheet`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpritesheetMeta {
+    /// Size of the sheet
+    pub size: (u32, u32),
+    /// Size of the canvas the frames come from
+    pub source_size: (u32, u32),
+    /// The frames in animation order
+    pub frames: Vec<SpritesheetFrame>,
+}
+
+/// The direction frames are placed in by [`Aseprite::export_strip`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StripOrientation {
+    /// Frames from left to right
+    #[default]
+    Horizontal,
+    /// Frames from top to bottom
+    Vertical,
+}
+
+/// The frames of a tag placed edge to edge, made by [`Aseprite::export_strip`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpriteStrip {
+    /// The strip itself
+    pub image: RgbaImage,
+    /// Size of every frame in the strip, this is the canvas size
+    pub cell_size: (u32, u32),
+    /// The frame in every cell of the strip, in playing order
+    pub frames: Vec<usize>,
+}
+
+impl Aseprite {
+    /// Place the frames of a tag edge to edge on a single image
+    ///
+    /// Frames follow the direction of the tag, so a ping-pong tag repeats its inner frames,
+    /// see [`AsepriteTag::frame_sequence`]. Returns [`AsepriteInvalidError::EmptyTag`] if the
+    /// tag has no frames.
+    pub fn export_strip(
+        &self,
+        tag: &AsepriteTag,
+        orientation: StripOrientation,
+    ) -> AseResult<SpriteStrip> {
+        let frames: Vec<usize> = tag.frame_sequence_iter().map(usize::from).collect();
+        if frames.is_empty() {
+            return Err(AsepriteInvalidError::EmptyTag(tag.name.clone()).into());
+        }
+        let (width, height) = (self.dimensions.0 as u32, self.dimensions.1 as u32);
+        let count = frames.len() as u32;
+        let mut image = match orientation {
+            StripOrientation::Horizontal => RgbaImage::new(width * count, height),
+            StripOrientation::Vertical => RgbaImage::new(width, height * count),
+        };
+        for (position, frame) in self.render_frames(&frames)?.iter().enumerate() {
+            let (x, y) = match orientation {
+                StripOrientation::Horizontal => (position as u32 * width, 0),
+                StripOrientation::Vertical => (0, position as u32 * height),
+            };
+            imageops::replace(&mut image, frame, x as i64, y as i64);
+        }
+        Ok(SpriteStrip {
+            image,
+            cell_size: (width, height),
+            frames,
+        })
+    }
+
+    /// Lay out frames on a single image
+    ///
+    /// See [`SpritesheetPacking`] for the available layouts. The same file and options always
+    /// give the same layout. Returns [`AsepriteInvalidError::InvalidTagName`] if
+    /// `options.tag` does not exist.
+    pub fn to_spritesheet(
+        &self,
+        options: &SpritesheetOptions,
+    ) -> AseResult<(RgbaImage, SpritesheetMeta)> {
+        let frames: Vec<usize> = match &options.tag {
+            Some(name) => {
+                let tag = self
+                    .get_tag_by_name(name)
+                    .ok_or_else(|| AsepriteInvalidError::InvalidTagName(name.clone()))?;
+                (tag.frames.start as usize..tag.frames.end as usize).collect()
+            }
+            None => (0..self.frame_count).collect(),
+        };
+        match options.packing {
+            SpritesheetPacking::Grid => self.grid_spritesheet(frames, options),
+            SpritesheetPacking::Packed { power_of_two } => {
+                self.packed_spritesheet(frames, options, power_of_two)
+            }
+        }
+    }
+
+    fn grid_spritesheet(
+        &self,
+        frames: Vec<usize>,
+        options: &SpritesheetOptions,
+    ) -> AseResult<(RgbaImage, SpritesheetMeta)> {
+        let unique = self.unique_frames(&frames)?;
+        // 每个格子里放的图片
+        let cells: Vec<usize> = match options.merge_duplicates {
+            true => (0..unique.images.len()).collect(),
+            false => unique.groups.clone(),
+        };
+        let count = cells.len();
+        let columns = options.columns.unwrap_or(count).clamp(1, count.max(1));
+        let rows = count.div_ceil(columns);
+        // 按列排列时，行数决定了实际用到的列数
+        let columns = match options.order {
+            SpritesheetOrder::RowMajor => columns,
+            SpritesheetOrder::ColumnMajor => count.div_ceil(rows.max(1)),
+        };
+
+        let (width, height) = (self.dimensions.0 as u32, self.dimensions.1 as u32);
+        let cell = (width + options.extrude * 2, height + options.extrude * 2);
+        let length = |cells: usize, cell: u32| match cells as u32 {
+            0 => 0,
+            cells => cells * cell + (cells - 1) * options.padding,
+        };
+        let size = (length(columns, cell.0), length(rows, cell.1));
+
+        let mut sheet = RgbaImage::new(size.0, size.1);
+        let mut rects = Vec::with_capacity(count);
+        for (position, &image_index) in cells.iter().enumerate() {
+            let (column, row) = match options.order {
+                SpritesheetOrder::RowMajor => (position % columns, position / columns),
+                SpritesheetOrder::ColumnMajor => (position / rows, position % rows),
+            };
+            let x = column as u32 * (cell.0 + options.padding) + options.extrude;
+            let y = row as u32 * (cell.1 + options.padding) + options.extrude;
+            copy_extruded(
+                &unique.images[image_index],
+                &mut sheet,
+                (x, y),
+                options.extrude,
+            );
+            rects.push(AsepriteRect::new(x as i32, y as i32, width, height));
+        }
+        let meta = SpritesheetMeta {
+            size,
+            source_size: (width, height),
+            frames: frames
+                .into_iter()
+                .zip(unique.groups)
+                .enumerate()
+                .map(|(position, (frame_index, group))| SpritesheetFrame {
+                    frame_index,
+                    rect: rects[if options.merge_duplicates {
+                        group
+                    } else {
+                        position
+                    }],
+                    offset: (0, 0),
+                    duration: self.frame_infos[frame_index].duration(),
+                })
+                .collect(),
+        };
+        Ok((sheet, meta))
+    }
+
+    fn packed_spritesheet(
+        &self,
+        frames: Vec<usize>,
+        options: &SpritesheetOptions,
+        power_of_two: bool,
+    ) -> AseResult<(RgbaImage, SpritesheetMeta)> {
+        // 裁剪每一帧，相同的图片只保留一份
+        // 内容相同的帧只裁剪一次，不同的帧裁剪后也可能相同，只是位置不一样
+        let unique = self.unique_frames(&frames)?;
+        let mut images: Vec<RgbaImage> = Vec::new();
+        let mut known: HashMap<((u32, u32), Vec<u8>), usize> = HashMap::new();
+        let mut unique_trimmed = Vec::with_capacity(unique.images.len());
+        for image in &unique.images {
+            let Some(bounds) = content_bounds(image) else {
+                unique_trimmed.push(None);
+                continue;
+            };
+            let offset = (bounds.x as u32, bounds.y as u32);
+            let image = imageops::crop_imm(image, offset.0, offset.1, bounds.width, bounds.height)
+                .to_image();
+            let key = (image.dimensions(), image.into_raw());
+            let next = images.len();
+            let image_index = *known.entry(key.clone()).or_insert(next);
+            if image_index == next {
+                let ((width, height), pixels) = key;
+                images.extend(RgbaImage::from_raw(width, height, pixels));
+            }
+            unique_trimmed.push(Some((image_index, offset)));
+        }
+        let trimmed: Vec<_> = unique
+            .groups
+            .iter()
+            .map(|&group| unique_trimmed[group])
+            .collect();
+
+        // 每张图片占用的空间包括重复的边缘和右下方的 padding
+        let margin = options.extrude * 2 + options.padding;
+        let sizes: Vec<(u32, u32)> = images
+            .iter()
+            .map(|image| (image.width() + margin, image.height() + margin))
+            .collect();
+        let positions = pack(&sizes);
+        // 最右下方的 padding 不需要
+        let mut size = (0, 0);
+        for (&(w, h), &(x, y)) in sizes.iter().zip(&positions) {
+            size.0 = u32::max(size.0, x + w - options.padding);
+            size.1 = u32::max(size.1, y + h - options.padding);
+        }
+        if power_of_two && !images.is_empty() {
+            size = (size.0.next_power_of_two(), size.1.next_power_of_two());
+        }
+
+        let mut sheet = RgbaImage::new(size.0, size.1);
+        let mut rects = Vec::with_capacity(images.len());
+        for (image, &(x, y)) in images.iter().zip(&positions) {
+            let at = (x + options.extrude, y + options.extrude);
+            copy_extruded(image, &mut sheet, at, options.extrude);
+            rects.push(AsepriteRect::new(
+                at.0 as i32,
+                at.1 as i32,
+                image.width(),
+                image.height(),
+            ));
+        }
+
+        let (width, height) = (self.dimensions.0 as u32, self.dimensions.1 as u32);
+        let meta = SpritesheetMeta {
+            size,
+            source_size: (width, height),
+            frames: frames
+                .into_iter()
+                .zip(trimmed)
+                .map(|(frame_index, trimmed)| {
+                    let (rect, offset) = match trimmed {
+                        Some((image_index, offset)) => (rects[image_index], offset),
+                        None => (AsepriteRect::default(), (0, 0)),
+                    };
+                    SpritesheetFrame {
+                        frame_index,
+                        rect,
+                        offset,
+                        duration: self.frame_infos[frame_index].duration(),
+                    }
+                })
+                .collect(),
+        };
+        Ok((sheet, meta))
+    }
+}
+
+/// 用 skyline 算法摆放矩形，返回每个矩形左上角的位置
+///
+/// 先放高的矩形，高度相同时按宽度和原来的顺序，保证结果稳定
+fn pack(sizes: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    let area: u64 = sizes.iter().map(|&(w, h)| w as u64 * h as u64).sum();
+    let widest = sizes.iter().map(|&(w, _)| w).max().unwrap_or(0);
+    let width = widest.max((area as f64).sqrt().ceil() as u32);
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&index| {
+        let (w, h) = sizes[index];
+        (std::cmp::Reverse(h), std::cmp::Reverse(w), index)
+    });
+
+    // skyline 的每一段：(x, y, 宽度)，按 x 排序并覆盖 0..width
+    let mut skyline = vec![(0, 0, width)];
+    let mut positions = vec![(0, 0); sizes.len()];
+    for index in order {
+        let (w, h) = sizes[index];
+        // 找到能放下矩形的最低位置，高度相同时取最左边
+        let mut best: Option<(u32, u32)> = None;
+        for (start, &(x, _, _)) in skyline.iter().enumerate() {
+            if x + w > width {
+                break;
+            }
+            let mut y = 0;
+            let mut covered = 0;
+            for &(_, segment_y, segment_width) in &skyline[start..] {
+                if covered >= w {
+                    break;
+                }
+                y = y.max(segment_y);
+                covered += segment_width;
+            }
+            if best.is_none_or(|best| (y, x) < best) {
+                best = Some((y, x));
+            }
+        }
+        let Some((y, x)) = best else {
+            continue;
+        };
+        positions[index] = (x, y);
+
+        // 更新 skyline：被矩形盖住的部分换成矩形的上边
+        let right = x + w;
+        let mut next = Vec::with_capacity(skyline.len() + 2);
+        for &(segment_x, segment_y, segment_width) in &skyline {
+            let segment_right = segment_x + segment_width;
+            if segment_right <= x || segment_x >= right {
+                next.push((segment_x, segment_y, segment_width));
+                continue;
+            }
+            if segment_x < x {
+                next.push((segment_x, segment_y, x - segment_x));
+            }
+            if segment_right > right {
+                next.push((right, segment_y, segment_right - right));
+            }
+        }
+        next.push((x, y + h, w));
+        next.sort_by_key(|&(segment_x, _, _)| segment_x);
+        skyline.clear();
+        for segment in next {
+            match skyline.last_mut() {
+                Some(last) if last.1 == segment.1 => last.2 += segment.2,
+                _ => skyline.push(segment),
+            }
+        }
+    }
+    positions
+}
+
+/// 把 image 复制到 sheet 的 position 处，并把边缘的像素向外重复 extrude 个像素
+fn copy_extruded(image: &RgbaImage, sheet: &mut RgbaImage, position: (u32, u32), extrude: u32) {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return;
+    }
+    let extrude = extrude as i64;
+    for y in -extrude..height as i64 + extrude {
+        for x in -extrude..width as i64 + extrude {
+            let source_x = x.clamp(0, width as i64 - 1) as u32;
+            let source_y = y.clamp(0, height as i64 - 1) as u32;
+            sheet.put_pixel(
+                (position.0 as i64 + x) as u32,
+                (position.1 as i64 + y) as u32,
+                *image.get_pixel(source_x, source_y),
+            );
+        }
+    }
+}
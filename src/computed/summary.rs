@@ -0,0 +1,78 @@
+use std::ops::Range;
+
+use crate::{
+    error::AseResult,
+    raw::{AsepriteAnimationDirection, AsepriteColorDepth, RawAsepriteChunk},
+};
+
+use super::{Aseprite, AsepriteTag};
+
+/// Metadata of an aseprite file, read without decoding any pixels
+///
+/// See [`Aseprite::probe`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsepriteSummary {
+    /// Canvas width
+    pub width: u16,
+    /// Canvas height
+    pub height: u16,
+    /// The color depth used
+    pub color_depth: AsepriteColorDepth,
+    /// Amount of frames
+    pub frame_count: usize,
+    /// Layer names, ordered by layer index
+    pub layer_names: Vec<String>,
+    /// The tags, ordered by tag index
+    pub tags: Vec<AsepriteTagSummary>,
+    /// Slice names
+    pub slice_names: Vec<String>,
+}
+
+/// The name and frames of a tag, see [`AsepriteSummary`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsepriteTagSummary {
+    /// The tag name
+    pub name: String,
+    /// The frames which this tag represents, the end is exclusive
+    pub frames: Range<u16>,
+    /// The direction of its animation
+    pub animation_direction: AsepriteAnimationDirection,
+}
+
+impl Aseprite {
+    /// Read the metadata of an aseprite file without parsing its pixels
+    ///
+    /// Cel chunks are skipped using their declared size, which makes this much faster than
+    /// [`Aseprite::from_bytes`] when indexing many files.
+    pub fn probe<S: AsRef<[u8]>>(buffer: S) -> AseResult<AsepriteSummary> {
+        let raw = crate::raw::skim_aseprite(buffer.as_ref())?;
+        let frame_count = raw.frames.len();
+
+        let mut summary = AsepriteSummary {
+            width: raw.header.width,
+            height: raw.header.height,
+            color_depth: raw.header.color_depth,
+            frame_count,
+            layer_names: Vec::new(),
+            tags: Vec::new(),
+            slice_names: Vec::new(),
+        };
+        for chunk in raw.frames.into_iter().flat_map(|frame| frame.chunks) {
+            match chunk {
+                RawAsepriteChunk::Layer { name, .. } => summary.layer_names.push(name),
+                RawAsepriteChunk::Tags { tags } => {
+                    for raw_tag in tags {
+                        summary.tags.push(AsepriteTagSummary {
+                            frames: AsepriteTag::frame_range(&raw_tag, frame_count)?,
+                            name: raw_tag.name,
+                            animation_direction: raw_tag.anim_direction,
+                        });
+                    }
+                }
+                RawAsepriteChunk::Slice { name, .. } => summary.slice_names.push(name),
+                _ => {}
+            }
+        }
+        Ok(summary)
+    }
+}
@@ -1,27 +1,178 @@
-use std::ops::Range;
+use std::{ops::Range, time::Duration};
 
-use crate::raw::{AsepriteAnimationDirection, AsepriteColor, RawAsepriteUserData};
+use tracing::warn;
 
-#[derive(Debug, Clone)]
+use crate::{
+    error::AsepriteInvalidError,
+    raw::{
+        AsepriteAnimationDirection, AsepriteColor, AsepritePropertiesMap, RawAsepriteTag,
+        RawAsepriteUserData,
+    },
+    Aseprite,
+};
+
+#[derive(Debug, Clone, PartialEq)]
 /// A single Aseprite tag
 pub struct AsepriteTag {
     /// The tag index
     pub index: usize,
-    /// The frames which this tag represents
+    /// The frames which this tag represents, the end is exclusive
     pub frames: Range<u16>,
     /// The direction of its animation
     pub animation_direction: AsepriteAnimationDirection,
+    /// How many times the tag is played, 0 means no limit
+    pub repeat: u16,
     /// The tag name
     pub name: String,
     /// Tag color
     pub color: AsepriteColor,
     /// Tag user data
     pub user_data: String,
+    /// Tag user data properties
+    pub properties: Vec<AsepritePropertiesMap>,
+}
+
+/// How [`Aseprite::primary_tag_for_frame`](crate::Aseprite::primary_tag_for_frame) picks a tag
+/// when several tags cover the same frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagPriority {
+    /// The tag with the lowest index, i.e. the first one in the timeline
+    FirstByIndex,
+    /// The tag covering the fewest frames, ties go to the lowest index
+    Smallest,
+    /// The tag covering the most frames, ties go to the lowest index
+    Largest,
+}
+
+/// The frames and timings of a tag, ready to be fed to an animation system
+///
+/// See [`Aseprite::animation_clips`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationClip {
+    /// The tag name
+    pub name: String,
+    /// The frames of one loop in playing order, see [`AsepriteTag::frame_sequence`]
+    pub frame_indices: Vec<usize>,
+    /// The duration of each frame of `frame_indices`
+    pub frame_durations: Vec<Duration>,
+    /// How many times the clip is played, 0 means no limit
+    pub repeat: u16,
+    /// The direction of the tag, already applied to `frame_indices`
+    pub direction: AsepriteAnimationDirection,
+    /// Tag color
+    pub color: AsepriteColor,
+    /// Tag user data
+    pub user_data: String,
 }
 
 impl AsepriteTag {
+    /// 文件中的 to 包含在内，检查后转换成不包含结尾的范围
+    pub(super) fn frame_range(
+        raw_tag: &RawAsepriteTag,
+        frame_count: usize,
+    ) -> Result<Range<u16>, AsepriteInvalidError> {
+        let invalid = || AsepriteInvalidError::InvalidTagRange {
+            name: raw_tag.name.clone(),
+            from: raw_tag.from,
+            to: raw_tag.to,
+        };
+        if raw_tag.from > raw_tag.to || raw_tag.to as usize >= frame_count {
+            return Err(invalid());
+        }
+        let end = raw_tag.to.checked_add(1).ok_or_else(invalid)?;
+        Ok(raw_tag.from..end)
+    }
+
+    /// Number of frames in this tag
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether the given frame is part of this tag
+    pub fn contains_frame(&self, frame_index: usize) -> bool {
+        (self.frames.start as usize..self.frames.end as usize).contains(&frame_index)
+    }
+
+    /// The frames played by one loop of this tag, in playing order
+    ///
+    /// A ping-pong loop does not repeat the first and last frame, e.g. `0..4` plays
+    /// `0, 1, 2, 3, 2, 1` before starting again.
+    pub fn frame_sequence(&self) -> Vec<u16> {
+        self.frame_sequence_iter().collect()
+    }
+
+    /// Same as [`AsepriteTag::frame_sequence`] without allocating
+    pub fn frame_sequence_iter(&self) -> impl Iterator<Item = u16> {
+        let forward = self.frames.clone();
+        // ping-pong 回程时去掉两端的帧
+        let inner = self.frames.start.saturating_add(1)..self.frames.end.saturating_sub(1);
+        let sequence: Box<dyn Iterator<Item = u16>> = match self.animation_direction {
+            AsepriteAnimationDirection::Forward => Box::new(forward),
+            AsepriteAnimationDirection::Reverse => Box::new(forward.rev()),
+            AsepriteAnimationDirection::PingPong => Box::new(forward.chain(inner.rev())),
+            AsepriteAnimationDirection::PingPongReverse => Box::new(forward.rev().chain(inner)),
+        };
+        sequence
+    }
+
+    /// Duration of one loop of this tag
+    ///
+    /// A ping-pong loop plays the inner frames twice but the first and last frame only once.
+    pub fn duration(&self, aseprite: &Aseprite) -> Duration {
+        let durations: Vec<Duration> = aseprite
+            .frame_infos()
+            .iter()
+            .skip(self.frames.start as usize)
+            .take(self.frames.len())
+            .map(|info| info.duration())
+            .collect();
+        let total: Duration = durations.iter().sum();
+        match (self.animation_direction, durations.as_slice()) {
+            (
+                AsepriteAnimationDirection::PingPong | AsepriteAnimationDirection::PingPongReverse,
+                [first, .., last],
+            ) => total * 2 - *first - *last,
+            _ => total,
+        }
+    }
+
     pub(super) fn apply_raw_user_data(&mut self, value: RawAsepriteUserData) {
         self.color = value.color;
         self.user_data = value.text;
+        self.properties = value.properties;
+    }
+}
+
+impl Aseprite {
+    /// One [`AnimationClip`] per tag, in tag order
+    ///
+    /// Tags with no frames or with frames past the end of the file are skipped with a warning.
+    pub fn animation_clips(&self) -> Vec<AnimationClip> {
+        self.tags()
+            .filter_map(|tag| {
+                if tag.frames.is_empty() || tag.frames.end as usize > self.frame_count {
+                    warn!(
+                        "Skipped tag {:?} with invalid frames {:?}.",
+                        tag.name, tag.frames
+                    );
+                    return None;
+                }
+                let frame_indices: Vec<usize> =
+                    tag.frame_sequence_iter().map(usize::from).collect();
+                let frame_durations = frame_indices
+                    .iter()
+                    .map(|&frame_index| self.frame_infos[frame_index].duration())
+                    .collect();
+                Some(AnimationClip {
+                    name: tag.name.clone(),
+                    frame_indices,
+                    frame_durations,
+                    repeat: tag.repeat,
+                    direction: tag.animation_direction,
+                    color: tag.color,
+                    user_data: tag.user_data.clone(),
+                })
+            })
+            .collect()
     }
 }
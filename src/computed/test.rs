@@ -1,7 +1,25 @@
-use crate::raw::{AsepriteBlendMode, AsepriteColor};
-use crate::{AsepriteLayer, GroupLayer, LayerTreeNode, NormalLayer};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
-use super::Aseprite;
+use image::RgbaImage;
+
+use crate::error::{AseResult, AsepriteError, AsepriteInvalidError, AsepriteLimit};
+use crate::raw::{
+    AsepriteAnimationDirection, AsepriteBlendMode, AsepriteColor, AsepriteColorDepth,
+    AsepriteLayerType, AsepriteNinePatchInfo, AsepritePivot, AsepritePixel, PackedPixels,
+    RawAseprite, RawAsepriteCel, RawAsepriteChunk, RawAsepriteFrame, RawAsepriteHeader,
+    RawAsepriteIccProfile, RawAsepritePaletteEntry, RawAsepriteSlice, RawAsepriteTag,
+    RawAsepriteUserData,
+};
+use crate::{
+    AsepriteColorProfile, AsepriteColorProfileType, AsepriteLayer, AsepritePalette, AsepriteRect,
+    AsepriteSlice, AsepriteSliceImage, AsepriteSliceKey, AsepriteTag, CelEntry, CelRef,
+    FrameFilter, FrameRenderOptions, GroupExport, GroupLayer, JsonExportOptions, JsonFrameFormat,
+    LayerExportOptions, LayerFilter, LayerTreeNode, LoadPhase, NinePatchMode, NormalLayer,
+    OnionSkinOptions, ParseOptions, SpritesheetOptions, SpritesheetOrder, SpritesheetPacking,
+    StripOrientation, TagPriority,
+};
+
+use super::{blend_onto_transparent, blend_rgba, blend_row, Aseprite, LayerCels};
 
 #[test]
 fn check_aseprite_reader_result() {
@@ -12,16 +30,28 @@ fn check_aseprite_reader_result() {
     let col2row1_layer_group_ids = aseprite.find_layer_belong_groups(col2row1_layer.index());
     let col2row1_layer_group: Vec<&str> = col2row1_layer_group_ids
         .into_iter()
-        .map(|index| aseprite.get_layer_by_index(&index).unwrap().name())
+        .map(|index| aseprite.get_layer_by_index(index).unwrap().name())
         .collect();
     assert_eq!(col2row1_layer_group, vec!["Col2", "Table"]);
 
     // 验证 layer BG1 的属性是否正确
     {
         let layer = aseprite.get_layer_by_name("BG1").unwrap();
-        let frame_2_cel = aseprite.get_cel(&layer.index(), &1).unwrap();
+        let frame_2_cel = aseprite.get_cel(layer.index(), 1).unwrap();
 
         assert_eq!(frame_2_cel.opacity, 128);
+
+        // 渲染出来的图片要带上 cel 的透明度
+        let pixels = aseprite.decoded_pixels(frame_2_cel).unwrap().to_vec();
+        let image = aseprite
+            .get_image_by_layer_frame(layer.index(), 1)
+            .unwrap()
+            .unwrap();
+        assert!(image
+            .pixels()
+            .zip(&pixels)
+            .all(|(rendered, raw)| rendered.0[3] == super::mul_un8(raw[3], 128)));
+        assert!(image.pixels().any(|pixel| pixel.0[3] == 128));
     }
 
     // 验证 layer Col1 的属性是否正确
@@ -36,7 +66,7 @@ fn check_aseprite_reader_result() {
         let layer = aseprite.get_layer_by_name("BG1").unwrap();
         let layer_index = layer.index();
         let layer_image = aseprite
-            .get_image_by_layer_frame(&layer_index, &0)
+            .get_image_by_layer_frame(layer_index, 0)
             .unwrap()
             .unwrap();
 
@@ -52,7 +82,7 @@ fn check_aseprite_reader_result() {
         let layer = aseprite.get_layer_by_name("Col1Row1").unwrap();
         let layer_index = layer.index();
         let layer_image = aseprite
-            .get_image_by_layer_frame(&layer_index, &0)
+            .get_image_by_layer_frame(layer_index, 0)
             .unwrap()
             .unwrap();
 
@@ -66,7 +96,7 @@ fn check_aseprite_reader_result() {
     {
         let layer = aseprite.get_layer_by_name("Col3Row1").unwrap();
         let layer_index = layer.index();
-        let layer_cel = aseprite.get_cel(&layer_index, &1).unwrap();
+        let layer_cel = aseprite.get_cel(layer_index, 1).unwrap();
 
         assert_eq!(layer_cel.z_index, -20);
     }
@@ -76,16 +106,16 @@ fn check_aseprite_reader_result() {
         let layer_index = layer.index();
         match layer.name() {
             "BG1" => {
-                let cel = aseprite.get_cel(&layer_index, &0).unwrap();
+                let cel = aseprite.get_cel(layer_index, 0).unwrap();
                 assert_eq!(cel.user_data, "CelBG1Frame1UserData");
-                let cel = aseprite.get_cel(&layer_index, &1).unwrap();
+                let cel = aseprite.get_cel(layer_index, 1).unwrap();
                 assert_eq!(cel.user_data, "CelBG1Frame2UserData");
 
                 assert_eq!(layer.blend_mode(), AsepriteBlendMode::Normal);
                 assert_eq!(layer.opacity(), Some(255));
             }
             "Col1BG" => {
-                let cel = aseprite.get_cel(&layer_index, &0).unwrap();
+                let cel = aseprite.get_cel(layer_index, 0).unwrap();
                 assert_eq!(cel.user_data, "CelCol1BGFrame1UserData");
             }
             "Day" => {
@@ -103,14 +133,17 @@ fn check_aseprite_reader_result() {
     for tag in aseprite.tags() {
         match tag.name.as_str() {
             "FrameAllTag" => {
-                assert_eq!(tag.frames, 0..1);
+                assert_eq!(tag.frames, 0..2);
+                assert_eq!(tag.frame_count(), 2);
                 assert_eq!(tag.user_data, "FrameAllTagUserData");
             }
             "Frame1Tag" => {
-                assert_eq!(tag.frames, 0..0);
+                assert_eq!(tag.frames, 0..1);
+                assert_eq!(tag.frame_count(), 1);
             }
             "Frame2Tag" => {
-                assert_eq!(tag.frames, 1..1);
+                assert_eq!(tag.frames, 1..2);
+                assert_eq!(tag.repeat, 0);
                 assert_eq!(tag.user_data, "Frame2TagUserData");
             }
             _ => {}
@@ -119,18 +152,22 @@ fn check_aseprite_reader_result() {
 }
 
 impl AsepriteLayer {
-    ///
+    /// Build a group layer for tests
     pub fn mock_group(index: usize, name: &str, child_level: u16) -> Self {
         AsepriteLayer::Group(GroupLayer {
             name: name.to_string(),
             index,
+            opacity: None,
             visible: true,
+            flags: 3,
+            uuid: None,
             child_level,
             color: AsepriteColor::default(),
             user_data: String::new(),
+            properties: Vec::new(),
         })
     }
-    ///
+    /// Build a normal layer for tests
     pub fn mock_normal(index: usize, name: &str, child_level: u16) -> Self {
         AsepriteLayer::Normal(NormalLayer {
             name: name.to_string(),
@@ -138,9 +175,12 @@ impl AsepriteLayer {
             blend_mode: AsepriteBlendMode::Normal,
             opacity: Some(255),
             visible: true,
+            flags: 3,
+            uuid: None,
             child_level,
             color: AsepriteColor::default(),
             user_data: String::new(),
+            properties: Vec::new(),
         })
     }
 }
@@ -200,11 +240,11 @@ fn check_build_layer_tree() {
                                     assert!(layer.is_group());
                                     assert_eq!(layer.name(), "Col3");
                                 }
-                                _ => assert!(false),
+                                _ => unreachable!(),
                             }
                         }
                     }
-                    _ => assert!(false),
+                    _ => unreachable!(),
                 }
             }
             4 => {
@@ -215,7 +255,4314 @@ fn check_build_layer_tree() {
                 assert!(layer.is_normal());
                 assert_eq!(layer.name(), "Watermark");
             }
-            _ => assert!(false),
+            _ => unreachable!(),
+        }
+    }
+}
+
+// Rendering different frames of one `Aseprite` from several threads must be data race free.
+// Besides `cargo test --release`, this can be checked locally with ThreadSanitizer:
+//
+// RUSTFLAGS="-Zsanitizer=thread" cargo +nightly test -Zbuild-std \
+//     --target x86_64-unknown-linux-gnu check_parallel_frame_rendering
+#[test]
+fn check_parallel_frame_rendering() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Aseprite>();
+
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let frame_count = aseprite.frame_count();
+    let serial: Vec<_> = (0..frame_count)
+        .map(|frame_index| aseprite.get_frame_image(frame_index).unwrap())
+        .collect();
+
+    // 重新加载，保证每个线程都从空缓存开始
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..8)
+            .map(|thread_index| {
+                let aseprite = &aseprite;
+                scope.spawn(move || {
+                    // 每个线程从不同的帧开始渲染，增加并发解码同一个 cel 的机会
+                    (0..frame_count)
+                        .map(|offset| {
+                            let frame_index = (thread_index + offset) % frame_count;
+                            (frame_index, aseprite.get_frame_image(frame_index).unwrap())
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for handle in handles {
+            for (frame_index, image) in handle.join().unwrap() {
+                assert_eq!(image, serial[frame_index]);
+            }
+        }
+    });
+
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    aseprite.prewarm(0..frame_count).unwrap();
+    for (frame_index, expected) in serial.iter().enumerate() {
+        assert_eq!(&aseprite.get_frame_image(frame_index).unwrap(), expected);
+    }
+    assert!(aseprite.prewarm([frame_count]).is_err());
+}
+
+#[test]
+fn check_parallel_exports() {
+    let buffer = std::fs::read("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+    let load = |threads: usize| {
+        let mut raw = crate::raw::read_aseprite(&buffer).unwrap();
+        raw.frames[0].chunks.push(RawAsepriteChunk::Tags {
+            tags: vec![RawAsepriteTag {
+                from: 0,
+                to: 3,
+                anim_direction: AsepriteAnimationDirection::PingPong,
+                repeat: 0,
+                name: "Loop".to_string(),
+            }],
+        });
+        let mut aseprite = Aseprite::from_raw(raw).unwrap();
+        aseprite.set_render_threads(threads);
+        aseprite
+    };
+    let export = |aseprite: &Aseprite| {
+        let tag = aseprite.tags().next().unwrap();
+        let packed = SpritesheetOptions {
+            packing: SpritesheetPacking::Packed {
+                power_of_two: false,
+            },
+            ..SpritesheetOptions::default()
+        };
+        (
+            aseprite
+                .to_spritesheet(&SpritesheetOptions::default())
+                .unwrap(),
+            aseprite.to_spritesheet(&packed).unwrap(),
+            aseprite
+                .export_strip(tag, StripOrientation::Horizontal)
+                .unwrap(),
+        )
+    };
+
+    // 帧比线程多，也有比线程少的情况
+    let serial = load(1);
+    let expected = export(&serial);
+    for threads in [2, 3, 8] {
+        let parallel = load(threads);
+        assert!(export(&parallel) == expected);
+
+        #[cfg(feature = "gif")]
+        {
+            let gif = |aseprite: &Aseprite| {
+                let mut gif = Vec::new();
+                aseprite
+                    .export_gif(aseprite.tags().next().unwrap(), &mut gif)
+                    .unwrap();
+                gif
+            };
+            assert_eq!(gif(&parallel), gif(&serial));
+        }
+        #[cfg(feature = "apng")]
+        {
+            let apng = |aseprite: &Aseprite| {
+                let mut apng = Vec::new();
+                let tag = aseprite.tags().next().unwrap();
+                aseprite
+                    .export_apng(tag.frame_sequence(), &mut apng)
+                    .unwrap();
+                apng
+            };
+            assert_eq!(apng(&parallel), apng(&serial));
+        }
+    }
+}
+
+/// Build a [`RawAseprite`] by hand, with the chunks of each frame
+pub(super) fn mock_raw(
+    color_depth: AsepriteColorDepth,
+    width: u16,
+    height: u16,
+    frames: Vec<Vec<RawAsepriteChunk>>,
+) -> RawAseprite {
+    RawAseprite {
+        header: RawAsepriteHeader {
+            file_size: 0,
+            magic_number: 0xA5E0,
+            frames: frames.len() as u16,
+            width,
+            height,
+            color_depth,
+            flags: 3,
+            speed: 100,
+            transparent_palette: 0,
+            color_count: 0,
+            pixel_width: 1,
+            pixel_height: 1,
+            grid_x: 0,
+            grid_y: 0,
+            grid_width: 16,
+            grid_height: 16,
+        },
+        frames: frames
+            .into_iter()
+            .map(|chunks| RawAsepriteFrame {
+                magic_number: 0xF1FA,
+                duration_ms: 100,
+                chunks,
+            })
+            .collect(),
+    }
+}
+
+/// Build `count` pixels of a single RGBA color for [`mock_cel_chunk`]
+pub(super) fn mock_rgba_pixels(color: [u8; 4], count: usize) -> Vec<AsepritePixel> {
+    let [red, green, blue, alpha] = color;
+    vec![
+        AsepritePixel::RGBA(AsepriteColor {
+            red,
+            green,
+            blue,
+            alpha,
+        });
+        count
+    ]
+}
+
+/// Build a group layer chunk for [`mock_raw`]
+pub(super) fn mock_group_chunk(name: &str, child_level: u16) -> RawAsepriteChunk {
+    RawAsepriteChunk::Layer {
+        flags: 3,
+        layer_type: AsepriteLayerType::Group,
+        layer_child: child_level,
+        width: 0,
+        height: 0,
+        blend_mode: AsepriteBlendMode::Normal,
+        opacity: 255,
+        name: name.to_string(),
+        uuid: None,
+    }
+}
+
+/// Build a normal layer chunk for [`mock_raw`]
+pub(super) fn mock_layer_chunk(name: &str, child_level: u16) -> RawAsepriteChunk {
+    RawAsepriteChunk::Layer {
+        flags: 3,
+        layer_type: AsepriteLayerType::Normal,
+        layer_child: child_level,
+        width: 0,
+        height: 0,
+        blend_mode: AsepriteBlendMode::Normal,
+        opacity: 255,
+        name: name.to_string(),
+        uuid: None,
+    }
+}
+
+/// Build a raw cel chunk for [`mock_raw`]
+pub(super) fn mock_cel_chunk(
+    layer_index: u16,
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+    pixels: Vec<AsepritePixel>,
+) -> RawAsepriteChunk {
+    RawAsepriteChunk::Cel {
+        layer_index,
+        x,
+        y,
+        opacity: 255,
+        z_index: 0,
+        cel: RawAsepriteCel::Raw {
+            width,
+            height,
+            pixels: mock_packed_pixels(&pixels),
+        },
+    }
+}
+
+/// 按第一个像素的类型打包，没有像素时当作 RGBA
+fn mock_packed_pixels(pixels: &[AsepritePixel]) -> PackedPixels {
+    let color_depth = match pixels.first() {
+        Some(AsepritePixel::Indexed(_)) => AsepriteColorDepth::Indexed,
+        Some(AsepritePixel::Grayscale { .. }) => AsepriteColorDepth::Grayscale,
+        _ => AsepriteColorDepth::RGBA,
+    };
+    PackedPixels::from_pixels(color_depth, pixels).unwrap()
+}
+
+#[test]
+fn check_indexed_image() {
+    let palette = RawAsepriteChunk::Palette {
+        palette_size: 3,
+        from_color: 0,
+        to_color: 2,
+        entries: [[0, 0, 0, 0], [255, 0, 0, 255], [0, 0, 255, 255]]
+            .iter()
+            .map(|&[red, green, blue, alpha]| RawAsepritePaletteEntry {
+                color: AsepriteColor {
+                    red,
+                    green,
+                    blue,
+                    alpha,
+                },
+                name: None,
+            })
+            .collect(),
+    };
+    let pixels = [0, 1, 2, 1, 0, 2]
+        .iter()
+        .map(|&index| AsepritePixel::Indexed(index))
+        .collect();
+    let raw = mock_raw(
+        AsepriteColorDepth::Indexed,
+        4,
+        4,
+        vec![vec![
+            palette,
+            mock_layer_chunk("Layer", 0),
+            mock_cel_chunk(0, 1, 1, 3, 2, pixels),
+        ]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+
+    let indexed = aseprite
+        .get_indexed_image_by_layer_frame(0, 0)
+        .unwrap()
+        .unwrap();
+    assert_eq!(indexed.dimensions(), (3, 2));
+    assert_eq!(indexed.into_raw(), vec![0, 1, 2, 1, 0, 2]);
+    assert!(aseprite
+        .get_indexed_image_by_layer_frame(1, 0)
+        .unwrap()
+        .is_none());
+
+    let rgba = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    assert!(matches!(
+        rgba.get_indexed_image_by_layer_frame(0, 0),
+        Err(AsepriteError::NotIndexed)
+    ));
+    assert!(rgba.indexed_colors.is_none());
+}
+
+#[test]
+fn check_used_palette_indices() {
+    let indexed = |indices: &[u8]| {
+        indices
+            .iter()
+            .map(|&index| AsepritePixel::Indexed(index))
+            .collect()
+    };
+    let raw = mock_raw(
+        AsepriteColorDepth::Indexed,
+        4,
+        1,
+        vec![
+            vec![
+                mock_layer_chunk("Layer", 0),
+                mock_cel_chunk(0, 0, 0, 4, 1, indexed(&[0, 3, 3, 9])),
+            ],
+            vec![RawAsepriteChunk::Cel {
+                layer_index: 0,
+                x: 0,
+                y: 0,
+                opacity: 255,
+                z_index: 0,
+                cel: RawAsepriteCel::Linked { frame_position: 0 },
+            }],
+            vec![mock_cel_chunk(0, 0, 0, 1, 1, indexed(&[12]))],
+        ],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    // mock 的 header 中透明 index 是 0
+    assert_eq!(
+        aseprite
+            .used_palette_indices(false)
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec![3, 9, 12]
+    );
+    assert_eq!(
+        aseprite
+            .used_palette_indices(true)
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>(),
+        vec![0, 3, 9, 12]
+    );
+
+    let rgba = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    assert!(matches!(
+        rgba.used_palette_indices(true),
+        Err(AsepriteError::NotIndexed)
+    ));
+}
+
+#[test]
+fn check_indexed_lookup_table() {
+    let palette = RawAsepriteChunk::Palette {
+        palette_size: 2,
+        from_color: 0,
+        to_color: 1,
+        entries: [[10, 20, 30, 255], [40, 50, 60, 128]]
+            .iter()
+            .map(|&[red, green, blue, alpha]| RawAsepritePaletteEntry {
+                color: AsepriteColor {
+                    red,
+                    green,
+                    blue,
+                    alpha,
+                },
+                name: None,
+            })
+            .collect(),
+    };
+    let cel = |layer: u16, indices: &[u8]| {
+        let pixels = indices
+            .iter()
+            .map(|&index| AsepritePixel::Indexed(index))
+            .collect();
+        mock_cel_chunk(layer, 0, 0, indices.len() as u16, 1, pixels)
+    };
+    let raw = mock_raw(
+        AsepriteColorDepth::Indexed,
+        4,
+        1,
+        vec![vec![
+            palette,
+            mock_layer_chunk("Valid", 0),
+            mock_layer_chunk("Invalid", 0),
+            cel(0, &[0, 1, 1, 0]),
+            cel(1, &[7]),
+        ]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+
+    // 查表的结果和逐个像素查找 palette 一致
+    let colors = aseprite.indexed_colors.as_ref().unwrap();
+    for index in 0..=255u8 {
+        let expected = AsepritePixel::Indexed(index)
+            .get_rgba(aseprite.palette(), aseprite.transparent_index())
+            .ok();
+        assert_eq!(colors[index as usize], expected);
+    }
+
+    let image = aseprite.get_image_by_layer_frame(0, 0).unwrap().unwrap();
+    // mock 的 header 中透明 index 是 0
+    assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 0]);
+    assert_eq!(image.get_pixel(1, 0).0, [40, 50, 60, 128]);
+    assert!(matches!(
+        aseprite.get_image_by_layer_frame(1, 0),
+        Err(AsepriteError::InvalidConfiguration(
+            AsepriteInvalidError::InvalidPaletteIndex(7)
+        ))
+    ));
+}
+
+#[test]
+fn check_web_render_plan() {
+    assert_eq!(
+        AsepriteBlendMode::Normal.to_canvas_composite_op(),
+        Some("source-over")
+    );
+    assert_eq!(
+        AsepriteBlendMode::Normal.to_css_mix_blend_mode(),
+        Some("normal")
+    );
+    assert_eq!(
+        AsepriteBlendMode::ColorDodge.to_canvas_composite_op(),
+        Some("color-dodge")
+    );
+    assert_eq!(
+        AsepriteBlendMode::SoftLight.to_css_mix_blend_mode(),
+        Some("soft-light")
+    );
+    for blend_mode in [
+        AsepriteBlendMode::Addition,
+        AsepriteBlendMode::Subtract,
+        AsepriteBlendMode::Divide,
+    ] {
+        assert_eq!(blend_mode.to_canvas_composite_op(), None);
+        assert_eq!(blend_mode.to_css_mix_blend_mode(), None);
+    }
+
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let plan = aseprite.web_render_plan(1);
+    let layers: Vec<&str> = plan
+        .iter()
+        .map(|command| aseprite.get_layer_by_index(command.layer).unwrap().name())
+        .collect();
+    assert_eq!(
+        layers,
+        vec![
+            // Col3Row1 的 cel 在第二帧 z-index 为 -20，最先绘制
+            "Col3Row1",
+            "BG1",
+            "BG2",
+            "BG3",
+            "Col1BG",
+            "Col1Row1",
+            "Col1Row2",
+            "Col2BG",
+            "Col2Row1",
+            "Col2Row2",
+            "Col3BG",
+            "Col3Row2",
+            "Day",
+            "Watermark"
+        ]
+    );
+    // BG1 的 cel 在第二帧透明度为 128
+    assert_eq!(plan[1].opacity, 128);
+    assert_eq!(plan[1].image_ref, (0, 1));
+    let day = &plan[12];
+    assert_eq!(day.composite_op, Some("soft-light"));
+    assert_eq!(day.opacity, 128);
+    assert_eq!((plan[2].x, plan[2].y), (5, 0));
+}
+
+#[test]
+fn check_render_viewport() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let full = aseprite.get_frame_image(1).unwrap();
+
+    let mut target = image::RgbaImage::new(1, 1);
+    for viewport in [
+        AsepriteRect::new(10, 5, 40, 30),
+        AsepriteRect::new(0, 0, 96, 64),
+        AsepriteRect::new(60, 50, 36, 14),
+    ] {
+        aseprite.render_viewport(1, viewport, &mut target).unwrap();
+        let expected = image::imageops::crop_imm(
+            &full,
+            viewport.x as u32,
+            viewport.y as u32,
+            viewport.width,
+            viewport.height,
+        )
+        .to_image();
+        assert_eq!(target, expected);
+    }
+
+    // 视口之外的 cel 不应该被解码
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    aseprite
+        .render_viewport(0, AsepriteRect::new(0, 0, 4, 4), &mut target)
+        .unwrap();
+    assert_eq!(target.dimensions(), (4, 4));
+    let bg1 = aseprite.get_layer_by_name("BG1").unwrap().index();
+    let col2_bg = aseprite.get_layer_by_name("Col2BG").unwrap().index();
+    assert!(aseprite.get_cel(bg1, 0).unwrap().decoded.get().is_some());
+    assert!(aseprite
+        .get_cel(col2_bg, 0)
+        .unwrap()
+        .decoded
+        .get()
+        .is_none());
+
+    assert!(aseprite
+        .render_viewport(2, AsepriteRect::new(0, 0, 4, 4), &mut target)
+        .is_err());
+}
+
+#[test]
+fn check_probe() {
+    let buffer = std::fs::read("./tests/test_cases/complex.aseprite").unwrap();
+    let header = crate::raw::read_header(&buffer).unwrap();
+    assert_eq!((header.width, header.height), (96, 64));
+
+    let summary = Aseprite::probe(&buffer).unwrap();
+    let aseprite = Aseprite::from_bytes(&buffer).unwrap();
+    assert_eq!((summary.width, summary.height), aseprite.size());
+    assert_eq!(summary.color_depth, AsepriteColorDepth::RGBA);
+    assert_eq!(summary.frame_count, 2);
+    assert_eq!(
+        summary.layer_names,
+        aseprite
+            .layers()
+            .map(|layer| layer.name().to_string())
+            .collect::<Vec<_>>()
+    );
+    let tags: Vec<_> = summary
+        .tags
+        .iter()
+        .map(|tag| (tag.name.as_str(), tag.frames.clone()))
+        .collect();
+    let expected: Vec<_> = aseprite
+        .tags()
+        .map(|tag| (tag.name.as_str(), tag.frames.clone()))
+        .collect();
+    assert_eq!(tags, expected);
+    assert!(summary.slice_names.is_empty());
+}
+
+#[test]
+fn check_parse_limits() {
+    let buffer = std::fs::read("./tests/test_cases/simple.aseprite").unwrap();
+    assert!(Aseprite::from_bytes_with(&buffer, ParseOptions::default()).is_ok());
+
+    let exceeded =
+        |options: ParseOptions, buffer: &[u8]| match Aseprite::from_bytes_with(buffer, options) {
+            Err(AsepriteError::LimitExceeded { limit, value, .. }) => (limit, value),
+            _ => unreachable!(),
+        };
+    assert_eq!(
+        exceeded(
+            ParseOptions {
+                max_frames: 0,
+                ..Default::default()
+            },
+            &buffer
+        ),
+        (AsepriteLimit::Frames, 1)
+    );
+    // 123 * 456 RGBA 像素
+    assert_eq!(
+        exceeded(
+            ParseOptions {
+                max_decompressed_bytes: 224_351,
+                ..Default::default()
+            },
+            &buffer
+        ),
+        (AsepriteLimit::DecompressedBytes, 224_352)
+    );
+
+    // 伪造一个 65535x65535 的画布
+    let mut hostile = buffer;
+    hostile[8..12].copy_from_slice(&[0xFF; 4]);
+    assert_eq!(
+        exceeded(ParseOptions::default(), &hostile),
+        (AsepriteLimit::CanvasArea, 65535 * 65535)
+    );
+}
+
+#[test]
+fn check_skip_pixels() {
+    let path = "./tests/test_cases/multiple_frames_layers.aseprite";
+    let full = Aseprite::from_path(path).unwrap();
+    let options = ParseOptions {
+        skip_pixels: true,
+        // 不读取像素时不检查解压后的大小
+        max_decompressed_bytes: 0,
+        ..Default::default()
+    };
+    let aseprite = Aseprite::from_path_with(path, options).unwrap();
+
+    // 元数据和完整解析的结果相同
+    assert_eq!(aseprite.frame_count(), full.frame_count());
+    let layer_names = |aseprite: &Aseprite| -> Vec<String> {
+        aseprite
+            .layers()
+            .map(|layer| layer.name().to_string())
+            .collect()
+    };
+    assert_eq!(layer_names(&aseprite), layer_names(&full));
+    assert_eq!(aseprite.tags().count(), full.tags().count());
+    let cel = aseprite.get_cel(2, 0).unwrap();
+    let full_cel = full.get_cel(2, 0).unwrap();
+    assert!(matches!(cel.raw_cel, RawAsepriteCel::Skipped { .. }));
+    assert_eq!(cel.get_size(), full_cel.get_size());
+    assert_eq!(cel.origin(), full_cel.origin());
+
+    assert!(matches!(
+        aseprite.get_frame_image(0),
+        Err(AsepriteError::PixelsNotLoaded)
+    ));
+    assert!(matches!(
+        aseprite.get_image_by_layer_frame(2, 1),
+        Err(AsepriteError::PixelsNotLoaded)
+    ));
+}
+
+#[test]
+fn check_frame_filter() {
+    let path = "./tests/test_cases/multiple_frames_layers.aseprite";
+    let full = Aseprite::from_path(path).unwrap();
+    let load = |frames| Aseprite::from_path_with(path, ParseOptions::default().frames(frames));
+
+    // 帧的序号不变，没选中的帧不能渲染
+    let aseprite = load(FrameFilter::List(vec![1])).unwrap();
+    assert_eq!(aseprite.frame_count(), full.frame_count());
+    assert_eq!(aseprite.tags().count(), full.tags().count());
+    assert_eq!(
+        aseprite.get_frame_image(1).unwrap(),
+        full.get_frame_image(1).unwrap()
+    );
+    assert!(matches!(
+        aseprite.get_frame_image(2),
+        Err(AsepriteError::FrameNotLoaded(2))
+    ));
+    // frame 1 的 cel 链接到 frame 0，frame 0 的像素也需要读取
+    assert!(matches!(
+        aseprite.get_image_by_layer_frame(2, 0),
+        Err(AsepriteError::FrameNotLoaded(0))
+    ));
+    assert!(!matches!(
+        aseprite.get_cel(2, 0).unwrap().raw_cel,
+        RawAsepriteCel::Skipped { .. }
+    ));
+    // 没选中的帧不读取像素
+    assert!(matches!(
+        aseprite.get_cel(1, 2).unwrap().raw_cel,
+        RawAsepriteCel::Skipped { .. }
+    ));
+
+    let aseprite = load(FrameFilter::List(vec![3])).unwrap();
+    assert_eq!(
+        aseprite.get_frame_image(3).unwrap(),
+        full.get_frame_image(3).unwrap()
+    );
+
+    let aseprite = load(FrameFilter::Range(2..3)).unwrap();
+    assert_eq!(
+        aseprite.get_frame_image(2).unwrap(),
+        full.get_frame_image(2).unwrap()
+    );
+    assert!(aseprite.get_frame_image(3).is_err());
+
+    let path = "./tests/test_cases/complex.aseprite";
+    let full = Aseprite::from_path(path).unwrap();
+    let aseprite = Aseprite::from_path_with(
+        path,
+        ParseOptions::default().frames(FrameFilter::Tag("Frame2Tag".to_string())),
+    )
+    .unwrap();
+    assert_eq!(
+        aseprite.get_frame_image(1).unwrap(),
+        full.get_frame_image(1).unwrap()
+    );
+    assert!(matches!(
+        aseprite.get_frame_image(0),
+        Err(AsepriteError::FrameNotLoaded(0))
+    ));
+    assert!(matches!(
+        Aseprite::from_path_with(
+            path,
+            ParseOptions::default().frames(FrameFilter::Tag("Missing".to_string()))
+        ),
+        Err(AsepriteError::InvalidConfiguration(
+            AsepriteInvalidError::InvalidTagName(_)
+        ))
+    ));
+}
+
+#[test]
+fn check_layer_filter() {
+    let path = "./tests/test_cases/complex.aseprite";
+    let full = Aseprite::from_path(path).unwrap();
+    let load = |layers| Aseprite::from_path_with(path, ParseOptions::default().layers(layers));
+    // 和只绘制这些 layer 的结果一致
+    let only = |layers: Vec<usize>| {
+        let options = FrameRenderOptions {
+            include: Some(layers),
+            ..Default::default()
+        };
+        (0..2)
+            .map(|frame_index| full.render_frame(frame_index, &options).unwrap())
+            .collect::<Vec<_>>()
+    };
+    let frames = |aseprite: &Aseprite| {
+        (0..2)
+            .map(|frame_index| aseprite.get_frame_image(frame_index).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    let aseprite = load(LayerFilter::Prefix("Col1".to_string())).unwrap();
+    // layer 和 tree 不变
+    assert_eq!(aseprite.layers().count(), full.layers().count());
+    assert_eq!(
+        aseprite.find_layer_belong_groups(7),
+        full.find_layer_belong_groups(7)
+    );
+    assert_eq!(frames(&aseprite), only(vec![4, 6, 7]));
+    assert!(aseprite.get_cel(0, 0).is_none());
+    assert_eq!(aseprite.cel_render_order(0), vec![4, 6, 7]);
+
+    let aseprite = load(LayerFilter::Names(vec![
+        "BG1".to_string(),
+        "Watermark".to_string(),
+    ]))
+    .unwrap();
+    assert_eq!(frames(&aseprite), only(vec![0, 19]));
+
+    let aseprite = load(LayerFilter::Indices(vec![2])).unwrap();
+    assert_eq!(frames(&aseprite), only(vec![2]));
+
+    assert!(matches!(
+        load(LayerFilter::Names(vec!["Missing".to_string()])),
+        Err(AsepriteError::InvalidConfiguration(
+            AsepriteInvalidError::InvalidLayerName(_)
+        ))
+    ));
+    assert!(matches!(
+        load(LayerFilter::Indices(vec![20])),
+        Err(AsepriteError::InvalidConfiguration(
+            AsepriteInvalidError::InvalidLayer(20)
+        ))
+    ));
+}
+
+#[test]
+fn check_tag_priority() {
+    let tag = |name: &str, from: u16, to: u16| RawAsepriteTag {
+        from,
+        to,
+        anim_direction: AsepriteAnimationDirection::Forward,
+        repeat: 0,
+        name: name.to_string(),
+    };
+    let mut frames: Vec<Vec<RawAsepriteChunk>> = (0..6).map(|_| Vec::new()).collect();
+    frames[0].push(RawAsepriteChunk::Tags {
+        tags: vec![
+            tag("all", 0, 5),
+            tag("walk", 1, 3),
+            tag("jump", 3, 4),
+            tag("land", 4, 5),
+        ],
+    });
+    let aseprite = Aseprite::from_raw(mock_raw(AsepriteColorDepth::RGBA, 4, 4, frames)).unwrap();
+
+    let names = |frame_index| {
+        aseprite
+            .tags_for_frame(frame_index)
+            .into_iter()
+            .map(|tag| tag.name.as_str())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(0), ["all"]);
+    assert_eq!(names(3), ["all", "walk", "jump"]);
+    assert_eq!(names(5), ["all", "land"]);
+    assert!(names(6).is_empty());
+
+    // (frame, FirstByIndex, Smallest, Largest)
+    let expected = [
+        (0, "all", "all", "all"),
+        (2, "all", "walk", "all"),
+        (3, "all", "jump", "all"),
+        // jump 和 land 一样长，取 index 更小的
+        (4, "all", "jump", "all"),
+        (5, "all", "land", "all"),
+    ];
+    for (frame_index, first, smallest, largest) in expected {
+        let primary = |policy| {
+            aseprite
+                .primary_tag_for_frame(frame_index, policy)
+                .map(|tag| tag.name.as_str())
+        };
+        assert_eq!(primary(TagPriority::FirstByIndex), Some(first));
+        assert_eq!(primary(TagPriority::Smallest), Some(smallest));
+        assert_eq!(primary(TagPriority::Largest), Some(largest));
+    }
+    assert!(aseprite
+        .primary_tag_for_frame(6, TagPriority::FirstByIndex)
+        .is_none());
+}
+
+#[test]
+fn check_load_progress() {
+    let buffer = std::fs::read("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+    let mut calls = Vec::new();
+    let aseprite = Aseprite::from_bytes_with_progress(&buffer, |phase, fraction| {
+        calls.push((phase, fraction))
+    })
+    .unwrap();
+    assert_eq!(aseprite.frame_infos().len(), 4);
+
+    assert_eq!(calls.first(), Some(&(LoadPhase::Header, 0.0)));
+    for phase in [LoadPhase::Header, LoadPhase::Frames, LoadPhase::Cels] {
+        let fractions: Vec<f32> = calls
+            .iter()
+            .filter(|(call_phase, _)| *call_phase == phase)
+            .map(|(_, fraction)| *fraction)
+            .collect();
+        assert!(fractions.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(fractions.last(), Some(&1.0));
+    }
+    // 每一帧都要通知一次
+    let frame_calls = calls
+        .iter()
+        .filter(|(phase, _)| *phase == LoadPhase::Frames)
+        .count();
+    assert!(frame_calls >= 4);
+}
+
+#[test]
+fn check_canvas_size() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    assert_eq!(aseprite.size(), (96, 64));
+    assert_eq!((aseprite.width(), aseprite.height()), aseprite.size());
+    assert_eq!(aseprite.get_frame(1).unwrap().size(), aseprite.size());
+
+    // 整帧的图片是画布大小，单个 cel 的图片是裁剪过的
+    let frame = aseprite.get_frame_image(1).unwrap();
+    assert_eq!(frame.dimensions(), (96, 64));
+    let layer = aseprite.get_layer_by_name("Col1Row1").unwrap();
+    let cel_image = aseprite
+        .get_frame(1)
+        .unwrap()
+        .get_image_by_layer(layer.index())
+        .unwrap()
+        .unwrap();
+    assert!(cel_image.width() < 96 && cel_image.height() < 64);
+}
+
+#[test]
+fn check_palette() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/simple.aseprite").unwrap();
+    // complex.aseprite 没有 palette chunk，这里用 simple.aseprite 的默认调色板
+    let palette = aseprite.palette().unwrap();
+    assert_eq!(palette.len(), 32);
+    let color = |red, green, blue| AsepriteColor {
+        red,
+        green,
+        blue,
+        alpha: 255,
+    };
+    assert_eq!(palette.get(0), Some(color(0, 0, 0)));
+    assert_eq!(palette.get(31), Some(color(138, 111, 48)));
+    assert_eq!(palette.get(32), None);
+    // 只有 indexed 文件才有透明索引
+    assert!(aseprite.transparent_index().is_none());
+
+    let indexed =
+        Aseprite::from_raw(mock_raw(AsepriteColorDepth::Indexed, 1, 1, vec![vec![]])).unwrap();
+    assert_eq!(indexed.transparent_index(), Some(0));
+}
+
+#[test]
+fn check_color_depth() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    assert_eq!(aseprite.color_depth(), crate::AsepriteColorDepth::RGBA);
+
+    for color_depth in [
+        AsepriteColorDepth::RGBA,
+        AsepriteColorDepth::Grayscale,
+        AsepriteColorDepth::Indexed,
+    ] {
+        let aseprite = Aseprite::from_raw(mock_raw(color_depth, 1, 1, vec![vec![]])).unwrap();
+        assert_eq!(aseprite.color_depth(), color_depth);
+    }
+}
+
+#[test]
+fn check_frame_infos() {
+    let aseprite =
+        Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+    assert_eq!(aseprite.frame_count(), 4);
+    assert_eq!(aseprite.frame_infos().len(), 4);
+    assert!(aseprite
+        .frame_infos()
+        .iter()
+        .all(|info| info.delay_ms == 100));
+    assert_eq!(aseprite.total_duration_ms(), 400);
+
+    let aseprite = Aseprite::from_raw(mock_raw(
+        AsepriteColorDepth::RGBA,
+        1,
+        1,
+        vec![vec![], vec![]],
+    ))
+    .unwrap();
+    assert_eq!(aseprite.total_duration_ms(), 200);
+}
+
+#[test]
+fn check_tag_by_name() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let tag = aseprite.get_tag_by_name("Frame2Tag").unwrap();
+    assert_eq!(tag.frames, 1..2);
+    assert!(aseprite.get_tag_by_name("Missing").is_none());
+    let names: Vec<_> = aseprite
+        .tags_for_frame(1)
+        .into_iter()
+        .map(|tag| tag.name.as_str())
+        .collect();
+    assert_eq!(names, ["FrameAllTag", "Frame2Tag"]);
+
+    // 同名的 tag 按 index 顺序返回
+    let tag = |from: u16, to: u16| RawAsepriteTag {
+        from,
+        to,
+        anim_direction: AsepriteAnimationDirection::Forward,
+        repeat: 0,
+        name: "walk".to_string(),
+    };
+    let aseprite = Aseprite::from_raw(mock_raw(
+        AsepriteColorDepth::RGBA,
+        1,
+        1,
+        vec![
+            vec![RawAsepriteChunk::Tags {
+                tags: vec![tag(1, 1), tag(0, 0)],
+            }],
+            vec![],
+        ],
+    ))
+    .unwrap();
+    assert_eq!(aseprite.get_tag_by_name("walk").unwrap().index, 0);
+    let indices: Vec<_> = aseprite
+        .get_tags_by_name("walk")
+        .into_iter()
+        .map(|tag| tag.index)
+        .collect();
+    assert_eq!(indices, [0, 1]);
+}
+
+#[test]
+fn check_layer_path() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let col2row1 = aseprite.get_layer_by_name("Col2Row1").unwrap();
+    assert_eq!(
+        aseprite.layer_path(col2row1.index()).as_deref(),
+        Some("Table/Col2/Col2Row1")
+    );
+    assert_eq!(aseprite.layer_path(0).as_deref(), Some("BG1"));
+    assert_eq!(aseprite.layer_path(100), None);
+
+    for layer in aseprite.layers() {
+        let path = aseprite.layer_path(layer.index()).unwrap();
+        assert_eq!(
+            aseprite.get_layer_by_path(&path).map(|found| found.index()),
+            Some(layer.index())
+        );
+    }
+    assert!(aseprite.get_layer_by_path("Col2Row1").is_none());
+    assert!(aseprite.get_layer_by_path("table/col2/col2row1").is_none());
+}
+
+#[test]
+fn check_layers_by_name() {
+    let aseprite = Aseprite::from_raw(mock_raw(
+        AsepriteColorDepth::RGBA,
+        1,
+        1,
+        vec![vec![
+            mock_group_chunk("Body", 0),
+            mock_layer_chunk("Outline", 1),
+            mock_group_chunk("Head", 0),
+            mock_layer_chunk("Outline", 1),
+        ]],
+    ))
+    .unwrap();
+
+    assert_eq!(aseprite.get_layer_by_name("Outline").unwrap().index(), 1);
+    let outlines: Vec<_> = aseprite
+        .get_layers_by_name("Outline")
+        .into_iter()
+        .map(|layer| aseprite.layer_path(layer.index()).unwrap())
+        .collect();
+    assert_eq!(outlines, ["Body/Outline", "Head/Outline"]);
+    assert!(aseprite.get_layers_by_name("Missing").is_empty());
+}
+
+#[test]
+fn check_layer_tree_walk() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let layer_tree = aseprite.layer_tree();
+
+    let mut visited = Vec::new();
+    for node in layer_tree.values() {
+        node.walk(|node, depth| visited.push((node.index(), depth)));
+    }
+    // 深度优先的顺序和 layer index（从下到上）一致
+    let expected: Vec<_> = aseprite
+        .layers()
+        .map(|layer| (layer.index(), layer.child_level() as usize))
+        .collect();
+    assert_eq!(visited, expected);
+
+    let table = &layer_tree[&3];
+    let children: Vec<_> = table.children().map(|node| node.name()).collect();
+    assert_eq!(
+        children,
+        ["Col1BG", "Col1", "Col2BG", "Col2", "Col3BG", "Col3"]
+    );
+    assert_eq!(layer_tree[&0].children().count(), 0);
+}
+
+#[test]
+fn check_layers_in_group() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let table = aseprite.get_layer_by_name("Table").unwrap().index();
+    let names = |recursive| {
+        aseprite
+            .layers_in_group(table, recursive)
+            .into_iter()
+            .map(|layer| layer.name())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        names(false),
+        ["Col1BG", "Col1", "Col2BG", "Col2", "Col3BG", "Col3"]
+    );
+    assert_eq!(
+        names(true),
+        [
+            "Col1BG", "Col1", "Col1Row1", "Col1Row2", "Col2BG", "Col2", "Col2Row1", "Col2Row2",
+            "Col3BG", "Col3", "Col3Row1", "Col3Row2"
+        ]
+    );
+
+    let bg1 = aseprite.get_layer_by_name("BG1").unwrap().index();
+    assert!(aseprite.layers_in_group(bg1, true).is_empty());
+    assert!(aseprite.layers_in_group(100, true).is_empty());
+}
+
+#[test]
+fn check_effective_visibility() {
+    let build = |group_visible: bool| {
+        let mut group = mock_group_chunk("Group", 0);
+        if let RawAsepriteChunk::Layer { flags, .. } = &mut group {
+            *flags &= if group_visible { !0 } else { !1 };
+        }
+        Aseprite::from_raw(mock_raw(
+            AsepriteColorDepth::RGBA,
+            2,
+            2,
+            vec![vec![
+                group,
+                mock_layer_chunk("Child", 1),
+                mock_cel_chunk(1, 0, 0, 2, 2, mock_rgba_pixels([255, 0, 0, 255], 4)),
+            ]],
+        ))
+        .unwrap()
+    };
+
+    let hidden = build(false);
+    assert!(hidden.get_layer_by_name("Child").unwrap().is_visible());
+    assert!(!hidden.is_layer_effectively_visible(1));
+    let frame = hidden.get_frame_image(0).unwrap();
+    assert!(frame.pixels().all(|pixel| pixel.0 == [0, 0, 0, 0]));
+    assert!(hidden.web_render_plan(0).is_empty());
+
+    let shown = build(true);
+    assert!(shown.is_layer_effectively_visible(1));
+    let frame = shown.get_frame_image(0).unwrap();
+    assert!(frame.pixels().all(|pixel| pixel.0 == [255, 0, 0, 255]));
+
+    // complex.aseprite 中 Night 图层本身是隐藏的
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let night = aseprite.get_layer_by_name("Night").unwrap().index();
+    let day = aseprite.get_layer_by_name("Day").unwrap().index();
+    assert!(!aseprite.is_layer_effectively_visible(night));
+    assert!(aseprite.is_layer_effectively_visible(day));
+}
+
+#[test]
+fn check_effective_opacity() {
+    let half = |mut chunk: RawAsepriteChunk| {
+        if let RawAsepriteChunk::Layer { opacity, .. } = &mut chunk {
+            *opacity = 128;
+        }
+        chunk
+    };
+    // 50% 的图层放在 50% 的 group 里
+    let aseprite = Aseprite::from_raw(mock_raw(
+        AsepriteColorDepth::RGBA,
+        2,
+        2,
+        vec![vec![
+            half(mock_group_chunk("Group", 0)),
+            half(mock_layer_chunk("Layer", 1)),
+            mock_cel_chunk(1, 0, 0, 2, 2, mock_rgba_pixels([255, 0, 0, 255], 4)),
+        ]],
+    ))
+    .unwrap();
+    assert_eq!(aseprite.effective_cel_opacity(1, 0), 64);
+    assert_eq!(aseprite.effective_cel_opacity(0, 0), 0);
+    let frame = aseprite.get_frame_image(0).unwrap();
+    assert!(frame.pixels().all(|pixel| pixel.0 == [255, 0, 0, 64]));
+    assert_eq!(aseprite.web_render_plan(0)[0].opacity, 64);
+
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let bg1 = aseprite.get_layer_by_name("BG1").unwrap().index();
+    assert_eq!(aseprite.effective_cel_opacity(bg1, 0), 255);
+    assert_eq!(aseprite.effective_cel_opacity(bg1, 1), 128);
+    // 这个文件没有设置 group opacity 有效的 flag
+    let table = aseprite.get_layer_by_name("Table").unwrap();
+    assert_eq!(table.opacity(), None);
+    let col1bg = aseprite.get_layer_by_name("Col1BG").unwrap().index();
+    assert_eq!(aseprite.effective_cel_opacity(col1bg, 1), 128);
+}
+
+#[test]
+fn check_layer_flags() {
+    let with_flags = |mut chunk: RawAsepriteChunk, layer_flags: u16| {
+        if let RawAsepriteChunk::Layer { flags, .. } = &mut chunk {
+            *flags = layer_flags;
+        }
+        chunk
+    };
+    let aseprite = Aseprite::from_raw(mock_raw(
+        AsepriteColorDepth::RGBA,
+        1,
+        1,
+        vec![vec![
+            // 可见 + 可编辑 + 锁定移动 + 背景
+            with_flags(mock_layer_chunk("Background", 0), 1 | 2 | 4 | 8),
+            mock_cel_chunk(0, 0, 0, 1, 1, mock_rgba_pixels([0, 0, 255, 255], 1)),
+            // 可见 + 参考图层
+            with_flags(mock_layer_chunk("Reference", 0), 1 | 64),
+            mock_cel_chunk(1, 0, 0, 1, 1, mock_rgba_pixels([255, 0, 0, 255], 1)),
+            // 可见 + 可编辑 + 折叠
+            with_flags(mock_group_chunk("Group", 0), 1 | 2 | 32),
+            with_flags(mock_layer_chunk("Continuous", 1), 1 | 2 | 16),
+        ]],
+    ))
+    .unwrap();
+
+    let layer = |name| aseprite.get_layer_by_name(name).unwrap();
+    assert!(layer("Background").is_background());
+    assert!(layer("Background").is_movement_locked());
+    assert!(!layer("Background").is_locked());
+    assert!(layer("Reference").is_reference());
+    assert!(layer("Reference").is_locked());
+    assert!(layer("Reference").is_visible());
+    assert!(layer("Group").is_collapsed());
+    assert!(layer("Continuous").is_continuous());
+    assert!(!layer("Continuous").is_background());
+
+    // 参考图层不参与合成
+    let frame = aseprite.get_frame_image(0).unwrap();
+    assert_eq!(frame.get_pixel(0, 0).0, [0, 0, 255, 255]);
+    let plan: Vec<_> = aseprite
+        .web_render_plan(0)
+        .iter()
+        .map(|command| command.layer)
+        .collect();
+    assert_eq!(plan, [0]);
+}
+
+#[test]
+fn check_layer_uuid() {
+    let buffer = std::fs::read("./tests/test_cases/simple.aseprite").unwrap();
+    let aseprite = Aseprite::from_bytes(&buffer).unwrap();
+    assert!(aseprite.layers().all(|layer| layer.uuid().is_none()));
+
+    // 模拟 Aseprite 1.3 保存的文件：设置 header flag 4，并在 layer chunk 末尾加上 UUID
+    let read_u32 = |buffer: &[u8], at: usize| {
+        u32::from_le_bytes([buffer[at], buffer[at + 1], buffer[at + 2], buffer[at + 3]])
+    };
+    let add_u32 = |buffer: &mut Vec<u8>, at: usize, value: u32| {
+        let sum = read_u32(buffer, at) + value;
+        buffer[at..at + 4].copy_from_slice(&sum.to_le_bytes());
+    };
+    let mut patched = buffer;
+    let mut chunk_offset = 128 + 16;
+    while u16::from_le_bytes([patched[chunk_offset + 4], patched[chunk_offset + 5]]) != 0x2004 {
+        chunk_offset += read_u32(&patched, chunk_offset) as usize;
+    }
+    let uuid: [u8; 16] = std::array::from_fn(|i| i as u8);
+    let chunk_end = chunk_offset + read_u32(&patched, chunk_offset) as usize;
+    patched.splice(chunk_end..chunk_end, uuid);
+    add_u32(&mut patched, chunk_offset, 16);
+    add_u32(&mut patched, 128, 16);
+    add_u32(&mut patched, 0, 16);
+    add_u32(&mut patched, 14, 4);
+
+    let aseprite = Aseprite::from_bytes(&patched).unwrap();
+    assert_eq!(aseprite.layers().next().unwrap().uuid(), Some(uuid));
+}
+
+#[test]
+fn check_blend_row() {
+    use AsepriteBlendMode::*;
+    // 不透明、半透明和完全透明的像素混合
+    let pixels: Vec<[u8; 4]> = (0..64u32)
+        .map(|i| {
+            let alpha = match i % 4 {
+                0 => 0,
+                1 => 255,
+                _ => (i * 37) as u8,
+            };
+            [(i * 53) as u8, (i * 101) as u8, (i * 7) as u8, alpha]
+        })
+        .collect();
+    for blend_mode in [
+        Normal, Multiply, Screen, Overlay, Darken, Lighten, ColorDodge, ColorBurn, HardLight,
+        SoftLight, Difference, Exclusion, Hue, Saturation, Color, Luminosity, Addition, Subtract,
+        Divide,
+    ] {
+        for opacity in [0, 128, 255] {
+            let mut row: Vec<u8> = pixels.iter().rev().flatten().copied().collect();
+            let expected: Vec<u8> = row
+                .chunks_exact(4)
+                .zip(&pixels)
+                .flat_map(|(backdrop, &src)| {
+                    let backdrop = [backdrop[0], backdrop[1], backdrop[2], backdrop[3]];
+                    blend_rgba(backdrop, src, opacity, blend_mode)
+                })
+                .collect();
+            blend_row(&mut row, &pixels, opacity, blend_mode);
+            assert_eq!(row, expected, "{:?} {}", blend_mode, opacity);
         }
     }
 }
+
+#[test]
+fn check_blend_modes() {
+    let backdrop = [200, 100, 50, 255];
+    let gray = [128, 128, 128, 255];
+    let blend = |src, blend_mode| blend_rgba(backdrop, src, 255, blend_mode);
+
+    // 期望值按照 Aseprite 的 blend_funcs.cpp 逐步计算得到
+    assert_eq!(blend(gray, AsepriteBlendMode::Normal), gray);
+    assert_eq!(blend(gray, AsepriteBlendMode::Multiply), [100, 50, 25, 255]);
+    assert_eq!(blend(gray, AsepriteBlendMode::Screen), [228, 178, 153, 255]);
+    assert_eq!(
+        blend(gray, AsepriteBlendMode::Addition),
+        [255, 228, 178, 255]
+    );
+    assert_eq!(blend(gray, AsepriteBlendMode::Subtract), [72, 0, 0, 255]);
+    assert_eq!(
+        blend(gray, AsepriteBlendMode::Difference),
+        [72, 28, 78, 255]
+    );
+    assert_eq!(
+        blend([255, 255, 255, 255], AsepriteBlendMode::SoftLight),
+        [226, 160, 113, 255]
+    );
+    // 灰色的亮度套用到 backdrop 的色相和饱和度上，得到的颜色不再带灰
+    let luminosity = blend(gray, AsepriteBlendMode::Luminosity);
+    assert!(luminosity[0] > luminosity[1] && luminosity[1] > luminosity[2]);
+    // 灰色没有饱和度，结果是 backdrop 的亮度 0.3 * 200 + 0.59 * 100 + 0.11 * 50
+    assert_eq!(
+        blend(gray, AsepriteBlendMode::Saturation)[..3],
+        [124, 124, 124]
+    );
+
+    // 半透明的 src 和 opacity
+    assert_eq!(
+        blend_rgba(
+            [0, 0, 255, 255],
+            [255, 0, 0, 128],
+            255,
+            AsepriteBlendMode::Normal
+        ),
+        [128, 0, 127, 255]
+    );
+    assert_eq!(
+        blend_rgba(
+            [0, 0, 0, 0],
+            [255, 0, 0, 255],
+            64,
+            AsepriteBlendMode::Multiply
+        ),
+        [0, 0, 0, 64]
+    );
+    assert_eq!(
+        blend_rgba(backdrop, gray, 0, AsepriteBlendMode::Screen),
+        backdrop
+    );
+}
+
+#[test]
+fn check_layer_opacity() {
+    let build = |header_flags: u32| {
+        let mut layer = mock_layer_chunk("Layer", 0);
+        if let RawAsepriteChunk::Layer { opacity, .. } = &mut layer {
+            *opacity = 128;
+        }
+        let mut raw = mock_raw(
+            AsepriteColorDepth::RGBA,
+            1,
+            1,
+            vec![vec![
+                layer,
+                mock_cel_chunk(0, 0, 0, 1, 1, mock_rgba_pixels([0, 255, 0, 255], 1)),
+            ]],
+        );
+        raw.header.flags = header_flags;
+        Aseprite::from_raw(raw).unwrap()
+    };
+
+    let aseprite = build(1);
+    assert!(aseprite.layer_opacity_valid());
+    assert_eq!(aseprite.get_layer_by_index(0).unwrap().opacity(), Some(128));
+    let frame = aseprite.get_frame_image(0).unwrap();
+    assert_eq!(frame.get_pixel(0, 0).0, [0, 255, 0, 128]);
+
+    // header 没有标记 layer opacity 有效时按 255 处理
+    let aseprite = build(0);
+    assert!(!aseprite.layer_opacity_valid());
+    assert_eq!(aseprite.get_layer_by_index(0).unwrap().opacity(), Some(255));
+    let frame = aseprite.get_frame_image(0).unwrap();
+    assert_eq!(frame.get_pixel(0, 0).0, [0, 255, 0, 255]);
+
+    // 旧版本 "不使用 layer opacity" 的设置保存的文件，header flags 为 0
+    let mut bytes = std::fs::read("./tests/test_cases/simple.aseprite").unwrap();
+    bytes[14..18].copy_from_slice(&0u32.to_le_bytes());
+    let aseprite = Aseprite::from_bytes(&bytes).unwrap();
+    assert!(!aseprite.layer_opacity_valid());
+    assert!(aseprite.layers().all(|layer| layer.opacity() == Some(255)));
+
+    // complex.aseprite 里 Day 图层的透明度是 128
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let day = aseprite.get_layer_by_name("Day").unwrap().index();
+    assert_eq!(aseprite.effective_cel_opacity(day, 0), 128);
+}
+
+#[test]
+fn check_cel_z_index() {
+    let mut below = mock_cel_chunk(1, 0, 0, 1, 1, mock_rgba_pixels([0, 0, 255, 255], 1));
+    if let RawAsepriteChunk::Cel { z_index, .. } = &mut below {
+        *z_index = -1;
+    }
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        1,
+        1,
+        vec![vec![
+            mock_layer_chunk("Red", 0),
+            mock_layer_chunk("Blue", 0),
+            mock_cel_chunk(0, 0, 0, 1, 1, mock_rgba_pixels([255, 0, 0, 255], 1)),
+            below,
+        ]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    // Blue 的 z-index 是 -1，排在 Red 下面
+    assert_eq!(aseprite.cel_render_order(0), vec![1, 0]);
+    let frame = aseprite.get_frame_image(0).unwrap();
+    assert_eq!(frame.get_pixel(0, 0).0, [255, 0, 0, 255]);
+
+    // complex.aseprite 第二帧里 Col3Row1 的 z-index 是 -20，最先绘制
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    assert_eq!(aseprite.cel_render_order(1)[0], 14);
+    assert_eq!(aseprite.cel_render_order(0)[0], 0);
+}
+
+#[test]
+#[allow(deprecated)]
+fn check_frame_image() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let image = aseprite.get_frame_image(1).unwrap();
+    assert_eq!(image.dimensions(), (96, 64));
+    assert_eq!(aseprite.get_frame(1).unwrap().get_image().unwrap(), image);
+    assert!(matches!(
+        aseprite.get_frame_image(2),
+        Err(AsepriteError::InvalidConfiguration(
+            AsepriteInvalidError::InvalidFrame(2)
+        ))
+    ));
+}
+
+#[test]
+fn check_frame_image_with_layers() {
+    let mut hidden = mock_layer_chunk("Hat", 0);
+    if let RawAsepriteChunk::Layer { flags, .. } = &mut hidden {
+        *flags &= !1;
+    }
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        2,
+        1,
+        vec![vec![
+            mock_layer_chunk("Body", 0),
+            hidden,
+            mock_layer_chunk("Cape", 0),
+            mock_cel_chunk(0, 0, 0, 2, 1, mock_rgba_pixels([255, 0, 0, 255], 2)),
+            mock_cel_chunk(1, 0, 0, 1, 1, mock_rgba_pixels([0, 0, 255, 255], 1)),
+        ]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+
+    // 隐藏的 Hat 也可以被选中，并且按图层顺序绘制在 Body 上面
+    let image = aseprite.get_frame_image_with_layers(0, &[1, 0]).unwrap();
+    assert_eq!(image.get_pixel(0, 0).0, [0, 0, 255, 255]);
+    assert_eq!(image.get_pixel(1, 0).0, [255, 0, 0, 255]);
+    let image = aseprite.get_frame_image_with_layers(0, &[0]).unwrap();
+    assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+
+    // Cape 在这一帧没有 cel，直接跳过
+    let image = aseprite
+        .get_frame_image_with_layer_names(0, &["Cape"])
+        .unwrap();
+    assert!(image.pixels().all(|pixel| pixel.0 == [0, 0, 0, 0]));
+    assert_eq!(
+        aseprite
+            .get_frame_image_with_layer_names(0, &["Body", "Hat"])
+            .unwrap(),
+        aseprite.get_frame_image_with_layers(0, &[0, 1]).unwrap()
+    );
+
+    assert!(matches!(
+        aseprite.get_frame_image_with_layers(0, &[0, 3]),
+        Err(AsepriteError::InvalidConfiguration(
+            AsepriteInvalidError::InvalidLayer(3)
+        ))
+    ));
+    assert!(matches!(
+        aseprite.get_frame_image_with_layer_names(0, &["Shoes"]),
+        Err(AsepriteError::InvalidConfiguration(
+            AsepriteInvalidError::InvalidLayerName(_)
+        ))
+    ));
+}
+
+#[test]
+fn check_group_image() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let table = aseprite.get_layer_by_name("Table").unwrap().index();
+    let members: Vec<usize> = aseprite
+        .layers_in_group(table, true)
+        .iter()
+        .map(|layer| layer.index())
+        .collect();
+    for frame_index in 0..aseprite.frame_count() {
+        let image = aseprite
+            .get_group_image(table, frame_index)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            image,
+            aseprite
+                .get_frame_image_with_layers(frame_index, &members)
+                .unwrap()
+        );
+    }
+    assert!(matches!(
+        aseprite.get_group_image(0, 0),
+        Err(AsepriteError::InvalidConfiguration(
+            AsepriteInvalidError::NotAGroup(0)
+        ))
+    ));
+    assert!(matches!(
+        aseprite.get_group_image(100, 0),
+        Err(AsepriteError::InvalidConfiguration(
+            AsepriteInvalidError::InvalidLayer(100)
+        ))
+    ));
+
+    // group 里只有隐藏的图层时没有图片
+    let mut hidden = mock_layer_chunk("Hidden", 1);
+    if let RawAsepriteChunk::Layer { flags, .. } = &mut hidden {
+        *flags &= !1;
+    }
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        1,
+        1,
+        vec![vec![
+            mock_group_chunk("Group", 0),
+            hidden,
+            mock_cel_chunk(1, 0, 0, 1, 1, mock_rgba_pixels([255, 0, 0, 255], 1)),
+        ]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    assert!(aseprite.get_group_image(0, 0).unwrap().is_none());
+}
+
+#[test]
+fn check_render_frame() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let options = FrameRenderOptions::default();
+    for frame_index in 0..aseprite.frame_count() {
+        assert_eq!(
+            aseprite.render_frame(frame_index, &options).unwrap(),
+            aseprite.get_frame_image(frame_index).unwrap()
+        );
+    }
+    // Night 图层是隐藏的
+    let night = aseprite.get_layer_by_name("Night").unwrap().index();
+    let mut options = FrameRenderOptions {
+        include: Some(vec![night]),
+        ..Default::default()
+    };
+    let image = aseprite.render_frame(0, &options).unwrap();
+    assert!(image.pixels().all(|pixel| pixel.0[3] == 0));
+    options.include_hidden = true;
+    let image = aseprite.render_frame(0, &options).unwrap();
+    assert!(image.pixels().any(|pixel| pixel.0[3] != 0));
+
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        2,
+        1,
+        vec![vec![
+            mock_layer_chunk("Sprite", 0),
+            mock_layer_chunk("Watermark", 0),
+            mock_cel_chunk(0, 0, 0, 1, 1, mock_rgba_pixels([255, 0, 0, 255], 1)),
+            mock_cel_chunk(1, 0, 0, 2, 1, mock_rgba_pixels([0, 0, 255, 255], 2)),
+        ]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    let options = FrameRenderOptions {
+        exclude: vec![1],
+        background: Some(AsepriteColor {
+            red: 0,
+            green: 255,
+            blue: 0,
+            alpha: 255,
+        }),
+        ..Default::default()
+    };
+    let image = aseprite.render_frame(0, &options).unwrap();
+    assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+    assert_eq!(image.get_pixel(1, 0).0, [0, 255, 0, 255]);
+
+    let options = FrameRenderOptions {
+        exclude: vec![2],
+        ..Default::default()
+    };
+    assert!(matches!(
+        aseprite.render_frame(0, &options),
+        Err(AsepriteError::InvalidConfiguration(
+            AsepriteInvalidError::InvalidLayer(2)
+        ))
+    ));
+}
+
+#[test]
+fn check_layer_image_on_canvas() {
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        3,
+        3,
+        vec![vec![
+            mock_layer_chunk("Left", 0),
+            mock_layer_chunk("Right", 0),
+            mock_cel_chunk(0, -1, -1, 2, 2, mock_rgba_pixels([255, 0, 0, 255], 4)),
+            mock_cel_chunk(1, 2, 1, 3, 3, mock_rgba_pixels([0, 0, 255, 255], 9)),
+        ]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+
+    // 负的偏移和超出画布的部分都要被裁掉
+    let left = aseprite
+        .get_image_by_layer_frame_on_canvas(0, 0)
+        .unwrap()
+        .unwrap();
+    assert_eq!(left.dimensions(), (3, 3));
+    let opaque: Vec<_> = left
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel.0[3] != 0)
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    assert_eq!(opaque, vec![(0, 0)]);
+
+    let right = aseprite
+        .get_image_by_layer_frame_on_canvas(1, 0)
+        .unwrap()
+        .unwrap();
+    let opaque: Vec<_> = right
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel.0[3] != 0)
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    assert_eq!(opaque, vec![(2, 1), (2, 2)]);
+
+    // 裁剪后的版本保持 cel 的大小
+    let trimmed = aseprite.get_image_by_layer_frame(1, 0).unwrap().unwrap();
+    assert_eq!(trimmed.dimensions(), (3, 3));
+    assert!(aseprite
+        .get_image_by_layer_frame_on_canvas(2, 0)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn check_cel_bounds() {
+    let aseprite =
+        Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+    let first = aseprite.get_cel(2, 0).unwrap();
+    let bounds = first.bounds().unwrap();
+    assert_eq!((bounds.x, bounds.y), first.origin());
+    assert_eq!(aseprite.cel_bounds(2, 0), Some(bounds));
+
+    // linked cel 用自己的位置和被链接 cel 的尺寸
+    let linked = aseprite.get_cel(2, 1).unwrap();
+    assert!(linked.bounds().is_none());
+    let (x, y) = linked.origin();
+    assert_eq!(
+        aseprite.cel_bounds(2, 1),
+        Some(AsepriteRect::new(x, y, bounds.width, bounds.height))
+    );
+    assert_eq!(aseprite.cel_bounds(0, 0), None);
+}
+
+#[test]
+#[allow(deprecated)]
+fn check_trimmed_frame() {
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        4,
+        4,
+        vec![vec![
+            mock_layer_chunk("Layer", 0),
+            mock_cel_chunk(0, 1, 2, 2, 1, mock_rgba_pixels([255, 0, 0, 255], 2)),
+        ]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    assert_eq!(
+        aseprite.frame_content_bounds(0).unwrap(),
+        Some(AsepriteRect::new(1, 2, 2, 1))
+    );
+    let (image, offset) = aseprite
+        .get_frame(0)
+        .unwrap()
+        .get_trimmed_image()
+        .unwrap()
+        .unwrap();
+    assert_eq!(offset, (1, 2));
+    assert_eq!(image.dimensions(), (2, 1));
+    assert!(image.pixels().all(|pixel| pixel.0 == [255, 0, 0, 255]));
+
+    // 完全透明的帧返回 None
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        4,
+        4,
+        vec![vec![
+            mock_layer_chunk("Layer", 0),
+            mock_cel_chunk(0, 0, 0, 2, 2, mock_rgba_pixels([255, 0, 0, 0], 4)),
+        ]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    assert_eq!(aseprite.frame_content_bounds(0).unwrap(), None);
+    assert!(aseprite
+        .get_frame(0)
+        .unwrap()
+        .get_trimmed_image()
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn check_durations() {
+    let aseprite =
+        Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+    assert_eq!(aseprite.total_duration(), Duration::from_millis(400));
+    assert_eq!(
+        aseprite.frame_infos()[0].duration(),
+        Duration::from_millis(100)
+    );
+
+    // 帧时长为 0 时使用 header 中的速度
+    let frames = (0..4).map(|_| Vec::new()).collect();
+    let mut raw = mock_raw(AsepriteColorDepth::RGBA, 1, 1, frames);
+    raw.frames[1].duration_ms = 0;
+    #[allow(deprecated)]
+    {
+        raw.header.speed = 40;
+    }
+    raw.frames[2].duration_ms = 50;
+    let tag = |name: &str, from: u16, to: u16, anim_direction| RawAsepriteTag {
+        from,
+        to,
+        anim_direction,
+        repeat: 0,
+        name: name.to_string(),
+    };
+    raw.frames[0].chunks.push(RawAsepriteChunk::Tags {
+        tags: vec![
+            tag("Forward", 0, 3, AsepriteAnimationDirection::Forward),
+            tag("PingPong", 0, 3, AsepriteAnimationDirection::PingPong),
+            tag("Single", 1, 1, AsepriteAnimationDirection::PingPong),
+        ],
+    });
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    assert_eq!(aseprite.default_delay_ms(), 40);
+    assert_eq!(aseprite.frame_infos()[1].delay_ms, 40);
+    assert_eq!(aseprite.total_duration(), Duration::from_millis(290));
+    #[allow(deprecated)]
+    let frame = aseprite.get_frame(1).unwrap();
+    assert_eq!(frame.duration(), Duration::from_millis(40));
+
+    let tag = |name| aseprite.get_tag_by_name(name).unwrap();
+    assert_eq!(
+        tag("Forward").duration(&aseprite),
+        Duration::from_millis(290)
+    );
+    // 100 + 40 + 50 + 100 + 50 + 40
+    assert_eq!(
+        tag("PingPong").duration(&aseprite),
+        Duration::from_millis(380)
+    );
+    assert_eq!(tag("Single").duration(&aseprite), Duration::from_millis(40));
+}
+
+#[test]
+fn check_tag_frame_sequence() {
+    let tag = |from: u16, to: u16, anim_direction| RawAsepriteTag {
+        from,
+        to,
+        anim_direction,
+        repeat: 0,
+        name: format!("{from}-{to}-{anim_direction:?}"),
+    };
+    let frames: Vec<Vec<RawAsepriteChunk>> = (0..6).map(|_| Vec::new()).collect();
+    let mut raw = mock_raw(AsepriteColorDepth::RGBA, 1, 1, frames);
+    raw.frames[0].chunks.push(RawAsepriteChunk::Tags {
+        tags: vec![
+            tag(2, 2, AsepriteAnimationDirection::PingPong),
+            tag(2, 2, AsepriteAnimationDirection::Reverse),
+            tag(1, 2, AsepriteAnimationDirection::PingPong),
+            tag(1, 4, AsepriteAnimationDirection::PingPong),
+            tag(1, 4, AsepriteAnimationDirection::Reverse),
+            tag(1, 4, AsepriteAnimationDirection::PingPongReverse),
+            tag(1, 2, AsepriteAnimationDirection::PingPongReverse),
+        ],
+    });
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    let sequences: Vec<Vec<u16>> = aseprite.tags().map(|tag| tag.frame_sequence()).collect();
+    assert_eq!(
+        sequences,
+        vec![
+            vec![2],
+            vec![2],
+            vec![1, 2],
+            vec![1, 2, 3, 4, 3, 2],
+            vec![4, 3, 2, 1],
+            vec![4, 3, 2, 1, 2, 3],
+            vec![2, 1],
+        ]
+    );
+
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let sequence = |name| aseprite.get_tag_by_name(name).unwrap().frame_sequence();
+    assert_eq!(sequence("FrameAllTag"), vec![0, 1]);
+    assert_eq!(sequence("Frame1Tag"), vec![0]);
+    assert_eq!(sequence("Frame2Tag"), vec![1]);
+}
+
+#[test]
+fn check_cels_iterators() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let col3row1 = aseprite.get_layer_by_name("Col3Row1").unwrap().index();
+    let cels: Vec<_> = aseprite.cels_for_layer(col3row1).collect();
+    assert_eq!(cels.len(), 2);
+    assert_eq!(cels[0].0, 0);
+    assert_eq!(cels[1].0, 1);
+    assert_eq!(cels[1].1.z_index, -20);
+    // group 没有 cel
+    assert_eq!(aseprite.cels_for_layer(3).count(), 0);
+    assert_eq!(aseprite.cels_for_layer(100).count(), 0);
+
+    let layers: Vec<usize> = aseprite
+        .cels_for_frame(1)
+        .map(|(layer_index, _)| layer_index)
+        .collect();
+    assert!(layers.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(layers.contains(&col3row1));
+    for (layer_index, cel) in aseprite.cels_for_frame(1) {
+        assert!(std::ptr::eq(cel, aseprite.get_cel(layer_index, 1).unwrap()));
+    }
+    assert_eq!(aseprite.cels_for_frame(2).count(), 0);
+}
+
+#[test]
+fn check_sparse_cels() {
+    // layer 0 每一帧都有 cel，layer 1 只在 4 帧中的 1 帧有 cel
+    let cel =
+        |layer_index, x| mock_cel_chunk(layer_index, x, 0, 1, 1, mock_rgba_pixels([255; 4], 1));
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        4,
+        1,
+        vec![
+            vec![
+                mock_layer_chunk("Dense", 0),
+                mock_layer_chunk("Sparse", 0),
+                cel(0, 0),
+            ],
+            vec![cel(0, 1)],
+            vec![cel(0, 2), cel(1, 3)],
+            vec![cel(0, 3)],
+        ],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    assert!(matches!(aseprite.cels.layer(0), Some(LayerCels::Dense(_))));
+    assert!(matches!(aseprite.cels.layer(1), Some(LayerCels::Sparse(_))));
+
+    let frames = |layer_index| -> Vec<(usize, i16)> {
+        aseprite
+            .cels_for_layer(layer_index)
+            .map(|(frame_index, cel)| (frame_index, cel.x))
+            .collect()
+    };
+    assert_eq!(frames(0), vec![(0, 0), (1, 1), (2, 2), (3, 3)]);
+    assert_eq!(frames(1), vec![(2, 3)]);
+    assert_eq!(aseprite.get_cel(1, 2).unwrap().x, 3);
+    assert!(aseprite.get_cel(1, 1).is_none());
+    assert!(aseprite.get_cel(1, 4).is_none());
+    assert_eq!(aseprite.cel_render_order(2), vec![0, 1]);
+    assert_eq!(
+        aseprite.get_frame_image(2).unwrap().get_pixel(3, 0).0,
+        [255; 4]
+    );
+}
+
+#[test]
+#[allow(deprecated)]
+fn check_frame_cels() {
+    let aseprite =
+        Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+    let cels = aseprite.get_frame(1).unwrap().cels();
+    match cels.get(&2) {
+        Some(CelRef::Linked { target_frame, cel }) => {
+            assert_eq!(*target_frame, 0);
+            assert!(std::ptr::eq(*cel, aseprite.get_cel(2, 1).unwrap()));
+        }
+        _ => unreachable!(),
+    }
+    let cels = aseprite.get_frame(0).unwrap().cels();
+    assert!(matches!(cels.get(&2), Some(CelRef::Direct(_))));
+    assert!(!cels.contains_key(&0));
+    assert_eq!(
+        aseprite.get_cels_by_frame(0).len(),
+        cels.values().map(CelRef::cel).count()
+    );
+}
+
+#[test]
+fn check_resolve_linked_cel() {
+    let aseprite =
+        Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+    let target = aseprite.get_cel(2, 0).unwrap();
+    let linked = aseprite.get_cel(2, 1).unwrap();
+    // 像素来自被链接的 cel，位置、透明度和 z-index 是 linked cel 自己的
+    let resolved = aseprite.resolve_cel(2, 1).unwrap();
+    assert!(std::ptr::eq(resolved.source, target));
+    assert_eq!(
+        (resolved.x, resolved.y, resolved.opacity, resolved.z_index),
+        (linked.x, linked.y, linked.opacity, linked.z_index)
+    );
+    assert_eq!(Some(resolved.bounds()), aseprite.cel_bounds(2, 1));
+    let resolved = aseprite.resolve_cel(2, 0).unwrap();
+    assert!(std::ptr::eq(resolved.source, target));
+    assert_eq!((resolved.x, resolved.y), (target.x, target.y));
+    assert!(aseprite.resolve_cel(0, 0).is_err());
+
+    // linked cel 的图片和被链接的 cel 一样，位置用 linked cel 自己的
+    assert_eq!(
+        aseprite.get_image_by_layer_frame(2, 1).unwrap(),
+        aseprite.get_image_by_layer_frame(2, 0).unwrap()
+    );
+    let mut expected = image::RgbaImage::new(32, 32);
+    image::imageops::replace(
+        &mut expected,
+        &aseprite.get_image_by_layer_frame(2, 0).unwrap().unwrap(),
+        linked.x as i64,
+        linked.y as i64,
+    );
+    assert_eq!(
+        aseprite
+            .get_image_by_layer_frame_on_canvas(2, 1)
+            .unwrap()
+            .unwrap(),
+        expected
+    );
+
+    // 互相链接的 cel 要报错而不是死循环
+    let linked_cel = |frame_position| RawAsepriteChunk::Cel {
+        layer_index: 0,
+        x: 0,
+        y: 0,
+        opacity: 255,
+        z_index: 0,
+        cel: RawAsepriteCel::Linked { frame_position },
+    };
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        1,
+        1,
+        vec![
+            vec![mock_layer_chunk("Layer", 0), linked_cel(1)],
+            vec![linked_cel(0)],
+        ],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    assert!(matches!(
+        aseprite.resolve_cel(0, 0),
+        Err(AsepriteError::InvalidConfiguration(
+            AsepriteInvalidError::LinkedCelCycle {
+                layer_index: 0,
+                frame_index: 0
+            }
+        ))
+    ));
+    assert!(aseprite.get_frame_image(0).is_err());
+    assert!(aseprite.prewarm([1]).is_err());
+    assert_eq!(aseprite.cel_bounds(0, 0), None);
+    assert!(aseprite.get_image_by_layer_frame(0, 1).is_err());
+}
+
+#[test]
+fn check_cel_frame_association() {
+    // Late 图层从第三帧才开始有 cel，cel 要对应到实际的帧而不是往前挪
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        1,
+        1,
+        vec![
+            vec![
+                mock_layer_chunk("Early", 0),
+                mock_layer_chunk("Late", 0),
+                mock_cel_chunk(0, 0, 0, 1, 1, mock_rgba_pixels([255, 0, 0, 255], 1)),
+            ],
+            vec![mock_cel_chunk(
+                0,
+                0,
+                0,
+                1,
+                1,
+                mock_rgba_pixels([0, 255, 0, 255], 1),
+            )],
+            vec![
+                mock_cel_chunk(0, 0, 0, 1, 1, mock_rgba_pixels([0, 0, 255, 255], 1)),
+                mock_cel_chunk(1, 0, 0, 1, 1, mock_rgba_pixels([255, 255, 0, 255], 1)),
+            ],
+        ],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    assert!(aseprite.get_cel(1, 0).is_none());
+    assert!(aseprite.get_cel(1, 1).is_none());
+    assert!(aseprite.get_cel(1, 2).is_some());
+    let frames: Vec<usize> = aseprite
+        .cels_for_layer(1)
+        .map(|(frame_index, _)| frame_index)
+        .collect();
+    assert_eq!(frames, vec![2]);
+
+    let late = aseprite.get_image_by_layer_frame(1, 2).unwrap().unwrap();
+    assert_eq!(late.get_pixel(0, 0).0, [255, 255, 0, 255]);
+    let frame = aseprite.get_frame_image(1).unwrap();
+    assert_eq!(frame.get_pixel(0, 0).0, [0, 255, 0, 255]);
+    let frame = aseprite.get_frame_image(2).unwrap();
+    assert_eq!(frame.get_pixel(0, 0).0, [255, 255, 0, 255]);
+}
+
+#[test]
+fn check_cel_outside_canvas() {
+    // cel 超出画布的右边和下边
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        4,
+        3,
+        vec![vec![
+            mock_layer_chunk("Layer", 0),
+            mock_cel_chunk(0, 2, 1, 5, 4, mock_rgba_pixels([255, 0, 0, 255], 20)),
+        ]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    let frame = aseprite.get_frame_image(0).unwrap();
+    assert_eq!(frame.dimensions(), (4, 3));
+    for (x, y, pixel) in frame.enumerate_pixels() {
+        let covered = x >= 2 && y >= 1;
+        assert_eq!(pixel.0[3] == 255, covered, "({x}, {y})");
+    }
+    let on_canvas = aseprite
+        .get_image_by_layer_frame_on_canvas(0, 0)
+        .unwrap()
+        .unwrap();
+    assert_eq!(on_canvas, frame);
+    let trimmed = aseprite.get_image_by_layer_frame(0, 0).unwrap().unwrap();
+    assert_eq!(trimmed.dimensions(), (5, 4));
+}
+
+#[test]
+fn check_slice_user_data() {
+    let slice = |name: &str| RawAsepriteChunk::Slice {
+        flags: 0,
+        name: name.to_string(),
+        slices: vec![RawAsepriteSlice {
+            frame: 0,
+            x_origin: 1,
+            y_origin: 2,
+            width: 3,
+            height: 4,
+            nine_patch_info: None,
+            pivot: None,
+        }],
+    };
+    let user_data = |text: &str, red: u8| RawAsepriteChunk::UserData {
+        data: RawAsepriteUserData {
+            text: text.to_string(),
+            color: AsepriteColor {
+                red,
+                green: 0,
+                blue: 0,
+                alpha: 255,
+            },
+            properties: Vec::new(),
+            properties_data: Vec::new(),
+        },
+    };
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        8,
+        8,
+        vec![vec![
+            slice("Hitbox"),
+            user_data("collision", 255),
+            slice("Plain"),
+        ]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    assert_eq!(aseprite.slices().count(), 2);
+
+    // slice 后面的 user data 属于这个 slice
+    let hitbox = aseprite.get_slice_by_name("Hitbox").unwrap();
+    assert_eq!(hitbox.user_data, "collision");
+    assert_eq!(hitbox.color.red, 255);
+    assert_eq!(hitbox.keys.len(), 1);
+    assert_eq!(hitbox.keys[0].bounds, AsepriteRect::new(1, 2, 3, 4));
+
+    let plain = aseprite.get_slice_by_name("Plain").unwrap();
+    assert_eq!(plain.index, 1);
+    assert!(plain.user_data.is_empty());
+    assert!(aseprite.get_slice_by_name("Missing").is_none());
+}
+
+#[test]
+fn check_slice_keys() {
+    let key = |frame: u32, x_origin: i32| RawAsepriteSlice {
+        frame,
+        x_origin,
+        y_origin: 0,
+        width: 2,
+        height: 2,
+        nine_patch_info: None,
+        pivot: None,
+    };
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        8,
+        8,
+        vec![vec![RawAsepriteChunk::Slice {
+            flags: 0,
+            name: "Moving".to_string(),
+            // 文件里的 key 不一定按帧排序
+            slices: vec![key(3, 4), key(1, 2)],
+        }]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    let slice = aseprite.get_slice_by_name("Moving").unwrap();
+    assert_eq!(slice.keys.len(), 2);
+    // 第一个 key 之前 slice 还不存在
+    assert!(slice.key_for_frame(0).is_none());
+    assert_eq!(slice.key_for_frame(1).unwrap().bounds.x, 2);
+    assert_eq!(slice.key_for_frame(2).unwrap().bounds.x, 2);
+    assert_eq!(slice.key_for_frame(3).unwrap().bounds.x, 4);
+    assert_eq!(slice.key_for_frame(10).unwrap().from_frame, 3);
+}
+
+#[test]
+fn check_nine_patch_rects() {
+    let key = |x_center: i32, y_center: i32, width: u32, height: u32| AsepriteSliceKey {
+        from_frame: 0,
+        bounds: AsepriteRect::new(10, 20, 8, 6),
+        nine_patch_info: Some(AsepriteNinePatchInfo {
+            x_center,
+            y_center,
+            width,
+            height,
+        }),
+        pivot: None,
+    };
+    let sizes = |rects: [AsepriteRect; 9]| rects.map(|rect| (rect.width, rect.height));
+
+    let rects = key(2, 1, 4, 3).nine_patch_rects().unwrap();
+    assert_eq!(rects[0], AsepriteRect::new(10, 20, 2, 1));
+    assert_eq!(rects[4], AsepriteRect::new(12, 21, 4, 3));
+    assert_eq!(rects[8], AsepriteRect::new(16, 24, 2, 2));
+
+    // 中心横跨整个 slice，左右两列为空
+    let rects = key(0, 1, 8, 3).nine_patch_rects().unwrap();
+    assert_eq!(
+        sizes(rects),
+        [
+            (0, 1),
+            (8, 1),
+            (0, 1),
+            (0, 3),
+            (8, 3),
+            (0, 3),
+            (0, 2),
+            (8, 2),
+            (0, 2)
+        ]
+    );
+
+    // 中心贴着左上角，负数的中心位置也被限制在 slice 内
+    for (x_center, y_center) in [(0, 0), (-3, -2)] {
+        let rects = key(x_center, y_center, 5, 4).nine_patch_rects().unwrap();
+        assert!(rects[0].is_empty());
+        assert_eq!(rects[4].x, 10);
+        assert_eq!(rects[4].y, 20);
+        assert_eq!(rects[8].right(), 18);
+        assert_eq!(rects[8].bottom(), 26);
+    }
+
+    // 中心超出右下角，不会溢出
+    let rects = key(6, 5, u32::MAX, u32::MAX).nine_patch_rects().unwrap();
+    assert_eq!(rects[4], AsepriteRect::new(16, 25, 2, 1));
+    assert!(rects[8].is_empty());
+
+    let plain = AsepriteSliceKey {
+        nine_patch_info: None,
+        ..key(0, 0, 1, 1)
+    };
+    assert!(plain.nine_patch_rects().is_none());
+}
+
+#[test]
+fn check_slice_image_at_frame() {
+    let buffer = std::fs::read("./tests/test_cases/complex.aseprite").unwrap();
+    let mut raw = crate::raw::read_aseprite(&buffer).unwrap();
+    let key = |frame: u32, x_origin: i32, y_origin: i32| RawAsepriteSlice {
+        frame,
+        x_origin,
+        y_origin,
+        width: 20,
+        height: 20,
+        nine_patch_info: Some(AsepriteNinePatchInfo {
+            x_center: 4,
+            y_center: 4,
+            width: 12,
+            height: 12,
+        }),
+        pivot: None,
+    };
+    raw.frames[0].chunks.push(RawAsepriteChunk::Slice {
+        flags: 1,
+        name: "Portrait".to_string(),
+        slices: vec![key(0, -4, -4), key(1, 80, 50)],
+    });
+    raw.frames[0].chunks.push(RawAsepriteChunk::Slice {
+        flags: 0,
+        name: "Later".to_string(),
+        slices: vec![key(1, 0, 0)],
+    });
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+
+    // 超出画布的部分是透明的，图像尺寸始终等于 slice 的尺寸
+    for (frame_index, (x, y)) in [(0, (-4, -4)), (1, (80, 50))] {
+        let slice_image = aseprite
+            .get_slice_image_at_frame("Portrait", frame_index)
+            .unwrap()
+            .unwrap();
+        let mut expected = image::RgbaImage::new(20, 20);
+        image::imageops::replace(
+            &mut expected,
+            &aseprite.get_frame_image(frame_index).unwrap(),
+            -x,
+            -y,
+        );
+        assert_eq!(slice_image.image, expected);
+        assert_eq!(slice_image.nine_patch_info.unwrap().width, 12);
+    }
+
+    assert!(aseprite
+        .get_slice_image_at_frame("Later", 0)
+        .unwrap()
+        .is_none());
+    assert!(aseprite
+        .get_slice_image_at_frame("Later", 1)
+        .unwrap()
+        .is_some());
+    assert!(aseprite
+        .get_slice_image_at_frame("Missing", 0)
+        .unwrap()
+        .is_none());
+    assert!(aseprite.get_slice_image_at_frame("Portrait", 2).is_err());
+}
+
+#[test]
+fn check_slice_at() {
+    let slice = |name: &str, keys: Vec<(u32, i32)>| RawAsepriteChunk::Slice {
+        flags: 0,
+        name: name.to_string(),
+        slices: keys
+            .into_iter()
+            .map(|(frame, x_origin)| RawAsepriteSlice {
+                frame,
+                x_origin,
+                y_origin: 0,
+                width: 4,
+                height: 4,
+                nine_patch_info: None,
+                pivot: None,
+            })
+            .collect(),
+    };
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        16,
+        16,
+        vec![
+            vec![
+                slice("Button", vec![(0, 0), (1, 8)]),
+                slice("Overlay", vec![(0, 2)]),
+            ],
+            Vec::new(),
+        ],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    let names = |slices: Vec<&AsepriteSlice>| -> Vec<String> {
+        slices.into_iter().map(|slice| slice.name.clone()).collect()
+    };
+
+    assert_eq!(
+        names(aseprite.slices_at(0, 3, 3)),
+        vec!["Button", "Overlay"]
+    );
+    assert_eq!(aseprite.slice_at(0, 3, 3).unwrap().name, "Overlay");
+    assert_eq!(aseprite.slice_at(0, 0, 0).unwrap().name, "Button");
+    // 右边和下边不包含在内
+    assert_eq!(aseprite.slice_at(0, 5, 0).unwrap().name, "Overlay");
+    assert!(aseprite.slice_at(0, 6, 0).is_none());
+    assert!(aseprite.slice_at(0, 0, 4).is_none());
+    assert!(aseprite.slice_at(0, -1, 0).is_none());
+    // 第二帧 Button 移动到了 x = 8
+    assert_eq!(names(aseprite.slices_at(1, 3, 3)), vec!["Overlay"]);
+    assert_eq!(aseprite.slice_at(1, 8, 0).unwrap().name, "Button");
+}
+
+#[test]
+fn check_nine_patch_render() {
+    // 6x6 的图，中心是 (2, 2) 开始的 2x2，每个部分颜色不同，中心两列颜色不同
+    let image = image::RgbaImage::from_fn(6, 6, |x, y| {
+        let part = |v: u32| {
+            if v < 2 {
+                0
+            } else if v < 4 {
+                1
+            } else {
+                2
+            }
+        };
+        let center_column = if part(x) == 1 && part(y) == 1 {
+            x as u8
+        } else {
+            0
+        };
+        image::Rgba([part(x) as u8, part(y) as u8, center_column, 255])
+    });
+    let slice_image = AsepriteSliceImage {
+        image,
+        nine_patch_info: Some(AsepriteNinePatchInfo {
+            x_center: 2,
+            y_center: 2,
+            width: 2,
+            height: 2,
+        }),
+        pivot: None,
+    };
+
+    let stretched = slice_image.render(10, 8, NinePatchMode::Stretch);
+    assert_eq!(stretched.dimensions(), (10, 8));
+    // 角落保持原尺寸
+    assert_eq!(stretched.get_pixel(1, 1), &image::Rgba([0, 0, 0, 255]));
+    assert_eq!(stretched.get_pixel(8, 6), &image::Rgba([2, 2, 0, 255]));
+    assert_eq!(stretched.get_pixel(5, 0), &image::Rgba([1, 0, 0, 255]));
+    // 拉伸时中心的第一列占据前一半
+    let center_row: Vec<u8> = (2..8).map(|x| stretched.get_pixel(x, 3)[2]).collect();
+    assert_eq!(center_row, vec![2, 2, 2, 3, 3, 3]);
+
+    let tiled = slice_image.render(10, 8, NinePatchMode::Tile);
+    let center_row: Vec<u8> = (2..8).map(|x| tiled.get_pixel(x, 3)[2]).collect();
+    assert_eq!(center_row, vec![2, 3, 2, 3, 2, 3]);
+    assert_eq!(tiled.get_pixel(8, 6), &image::Rgba([2, 2, 0, 255]));
+
+    // 原尺寸渲染得到原图
+    assert_eq!(
+        slice_image.render(6, 6, NinePatchMode::Tile),
+        slice_image.image
+    );
+
+    // 目标比角落还小时，角落按比例缩小，不会 panic
+    let small = slice_image.render(3, 2, NinePatchMode::Stretch);
+    assert_eq!(small.dimensions(), (3, 2));
+    assert_eq!(small.get_pixel(0, 0), &image::Rgba([0, 0, 0, 255]));
+    assert_eq!(small.get_pixel(2, 1), &image::Rgba([2, 2, 0, 255]));
+    assert_eq!(
+        slice_image.render(0, 0, NinePatchMode::Tile).dimensions(),
+        (0, 0)
+    );
+
+    // 没有 9-patch 时整张图当作中心
+    let plain = AsepriteSliceImage {
+        nine_patch_info: None,
+        ..slice_image.clone()
+    };
+    assert_eq!(
+        plain
+            .render(12, 12, NinePatchMode::Stretch)
+            .get_pixel(11, 11),
+        &image::Rgba([2, 2, 0, 255])
+    );
+}
+
+#[test]
+fn check_palette_export() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/simple.aseprite").unwrap();
+    let mut palette = aseprite.palette().unwrap().clone();
+    palette.entries[3].alpha = 0;
+
+    // 把生成的 gpl 解析回来，和原来的颜色比较
+    let gpl = palette.to_gpl("Simple");
+    let mut lines = gpl.lines();
+    assert_eq!(lines.next(), Some("GIMP Palette"));
+    assert_eq!(lines.next(), Some("Name: Simple"));
+    let mut transparent = Vec::new();
+    let mut parsed = Vec::new();
+    for line in lines {
+        if line == "# transparent" {
+            transparent.push(parsed.len());
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+        let rgb: Vec<u8> = line
+            .split_whitespace()
+            .take(3)
+            .map(|value| value.parse().unwrap())
+            .collect();
+        parsed.push(rgb);
+    }
+    let expected: Vec<Vec<u8>> = palette
+        .entries
+        .iter()
+        .map(|color| vec![color.red, color.green, color.blue])
+        .collect();
+    assert_eq!(parsed, expected);
+    assert_eq!(transparent, vec![3]);
+
+    let act = palette.to_act();
+    assert_eq!(act.len(), 256 * 3 + 4);
+    assert_eq!(act[..3 * 32].chunks(3).collect::<Vec<_>>(), expected);
+    assert!(act[3 * 32..256 * 3].iter().all(|&value| value == 0));
+    assert_eq!(&act[256 * 3..], &[0, 32, 0, 3]);
+    assert_eq!(
+        &AsepritePalette::default().to_act()[256 * 3..],
+        &[0, 0, 0xFF, 0xFF]
+    );
+
+    let image = palette.to_image();
+    assert_eq!(image.dimensions(), (32, 1));
+    assert_eq!(image.get_pixel(3, 0)[3], 0);
+    assert_eq!(image.get_pixel(31, 0), &image::Rgba([138, 111, 48, 255]));
+}
+
+#[test]
+fn check_palette_quantize() {
+    let color = |red: u8, green: u8, blue: u8, alpha: u8| AsepriteColor {
+        red,
+        green,
+        blue,
+        alpha,
+    };
+    let palette = AsepritePalette {
+        entries: vec![
+            color(0, 0, 0, 255),
+            color(255, 0, 0, 255),
+            color(0, 0, 0, 0),
+            color(255, 0, 0, 255),
+            color(0, 0, 255, 255),
+        ],
+    };
+    assert_eq!(palette.nearest(color(250, 10, 10, 255)), 1);
+    assert_eq!(palette.nearest(color(10, 10, 10, 255)), 0);
+    // 距离相同时取较小的 index
+    assert_eq!(palette.nearest(color(255, 0, 0, 255)), 1);
+    assert_eq!(palette.nearest(color(127, 0, 0, 255)), 0);
+    assert_eq!(AsepritePalette::default().nearest(color(1, 2, 3, 4)), 0);
+
+    let image = image::RgbaImage::from_fn(2, 2, |x, y| match (x, y) {
+        (0, 0) => image::Rgba([255, 0, 0, 255]),
+        (1, 0) => image::Rgba([0, 0, 200, 255]),
+        (0, 1) => image::Rgba([255, 255, 255, 0]),
+        _ => image::Rgba([20, 0, 0, 255]),
+    });
+    assert_eq!(palette.quantize_image(&image), vec![1, 4, 2, 0]);
+
+    // 没有透明颜色时，透明像素使用 index 0
+    let opaque = AsepritePalette {
+        entries: vec![color(255, 255, 255, 255), color(0, 0, 0, 255)],
+    };
+    assert_eq!(opaque.quantize_image(&image), vec![1, 1, 0, 1]);
+
+    // 不透明的像素不会映射到透明色，即使透明色的距离更近
+    let gif_like = AsepritePalette {
+        entries: vec![
+            color(0, 0, 0, 0),
+            color(255, 0, 0, 255),
+            color(0, 255, 0, 255),
+        ],
+    };
+    let black = image::RgbaImage::from_pixel(1, 1, image::Rgba([0, 0, 0, 255]));
+    assert_eq!(gif_like.quantize_image(&black), vec![1]);
+    let only_transparent = AsepritePalette {
+        entries: vec![color(0, 0, 0, 0)],
+    };
+    assert_eq!(only_transparent.quantize_image(&black), vec![0]);
+}
+
+#[test]
+fn check_incremental_palette() {
+    let palette =
+        |palette_size: u32, from_color: u32, colors: &[[u8; 4]]| RawAsepriteChunk::Palette {
+            palette_size,
+            from_color,
+            to_color: from_color + colors.len() as u32 - 1,
+            entries: colors
+                .iter()
+                .map(|&[red, green, blue, alpha]| RawAsepritePaletteEntry {
+                    color: AsepriteColor {
+                        red,
+                        green,
+                        blue,
+                        alpha,
+                    },
+                    name: None,
+                })
+                .collect(),
+        };
+    let color = |value: u8| [value, value, value, 255];
+    let raw = mock_raw(
+        AsepriteColorDepth::Indexed,
+        1,
+        1,
+        vec![
+            vec![palette(32, 0, &(0..32).map(color).collect::<Vec<_>>())],
+            // 后面的帧只更新中间的一段
+            vec![palette(32, 10, &[color(100); 11])],
+            // palette 变大时，只有新增的部分写在 chunk 里
+            vec![palette(34, 32, &[color(200); 2])],
+        ],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    let palette = aseprite.palette().unwrap();
+    assert_eq!(palette.len(), 34);
+    let colors: Vec<u8> = palette.entries.iter().map(|color| color.red).collect();
+    let mut expected: Vec<u8> = (0..32).collect();
+    expected[10..=20].fill(100);
+    expected.extend([200, 200]);
+    assert_eq!(colors, expected);
+}
+
+#[test]
+#[allow(deprecated)]
+fn check_frame_bounds() {
+    let aseprite =
+        Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+    assert!(aseprite.get_frame(4).is_none());
+    let frame = aseprite.get_frame(3).unwrap();
+    assert_eq!(frame.get_infos().delay_ms, 100);
+}
+
+#[test]
+fn check_malformed_layer_hierarchy() {
+    // 第一个 layer 的 child level 就大于 0，并且 normal layer 下面跟着更深的 layer
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        1,
+        1,
+        vec![vec![
+            mock_layer_chunk("Orphan", 2),
+            mock_group_chunk("Group", 0),
+            mock_layer_chunk("Child", 1),
+            mock_layer_chunk("Grandchild", 2),
+        ]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    assert!(aseprite.find_layer_belong_groups(0).is_empty());
+    assert_eq!(aseprite.find_layer_belong_groups(2), vec![1]);
+    assert_eq!(aseprite.find_layer_belong_groups(3), vec![1]);
+
+    let tree = aseprite.layer_tree();
+    assert_eq!(tree.keys().copied().collect::<Vec<_>>(), vec![0, 1]);
+    let children: Vec<usize> = tree[&1].children().map(|child| child.index()).collect();
+    assert_eq!(children, vec![2, 3]);
+}
+
+/// 测试用的 GIF LZW 解码
+#[cfg(feature = "gif")]
+fn decode_gif_lzw(data: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1usize << min_code_size;
+    let reset = || -> Vec<Vec<u8>> {
+        (0..clear + 2)
+            .map(|code| vec![code as u8])
+            .collect::<Vec<_>>()
+    };
+    let mut table = reset();
+    let mut width = min_code_size as usize + 1;
+    let mut bit = 0;
+    let mut previous: Option<Vec<u8>> = None;
+    let mut output = Vec::new();
+    while bit + width <= data.len() * 8 {
+        let code = (0..width).fold(0, |code, offset| {
+            let position = bit + offset;
+            code | ((data[position / 8] as usize >> (position % 8)) & 1) << offset
+        });
+        bit += width;
+        if code == clear {
+            table = reset();
+            width = min_code_size as usize + 1;
+            previous = None;
+            continue;
+        }
+        if code == clear + 1 {
+            break;
+        }
+        let entry = match (table.get(code), &previous) {
+            (Some(entry), _) => entry.clone(),
+            (None, Some(previous)) => {
+                let mut entry = previous.clone();
+                entry.push(previous[0]);
+                entry
+            }
+            (None, None) => unreachable!(),
+        };
+        if let Some(mut previous) = previous {
+            previous.push(entry[0]);
+            table.push(previous);
+        }
+        if table.len() == 1 << width && width < 12 {
+            width += 1;
+        }
+        output.extend_from_slice(&entry);
+        previous = Some(entry);
+    }
+    output
+}
+
+#[cfg(feature = "gif")]
+#[test]
+fn check_gif_lzw() {
+    // 足够长的数据，会用完 4096 个 code 并清空字典
+    let mut seed = 7u32;
+    let noise: Vec<u8> = (0..40_000)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            ((seed >> 16) % 16) as u8
+        })
+        .collect();
+    let runs: Vec<u8> = (0..20_000).map(|index| (index / 300 % 4) as u8).collect();
+    for (indices, min_code_size) in [(noise, 4), (runs, 2), (vec![3], 2), (Vec::new(), 2)] {
+        let encoded = super::gif::lzw_encode(&indices, min_code_size);
+        assert_eq!(decode_gif_lzw(&encoded, min_code_size), indices);
+    }
+
+    // 不依赖上面的解码器：sample_1.gif 的图片数据，来自
+    // https://www.matthewflickinger.com/lab/whatsinagif/lzw_image_data.asp
+    let rows: [&[u8]; 10] = [
+        &[1, 1, 1, 1, 1, 2, 2, 2, 2, 2],
+        &[1, 1, 1, 1, 1, 2, 2, 2, 2, 2],
+        &[1, 1, 1, 1, 1, 2, 2, 2, 2, 2],
+        &[1, 1, 1, 0, 0, 0, 0, 2, 2, 2],
+        &[1, 1, 1, 0, 0, 0, 0, 2, 2, 2],
+        &[2, 2, 2, 0, 0, 0, 0, 1, 1, 1],
+        &[2, 2, 2, 0, 0, 0, 0, 1, 1, 1],
+        &[2, 2, 2, 2, 2, 1, 1, 1, 1, 1],
+        &[2, 2, 2, 2, 2, 1, 1, 1, 1, 1],
+        &[2, 2, 2, 2, 2, 1, 1, 1, 1, 1],
+    ];
+    assert_eq!(
+        super::gif::lzw_encode(&rows.concat(), 2),
+        vec![
+            0x8C, 0x2D, 0x99, 0x87, 0x2A, 0x1C, 0xDC, 0x33, 0xA0, 0x02, 0x75, 0xEC, 0x95, 0xFA,
+            0xA8, 0xDE, 0x60, 0x8C, 0x04, 0x91, 0x4C, 0x01,
+        ]
+    );
+}
+
+#[cfg(feature = "gif")]
+#[test]
+fn check_export_gif() {
+    let buffer = std::fs::read("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+    let mut raw = crate::raw::read_aseprite(&buffer).unwrap();
+    raw.frames[1].duration_ms = 33;
+    raw.frames[2].duration_ms = 25;
+    raw.frames[0].chunks.push(RawAsepriteChunk::Tags {
+        tags: vec![RawAsepriteTag {
+            from: 0,
+            to: 3,
+            anim_direction: AsepriteAnimationDirection::PingPong,
+            repeat: 3,
+            name: "Loop".to_string(),
+        }],
+    });
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    let tag = aseprite.tags().find(|tag| tag.name == "Loop").unwrap();
+    let mut gif = Vec::new();
+    aseprite.export_gif(tag, &mut gif).unwrap();
+
+    assert_eq!(&gif[..6], b"GIF89a");
+    assert_eq!(&gif[6..10], &[32, 0, 32, 0]);
+    let table_size = 3 << ((gif[10] & 0x7) + 1);
+    let table = &gif[13..13 + table_size];
+    let mut position = 13 + table_size;
+    let sub_blocks = |position: &mut usize| {
+        let mut blocks = Vec::new();
+        while gif[*position] != 0 {
+            let len = gif[*position] as usize;
+            blocks.push(&gif[*position + 1..*position + 1 + len]);
+            *position += len + 1;
+        }
+        *position += 1;
+        blocks
+    };
+    let mut loops = None;
+    let mut delays = Vec::new();
+    let mut frames = Vec::new();
+    loop {
+        match gif[position] {
+            0x21 => {
+                let label = gif[position + 1];
+                position += 2;
+                let blocks = sub_blocks(&mut position);
+                match label {
+                    0xFF if blocks[0] == b"NETSCAPE2.0" => {
+                        loops = Some(u16::from_le_bytes([blocks[1][1], blocks[1][2]]));
+                    }
+                    0xF9 => {
+                        // 透明色是 index 0
+                        assert_eq!(blocks[0][0] & 1, 1);
+                        assert_eq!(blocks[0][3], 0);
+                        delays.push(u16::from_le_bytes([blocks[0][1], blocks[0][2]]));
+                    }
+                    _ => {}
+                }
+            }
+            0x2C => {
+                position += 10;
+                let min_code_size = gif[position];
+                position += 1;
+                let data: Vec<u8> = sub_blocks(&mut position).concat();
+                frames.push(decode_gif_lzw(&data, min_code_size));
+            }
+            0x3B => break,
+            other => unreachable!("unexpected block {:#x}", other),
+        }
+    }
+
+    // 播放 3 次，NETSCAPE 扩展里记录的是额外循环的次数
+    assert_eq!(loops, Some(2));
+    // ping-pong：0 1 2 3 2 1，毫秒四舍五入成百分之一秒
+    // 0 和 1 的内容相同，合并成一帧，延迟相加
+    assert_eq!(
+        aseprite.frame_content_hash(0).unwrap(),
+        aseprite.frame_content_hash(1).unwrap()
+    );
+    assert_eq!(delays, vec![13, 3, 10, 3, 3]);
+    assert_eq!(frames.len(), 5);
+    for (indices, frame_index) in frames.iter().zip([0, 2, 3, 2, 1]) {
+        let expected = aseprite.get_frame_image(frame_index).unwrap();
+        assert_eq!(indices.len(), 32 * 32);
+        for (&index, pixel) in indices.iter().zip(expected.pixels()) {
+            let index = index as usize;
+            if pixel[3] < 128 {
+                assert_eq!(index, 0);
+            } else {
+                assert_eq!(&table[index * 3..index * 3 + 3], &pixel.0[..3]);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "apng")]
+#[test]
+fn check_export_apng() {
+    let buffer = std::fs::read("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+    let mut raw = crate::raw::read_aseprite(&buffer).unwrap();
+    raw.frames[1].duration_ms = 33;
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    let mut apng = Vec::new();
+    aseprite.export_apng([0, 1, 2, 1], &mut apng).unwrap();
+
+    let be_u32 = |bytes: &[u8]| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    // 读出 acTL 和 fcTL chunk
+    let mut position = 8;
+    let mut actl = None;
+    let mut delays = Vec::new();
+    while position < apng.len() {
+        let len = be_u32(&apng[position..]) as usize;
+        let kind = &apng[position + 4..position + 8];
+        let data = &apng[position + 8..position + 8 + len];
+        match kind {
+            b"acTL" => {
+                actl = Some((be_u32(&data[0..]), be_u32(&data[4..])));
+            }
+            b"fcTL" => {
+                assert_eq!(&data[4..12], &[0, 0, 0, 32, 0, 0, 0, 32]);
+                delays.push((
+                    u16::from_be_bytes([data[20], data[21]]),
+                    u16::from_be_bytes([data[22], data[23]]),
+                ));
+            }
+            _ => {}
+        }
+        position += len + 12;
+    }
+    assert_eq!(actl, Some((4, 0)));
+    assert_eq!(
+        delays,
+        vec![(100, 1000), (33, 1000), (100, 1000), (33, 1000)]
+    );
+
+    // 解码后每一帧和合成的图片一致
+    let mut reader = png::Decoder::new(std::io::Cursor::new(&apng))
+        .read_info()
+        .unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+    for frame_index in [0, 1, 2, 1] {
+        reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(
+            pixels,
+            aseprite.get_frame_image(frame_index).unwrap().into_raw()
+        );
+    }
+}
+
+#[test]
+fn check_grid_spritesheet() {
+    // 5 帧 4x3 的图，每帧只有一个颜色不同的像素铺满
+    let frames = (0..5u8)
+        .map(|frame| {
+            vec![
+                mock_layer_chunk("Layer", 0),
+                mock_cel_chunk(0, 0, 0, 4, 3, mock_rgba_pixels([frame, 0, 0, 255], 12)),
+            ]
+        })
+        .collect();
+    let mut raw = mock_raw(AsepriteColorDepth::RGBA, 4, 3, frames);
+    raw.frames[2].duration_ms = 50;
+    raw.frames[0].chunks.push(RawAsepriteChunk::Tags {
+        tags: vec![RawAsepriteTag {
+            from: 1,
+            to: 3,
+            anim_direction: AsepriteAnimationDirection::Forward,
+            repeat: 0,
+            name: "Middle".to_string(),
+        }],
+    });
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+
+    let options = SpritesheetOptions {
+        columns: Some(3),
+        padding: 2,
+        ..SpritesheetOptions::default()
+    };
+    let (sheet, meta) = aseprite.to_spritesheet(&options).unwrap();
+    assert_eq!(sheet.dimensions(), (16, 8));
+    assert_eq!(meta.size, (16, 8));
+    let rects: Vec<AsepriteRect> = meta.frames.iter().map(|frame| frame.rect).collect();
+    assert_eq!(
+        rects,
+        vec![
+            AsepriteRect::new(0, 0, 4, 3),
+            AsepriteRect::new(6, 0, 4, 3),
+            AsepriteRect::new(12, 0, 4, 3),
+            AsepriteRect::new(0, 5, 4, 3),
+            AsepriteRect::new(6, 5, 4, 3),
+        ]
+    );
+    assert_eq!(meta.frames[4].frame_index, 4);
+    assert_eq!(meta.frames[2].duration, Duration::from_millis(50));
+    assert_eq!(sheet.get_pixel(9, 7).0, [4, 0, 0, 255]);
+    // padding 和空的格子是透明的
+    assert_eq!(sheet.get_pixel(4, 0).0, [0; 4]);
+    assert_eq!(sheet.get_pixel(12, 5).0, [0; 4]);
+
+    // 按列排列：3 列时需要 2 行
+    let (_, meta) = aseprite
+        .to_spritesheet(&SpritesheetOptions {
+            order: SpritesheetOrder::ColumnMajor,
+            ..options.clone()
+        })
+        .unwrap();
+    let origins: Vec<(i32, i32)> = meta
+        .frames
+        .iter()
+        .map(|frame| (frame.rect.x, frame.rect.y))
+        .collect();
+    assert_eq!(origins, vec![(0, 0), (0, 5), (6, 0), (6, 5), (12, 0)]);
+
+    // 边缘像素向外重复，padding 加在重复的像素之外
+    let (sheet, meta) = aseprite
+        .to_spritesheet(&SpritesheetOptions {
+            tag: Some("Middle".to_string()),
+            columns: None,
+            extrude: 1,
+            ..options.clone()
+        })
+        .unwrap();
+    assert_eq!(sheet.dimensions(), (3 * 6 + 2 * 2, 5));
+    let indices: Vec<usize> = meta.frames.iter().map(|frame| frame.frame_index).collect();
+    assert_eq!(indices, vec![1, 2, 3]);
+    assert_eq!(meta.frames[1].rect, AsepriteRect::new(9, 1, 4, 3));
+    assert_eq!(sheet.get_pixel(8, 0).0, [2, 0, 0, 255]);
+    assert_eq!(sheet.get_pixel(13, 4).0, [2, 0, 0, 255]);
+    assert_eq!(sheet.get_pixel(14, 4).0, [0; 4]);
+
+    assert!(matches!(
+        aseprite.to_spritesheet(&SpritesheetOptions {
+            tag: Some("Missing".to_string()),
+            ..SpritesheetOptions::default()
+        }),
+        Err(AsepriteError::InvalidConfiguration(
+            AsepriteInvalidError::InvalidTagName(_)
+        ))
+    ));
+}
+
+#[test]
+fn check_packed_spritesheet() {
+    let frame = |x: i16, y: i16, width: u16, height: u16, color: u8| {
+        let pixels = mock_rgba_pixels([color, 0, 0, 255], width as usize * height as usize);
+        vec![
+            mock_layer_chunk("Layer", 0),
+            mock_cel_chunk(0, x, y, width, height, pixels),
+        ]
+    };
+    let frames = vec![
+        frame(1, 1, 3, 2, 10),
+        // 和第一帧内容相同，只是位置不同
+        frame(4, 4, 3, 2, 10),
+        frame(0, 0, 5, 4, 20),
+        frame(2, 2, 1, 6, 30),
+        // 完全透明的帧
+        vec![mock_layer_chunk("Layer", 0)],
+    ];
+    let aseprite = Aseprite::from_raw(mock_raw(AsepriteColorDepth::RGBA, 8, 8, frames)).unwrap();
+    let options = SpritesheetOptions {
+        packing: SpritesheetPacking::Packed {
+            power_of_two: false,
+        },
+        padding: 1,
+        extrude: 1,
+        ..SpritesheetOptions::default()
+    };
+    let (sheet, meta) = aseprite.to_spritesheet(&options).unwrap();
+    assert_eq!(meta.size, sheet.dimensions());
+    assert_eq!(meta.source_size, (8, 8));
+
+    // 相同的帧共用一个区域，各自保留裁剪的偏移
+    assert_eq!(meta.frames[0].rect, meta.frames[1].rect);
+    assert_eq!(meta.frames[0].offset, (1, 1));
+    assert_eq!(meta.frames[1].offset, (4, 4));
+    assert_eq!(meta.frames[2].offset, (0, 0));
+    assert!(meta.frames[4].rect.is_empty());
+
+    let rects: Vec<AsepriteRect> = [0, 2, 3].iter().map(|&i| meta.frames[i].rect).collect();
+    let sizes: Vec<(u32, u32)> = rects.iter().map(|rect| (rect.width, rect.height)).collect();
+    assert_eq!(sizes, vec![(3, 2), (5, 4), (1, 6)]);
+    let sheet_rect = AsepriteRect::new(0, 0, meta.size.0, meta.size.1);
+    for (index, rect) in rects.iter().enumerate() {
+        // 加上重复的边缘和 padding 后也不会重叠，并且都在图片内
+        let outer = AsepriteRect::new(rect.x - 1, rect.y - 1, rect.width + 2, rect.height + 2);
+        assert_eq!(outer.intersection(&sheet_rect), Some(outer));
+        for other in &rects[index + 1..] {
+            let other =
+                AsepriteRect::new(other.x - 2, other.y - 2, other.width + 4, other.height + 4);
+            assert!(!outer.intersects(&other));
+        }
+    }
+    for (index, color) in [(0, 10), (2, 20), (3, 30)] {
+        let rect = meta.frames[index].rect;
+        for y in rect.y - 1..rect.bottom() + 1 {
+            for x in rect.x - 1..rect.right() + 1 {
+                assert_eq!(sheet.get_pixel(x as u32, y as u32).0, [color, 0, 0, 255]);
+            }
+        }
+    }
+
+    // 结果是确定的
+    assert_eq!(aseprite.to_spritesheet(&options).unwrap(), (sheet, meta));
+
+    let (sheet, _) = aseprite
+        .to_spritesheet(&SpritesheetOptions {
+            packing: SpritesheetPacking::Packed { power_of_two: true },
+            ..options
+        })
+        .unwrap();
+    assert!(sheet.width().is_power_of_two());
+    assert!(sheet.height().is_power_of_two());
+}
+
+#[test]
+fn check_export_json() {
+    let frames = (0..2u8)
+        .map(|frame| {
+            vec![mock_cel_chunk(
+                1,
+                0,
+                0,
+                2,
+                2,
+                mock_rgba_pixels([frame, 0, 0, 255], 4),
+            )]
+        })
+        .collect();
+    let mut raw = mock_raw(AsepriteColorDepth::RGBA, 2, 2, frames);
+    raw.frames[1].duration_ms = 50;
+    raw.frames[0].chunks.splice(
+        0..0,
+        [
+            mock_group_chunk("Group", 0),
+            mock_layer_chunk("Body \"main\"", 1),
+            RawAsepriteChunk::Tags {
+                tags: vec![RawAsepriteTag {
+                    from: 0,
+                    to: 1,
+                    anim_direction: AsepriteAnimationDirection::PingPong,
+                    repeat: 2,
+                    name: "Walk".to_string(),
+                }],
+            },
+            RawAsepriteChunk::Slice {
+                flags: 3,
+                name: "Hit".to_string(),
+                slices: vec![RawAsepriteSlice {
+                    frame: 0,
+                    x_origin: 0,
+                    y_origin: 1,
+                    width: 2,
+                    height: 1,
+                    nine_patch_info: Some(AsepriteNinePatchInfo {
+                        x_center: 1,
+                        y_center: 0,
+                        width: 1,
+                        height: 1,
+                    }),
+                    pivot: Some(AsepritePivot {
+                        x_pivot: 1,
+                        y_pivot: 0,
+                    }),
+                }],
+            },
+        ],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    let (_, sheet) = aseprite
+        .to_spritesheet(&SpritesheetOptions::default())
+        .unwrap();
+
+    let json = aseprite.export_json(&sheet, &JsonExportOptions::default());
+    let frame = |x: u32, duration: u32| {
+        format!(
+            concat!(
+                r#"{{"frame":{{"x":{},"y":0,"w":2,"h":2}},"rotated":false,"trimmed":false,"#,
+                r#""spriteSourceSize":{{"x":0,"y":0,"w":2,"h":2}},"sourceSize":{{"w":2,"h":2}},"#,
+                r#""duration":{},"anchor":{{"x":1,"y":1}}}}"#
+            ),
+            x, duration
+        )
+    };
+    let meta = concat!(
+        r#""meta":{"app":"https://www.aseprite.org/","version":"1.3","image":"sheet.png","#,
+        r#""format":"RGBA8888","size":{"w":4,"h":2},"scale":"1","#,
+        r#""frameTags":[{"name":"Walk","from":0,"to":1,"direction":"pingpong","#,
+        r##""color":"#00000000","repeat":"2"}],"##,
+        r#""layers":[{"name":"Group"},"#,
+        r#"{"name":"Body \"main\"","group":"Group","opacity":255,"blendMode":"normal"}],"#,
+        r##""slices":[{"name":"Hit","color":"#00000000","keys":[{"frame":0,"##,
+        r#""bounds":{"x":0,"y":1,"w":2,"h":1},"center":{"x":1,"y":0,"w":1,"h":1},"#,
+        r#""pivot":{"x":1,"y":0}}]}]}"#,
+    );
+    assert_eq!(
+        json,
+        format!(
+            r#"{{"frames":{{"0":{},"1":{}}},{}}}"#,
+            frame(0, 100),
+            frame(2, 50),
+            meta
+        )
+    );
+
+    let json = aseprite.export_json(
+        &sheet,
+        &JsonExportOptions {
+            frame_name: "walk_{frame}.png".to_string(),
+            format: JsonFrameFormat::Array,
+            ..JsonExportOptions::default()
+        },
+    );
+    assert!(json.starts_with(r#"{"frames":[{"filename":"walk_0.png","frame":{"x":0,"#));
+    assert!(json.contains(r#"},{"filename":"walk_1.png","frame":{"x":2,"#));
+
+    // 没有 {frame} 时 key 也不重复
+    let json = aseprite.export_json(
+        &sheet,
+        &JsonExportOptions {
+            frame_name: "walk".to_string(),
+            ..JsonExportOptions::default()
+        },
+    );
+    assert!(json.starts_with(r#"{"frames":{"walk":{"frame":{"x":0,"#));
+    assert!(json.contains(r#"},"walk 1":{"frame":{"x":2,"#));
+}
+
+#[test]
+fn check_export_texture_packer_json() {
+    let key = |frame: u32, pivot: Option<AsepritePivot>| RawAsepriteSlice {
+        frame,
+        x_origin: 0,
+        y_origin: 0,
+        width: 4,
+        height: 2,
+        nine_patch_info: None,
+        pivot,
+    };
+    let frames = vec![
+        vec![
+            mock_layer_chunk("Layer", 0),
+            mock_cel_chunk(0, 1, 1, 2, 2, mock_rgba_pixels([255, 0, 0, 255], 4)),
+            RawAsepriteChunk::Slice {
+                flags: 2,
+                name: "pivot".to_string(),
+                slices: vec![
+                    key(0, None),
+                    key(
+                        1,
+                        Some(AsepritePivot {
+                            x_pivot: 1,
+                            y_pivot: 3,
+                        }),
+                    ),
+                ],
+            },
+        ],
+        vec![mock_cel_chunk(
+            0,
+            0,
+            0,
+            4,
+            4,
+            mock_rgba_pixels([0, 255, 0, 255], 16),
+        )],
+    ];
+    let aseprite = Aseprite::from_raw(mock_raw(AsepriteColorDepth::RGBA, 4, 4, frames)).unwrap();
+    let (_, sheet) = aseprite
+        .to_spritesheet(&SpritesheetOptions {
+            packing: SpritesheetPacking::Packed {
+                power_of_two: false,
+            },
+            ..SpritesheetOptions::default()
+        })
+        .unwrap();
+    let json = aseprite.export_texture_packer_json(&sheet, &JsonExportOptions::default());
+
+    // 第一帧被裁剪，pivot 是 slice 的中心；第二帧使用 slice 的 pivot
+    let rect = sheet.frames[0].rect;
+    let first = format!(
+        concat!(
+            r#""0":{{"frame":{{"x":{},"y":{},"w":2,"h":2}},"rotated":false,"trimmed":true,"#,
+            r#""spriteSourceSize":{{"x":1,"y":1,"w":2,"h":2}},"sourceSize":{{"w":4,"h":4}},"#,
+            r#""pivot":{{"x":0.5,"y":0.25}}}}"#
+        ),
+        rect.x, rect.y
+    );
+    assert!(json.contains(&first), "{}", json);
+    assert!(json.contains(r#""trimmed":false,"spriteSourceSize":{"x":0,"y":0,"w":4,"h":4}"#));
+    assert!(json.contains(r#""pivot":{"x":0.25,"y":0.75}}"#));
+    assert!(json.contains(r#""meta":{"app":"https://www.codeandweb.com/texturepacker""#));
+
+    // 没有 pivot slice 时使用画布中心
+    let aseprite =
+        Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+    let (_, sheet) = aseprite
+        .to_spritesheet(&SpritesheetOptions::default())
+        .unwrap();
+    let json = aseprite.export_texture_packer_json(&sheet, &JsonExportOptions::default());
+    assert_eq!(json.matches(r#""pivot":{"x":0.5,"y":0.5}"#).count(), 4);
+}
+
+#[test]
+fn check_image_frames() {
+    let buffer = std::fs::read("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+    let mut raw = crate::raw::read_aseprite(&buffer).unwrap();
+    raw.frames[2].duration_ms = 30;
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+
+    let frames = aseprite
+        .to_image_frames(None)
+        .unwrap()
+        .collect_frames()
+        .unwrap();
+    assert_eq!(frames.len(), 4);
+    for (frame_index, frame) in frames.iter().enumerate() {
+        assert_eq!(
+            frame.buffer(),
+            &aseprite.get_frame_image(frame_index).unwrap()
+        );
+        assert_eq!((frame.left(), frame.top()), (0, 0));
+    }
+    assert_eq!(Duration::from(frames[2].delay()), Duration::from_millis(30));
+
+    let frames = aseprite
+        .to_image_frames(Some(1..3))
+        .unwrap()
+        .collect_frames()
+        .unwrap();
+    let delays: Vec<Duration> = frames.iter().map(|frame| frame.delay().into()).collect();
+    assert_eq!(
+        delays,
+        vec![Duration::from_millis(100), Duration::from_millis(30)]
+    );
+    assert!(aseprite.to_image_frames(Some(3..5)).is_err());
+}
+
+#[test]
+fn check_export_strip() {
+    let aseprite =
+        Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+    let mut tag = AsepriteTag {
+        index: 0,
+        frames: 1..4,
+        animation_direction: AsepriteAnimationDirection::PingPong,
+        repeat: 0,
+        name: "Bounce".to_string(),
+        color: AsepriteColor::default(),
+        user_data: String::new(),
+        properties: Vec::new(),
+    };
+
+    let strip = aseprite
+        .export_strip(&tag, StripOrientation::Horizontal)
+        .unwrap();
+    assert_eq!(strip.frames, vec![1, 2, 3, 2]);
+    assert_eq!(strip.cell_size, (32, 32));
+    assert_eq!(strip.image.dimensions(), (128, 32));
+    for (position, &frame_index) in strip.frames.iter().enumerate() {
+        let cell = image::imageops::crop_imm(&strip.image, position as u32 * 32, 0, 32, 32);
+        assert_eq!(
+            cell.to_image(),
+            aseprite.get_frame_image(frame_index).unwrap()
+        );
+    }
+
+    let strip = aseprite
+        .export_strip(&tag, StripOrientation::Vertical)
+        .unwrap();
+    assert_eq!(strip.image.dimensions(), (32, 128));
+    let cell = image::imageops::crop_imm(&strip.image, 0, 96, 32, 32);
+    assert_eq!(cell.to_image(), aseprite.get_frame_image(2).unwrap());
+
+    tag.frames = 2..2;
+    assert!(matches!(
+        aseprite.export_strip(&tag, StripOrientation::Horizontal),
+        Err(AsepriteError::InvalidConfiguration(
+            AsepriteInvalidError::EmptyTag(_)
+        ))
+    ));
+}
+
+#[test]
+fn check_duplicate_frames() {
+    let red = [255, 0, 0, 255];
+    let linked_cel = |x, frame_position| RawAsepriteChunk::Cel {
+        layer_index: 0,
+        x,
+        y: 0,
+        opacity: 255,
+        z_index: 0,
+        cel: RawAsepriteCel::Linked { frame_position },
+    };
+    // 0 和 1 链接到同一个 cel，2 的像素和 0 相同，3 是别的颜色，4 链接到 0 但位置不同
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        2,
+        1,
+        vec![
+            vec![
+                mock_layer_chunk("Layer", 0),
+                mock_cel_chunk(0, 0, 0, 1, 1, mock_rgba_pixels(red, 1)),
+            ],
+            vec![linked_cel(0, 0)],
+            vec![mock_cel_chunk(0, 0, 0, 1, 1, mock_rgba_pixels(red, 1))],
+            vec![mock_cel_chunk(
+                0,
+                0,
+                0,
+                1,
+                1,
+                mock_rgba_pixels([0, 0, 255, 255], 1),
+            )],
+            vec![linked_cel(1, 0)],
+        ],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    let colliding = aseprite.clone();
+    assert_eq!(
+        aseprite.duplicate_frames().unwrap(),
+        vec![vec![0, 1, 2], vec![3], vec![4]]
+    );
+    let hash = aseprite.frame_content_hash(0).unwrap();
+    assert_eq!(aseprite.frame_content_hash(1).unwrap(), hash);
+    assert_eq!(aseprite.frame_content_hash(2).unwrap(), hash);
+    assert_ne!(aseprite.frame_content_hash(3).unwrap(), hash);
+    assert!(aseprite.frame_content_hash(5).is_err());
+
+    // 合并相同的帧后只需要 3 个格子，相同的帧共用同一个位置
+    let (sheet, meta) = aseprite
+        .to_spritesheet(&SpritesheetOptions {
+            merge_duplicates: true,
+            ..SpritesheetOptions::default()
+        })
+        .unwrap();
+    assert_eq!(sheet.dimensions(), (6, 1));
+    let origins: Vec<i32> = meta.frames.iter().map(|frame| frame.rect.x).collect();
+    assert_eq!(origins, vec![0, 0, 0, 2, 4]);
+    assert_eq!(sheet.get_pixel(5, 0).0, red);
+
+    // hash 冲突时比较像素，不同的帧不会被合并
+    for frame_hash in &colliding.frame_hashes {
+        frame_hash.set(42).unwrap();
+    }
+    assert_eq!(
+        colliding.duplicate_frames().unwrap(),
+        vec![vec![0, 1, 2], vec![3], vec![4]]
+    );
+}
+
+#[test]
+fn check_rgba_bytes() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let (bytes, width, height) = aseprite.frame_rgba_bytes(1).unwrap();
+    assert_eq!((width, height), (96, 64));
+    assert_eq!(bytes.len(), 96 * 64 * 4);
+    // 按行从上到下排列，每个像素 4 个字节，行之间没有 padding
+    let image = aseprite.get_frame_image(1).unwrap();
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let offset = ((y * width + x) * 4) as usize;
+        assert_eq!(&bytes[offset..offset + 4], &pixel.0);
+    }
+    assert!(aseprite.frame_rgba_bytes(2).is_err());
+
+    let aseprite =
+        Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+    let (bytes, width, height) = aseprite.layer_rgba_bytes(2, 1).unwrap().unwrap();
+    assert_eq!((width, height), (32, 32));
+    assert_eq!(
+        bytes,
+        aseprite
+            .get_image_by_layer_frame_on_canvas(2, 1)
+            .unwrap()
+            .unwrap()
+            .into_raw()
+    );
+}
+
+#[test]
+fn check_export_layers() {
+    let dir = std::env::temp_dir().join("aseprite_reader_check_export_layers");
+    let _ = std::fs::remove_dir_all(&dir);
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+
+    let paths = aseprite
+        .export_layers(
+            &dir,
+            "{layer_path}/{frame001}.png",
+            &LayerExportOptions::default(),
+        )
+        .unwrap();
+    // 隐藏的 Night 和 group 都不导出
+    let visible = aseprite
+        .layers()
+        .filter(|layer| matches!(layer, AsepriteLayer::Normal(_)) && layer.name() != "Night")
+        .count();
+    assert_eq!(paths.len(), visible * 2);
+    let path = dir.join("Table/Col2/Col2Row1/002.png");
+    assert!(paths.contains(&path));
+    assert!(!dir.join("Filter/Night").exists());
+    let layer = aseprite.get_layer_by_name("Col2Row1").unwrap();
+    assert_eq!(
+        image::open(&path).unwrap().to_rgba8(),
+        aseprite
+            .get_image_by_layer_frame_on_canvas(layer.index(), 1)
+            .unwrap()
+            .unwrap()
+    );
+
+    // 多个线程同时写文件和创建目录
+    let mut aseprite = aseprite;
+    aseprite.set_render_threads(4);
+    let paths = aseprite
+        .export_layers(
+            &dir,
+            "{layer}_{tag}_{frame}.png",
+            &LayerExportOptions {
+                include_hidden: true,
+                groups: GroupExport::Merged,
+            },
+        )
+        .unwrap();
+    assert_eq!(paths.len(), aseprite.layers().count() * 2);
+    assert!(paths.contains(&dir.join("Night_FrameAllTag_1.png")));
+    let table = aseprite.get_layer_by_name("Table").unwrap();
+    assert_eq!(
+        image::open(dir.join("Table_FrameAllTag_0.png"))
+            .unwrap()
+            .to_rgba8(),
+        aseprite.get_group_image(table.index(), 0).unwrap().unwrap()
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // 清理后文件名相同的图层报错，并且不写入任何文件
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        1,
+        1,
+        vec![vec![mock_layer_chunk("A/B", 0), mock_layer_chunk("A_B", 0)]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    match aseprite.export_layers(&dir, "{layer}.png", &LayerExportOptions::default()) {
+        Err(AsepriteError::ExportPathCollision(path)) => assert_eq!(path, dir.join("A_B.png")),
+        _ => unreachable!(),
+    }
+    assert!(!dir.exists());
+}
+
+#[test]
+fn check_lazy_cel_decompression() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let cels = |frame_index| {
+        aseprite
+            .cels
+            .iter()
+            .filter_map(move |(_, layer_cels)| layer_cels.get(frame_index))
+            .filter(|cel| !matches!(cel.raw_cel, RawAsepriteCel::Linked { .. }))
+    };
+    assert!(cels(0)
+        .chain(cels(1))
+        .all(|cel| cel.decoded.get().is_none()));
+    assert!(cels(1).any(|cel| matches!(cel.raw_cel, RawAsepriteCel::Deferred { .. })));
+
+    // 只解压渲染的这一帧用到的 cel
+    aseprite.get_frame_image(0).unwrap();
+    assert!(cels(0).any(|cel| cel.decoded.get().is_some()));
+    assert!(cels(1).all(|cel| cel.decoded.get().is_none()));
+
+    let buffer = std::fs::read("./tests/test_cases/complex.aseprite").unwrap();
+    let eager = Aseprite::from_raw(crate::raw::read_aseprite(&buffer).unwrap()).unwrap();
+    assert_eq!(
+        aseprite.get_frame_image(1).unwrap(),
+        eager.get_frame_image(1).unwrap()
+    );
+}
+
+#[test]
+fn check_render_cache() {
+    let mut aseprite =
+        Aseprite::from_path("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+    let first = aseprite.get_frame_image_cached(1).unwrap();
+    assert_eq!(*first, aseprite.get_frame_image(1).unwrap());
+    // 命中缓存时返回同一张图片
+    assert!(Arc::ptr_eq(
+        &first,
+        &aseprite.get_frame_image_cached(1).unwrap()
+    ));
+    assert!(aseprite.get_frame_image_cached(4).is_err());
+
+    aseprite.clear_render_cache();
+    let second = aseprite.get_frame_image_cached(1).unwrap();
+    assert!(!Arc::ptr_eq(&first, &second));
+
+    // 超过容量时丢掉最久没用到的帧
+    aseprite.set_render_cache_capacity(2);
+    let zero = aseprite.get_frame_image_cached(0).unwrap();
+    aseprite.get_frame_image_cached(1).unwrap();
+    aseprite.get_frame_image_cached(2).unwrap();
+    assert!(Arc::ptr_eq(
+        &second,
+        &aseprite.get_frame_image_cached(1).unwrap()
+    ));
+    assert!(!Arc::ptr_eq(
+        &zero,
+        &aseprite.get_frame_image_cached(0).unwrap()
+    ));
+
+    aseprite.set_render_cache_capacity(0);
+    let uncached = aseprite.get_frame_image_cached(3).unwrap();
+    assert!(!Arc::ptr_eq(
+        &uncached,
+        &aseprite.get_frame_image_cached(3).unwrap()
+    ));
+}
+
+#[test]
+fn check_layer_image_fast_path() {
+    // 和 Aseprite 的 normal 混合到全透明的像素上的结果一致，完全透明时为 [0; 4]
+    let expected = |pixel: [u8; 4], opacity| match blend_rgba(
+        [0; 4],
+        pixel,
+        opacity,
+        AsepriteBlendMode::Normal,
+    ) {
+        [_, _, _, 0] => [0; 4],
+        blended => blended,
+    };
+    for alpha in 0..=255 {
+        for opacity in [0, 1, 77, 128, 254, 255] {
+            let pixel = [200, 100, 3, alpha];
+            assert_eq!(
+                blend_onto_transparent(pixel, opacity),
+                expected(pixel, opacity)
+            );
+        }
+    }
+
+    for path in [
+        "./tests/test_cases/complex.aseprite",
+        "./tests/test_cases/multiple_frames_layers.aseprite",
+        "./tests/test_cases/simple.aseprite",
+    ] {
+        let aseprite = Aseprite::from_path(path).unwrap();
+        for layer_index in 0..aseprite.layers.len() {
+            for frame_index in 0..aseprite.frame_count {
+                let Some(image) = aseprite
+                    .get_image_by_layer_frame(layer_index, frame_index)
+                    .unwrap()
+                else {
+                    continue;
+                };
+                let cel = aseprite.get_cel(layer_index, frame_index).unwrap();
+                let source = aseprite
+                    .resolve_cel(layer_index, frame_index)
+                    .unwrap()
+                    .source;
+                let pixels = aseprite.decoded_pixels(source).unwrap();
+                let mut expected_image = RgbaImage::new(image.width(), image.height());
+                for (pixel, target) in pixels.iter().zip(expected_image.pixels_mut()) {
+                    target.0 = expected(*pixel, cel.opacity);
+                }
+                assert_eq!(
+                    image, expected_image,
+                    "{} {} {}",
+                    path, layer_index, frame_index
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn check_render_into() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    // 大小不对时换成画布大小
+    let mut target = RgbaImage::new(3, 3);
+    aseprite.render_frame_into(1, &mut target).unwrap();
+    assert_eq!(target, aseprite.get_frame_image(1).unwrap());
+    // 大小相同时复用同一块内存
+    let buffer = target.as_ptr();
+    aseprite.render_frame_into(0, &mut target).unwrap();
+    assert_eq!(target.as_ptr(), buffer);
+    assert_eq!(target, aseprite.get_frame_image(0).unwrap());
+    assert!(aseprite.render_frame_into(2, &mut target).is_err());
+
+    for layer_index in 0..aseprite.layers.len() {
+        for frame_index in 0..aseprite.frame_count {
+            let drawn = aseprite
+                .render_layer_into(layer_index, frame_index, &mut target)
+                .unwrap();
+            assert_eq!(target.as_ptr(), buffer);
+            let Some(cel_image) = aseprite
+                .get_image_by_layer_frame(layer_index, frame_index)
+                .unwrap()
+            else {
+                assert!(!drawn);
+                assert!(target.pixels().all(|pixel| pixel.0 == [0; 4]));
+                continue;
+            };
+            assert!(drawn);
+            let cel = aseprite.get_cel(layer_index, frame_index).unwrap();
+            let mut expected = RgbaImage::new(96, 64);
+            image::imageops::replace(&mut expected, &cel_image, cel.x as i64, cel.y as i64);
+            assert_eq!(target, expected);
+        }
+    }
+}
+
+#[test]
+fn check_batch_loading() {
+    let dir = std::env::temp_dir().join("aseprite_reader_check_batch_loading");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+    std::fs::copy("./tests/test_cases/simple.aseprite", dir.join("b.aseprite")).unwrap();
+    std::fs::copy(
+        "./tests/test_cases/complex.aseprite",
+        dir.join("nested/c.ASE"),
+    )
+    .unwrap();
+    // 损坏的文件不影响其他文件
+    std::fs::write(dir.join("a.aseprite"), b"not an aseprite file").unwrap();
+    std::fs::write(dir.join("notes.txt"), b"").unwrap();
+
+    let names = |results: &[(PathBuf, AseResult<Aseprite>)]| -> Vec<String> {
+        results
+            .iter()
+            .map(|(path, _)| {
+                let relative = path.strip_prefix(&dir).unwrap();
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect()
+    };
+    let results = Aseprite::from_dir(&dir, false).unwrap();
+    assert_eq!(names(&results), vec!["a.aseprite", "b.aseprite"]);
+    assert!(matches!(
+        results[0].1,
+        Err(AsepriteError::NotAnAsepriteFile)
+    ));
+    assert!(results[1].1.is_ok());
+
+    let results = Aseprite::from_dir(&dir, true).unwrap();
+    assert_eq!(
+        names(&results),
+        vec!["a.aseprite", "b.aseprite", "nested/c.ASE"]
+    );
+    assert_eq!(results[2].1.as_ref().unwrap().frame_count(), 2);
+
+    // 结果和传入的顺序一致，文件比线程多时也一样
+    let paths: Vec<PathBuf> = (0..32)
+        .map(|index| match index % 3 {
+            0 => dir.join("nested/c.ASE"),
+            1 => dir.join("missing.aseprite"),
+            _ => dir.join("b.aseprite"),
+        })
+        .collect();
+    let results = Aseprite::from_paths(paths.clone());
+    assert_eq!(
+        results.iter().map(|(path, _)| path).collect::<Vec<_>>(),
+        paths.iter().collect::<Vec<_>>()
+    );
+    for (index, (_, result)) in results.iter().enumerate() {
+        match index % 3 {
+            0 => assert_eq!(result.as_ref().unwrap().frame_count(), 2),
+            1 => assert!(matches!(result, Err(AsepriteError::Io(_)))),
+            _ => assert_eq!(result.as_ref().unwrap().frame_count(), 1),
+        }
+    }
+
+    assert!(Aseprite::from_dir(dir.join("missing"), false).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn check_map_on_threads() {
+    let items: Vec<u64> = (0..100).collect();
+    // 耗时不同的项会以不同的顺序完成
+    let squares = super::batch::map_on_threads(&items, 4, |&item| {
+        std::thread::sleep(std::time::Duration::from_micros((item % 7) * 100));
+        item * item
+    });
+    assert_eq!(
+        squares,
+        items.iter().map(|item| item * item).collect::<Vec<_>>()
+    );
+    assert!(super::batch::map_on_threads(&items[..0], 4, |&item| item).is_empty());
+}
+
+#[test]
+fn check_shrink_pixels() {
+    let mut aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let before = aseprite.memory_usage();
+    assert!(before > 0);
+    // 解码的像素和缓存的帧也计算在内
+    aseprite.get_frame_image_cached(0).unwrap();
+    let (width, height) = aseprite.size();
+    assert!(aseprite.memory_usage() >= before + width as usize * height as usize * 4);
+
+    let size = aseprite.get_cel(2, 0).unwrap().get_size();
+    aseprite.shrink_pixels();
+    assert_eq!(aseprite.memory_usage(), 0);
+
+    // 元数据保留
+    assert_eq!(aseprite.frame_count(), 2);
+    assert_eq!(aseprite.layers().count(), 20);
+    assert_eq!(aseprite.tags().count(), 3);
+    let cel = aseprite.get_cel(2, 0).unwrap();
+    assert!(matches!(cel.raw_cel, RawAsepriteCel::Dropped { .. }));
+    assert_eq!(cel.get_size(), size);
+
+    assert!(matches!(
+        aseprite.get_frame_image(0),
+        Err(AsepriteError::PixelsDropped)
+    ));
+    assert!(matches!(
+        aseprite.get_frame_image_cached(0),
+        Err(AsepriteError::PixelsDropped)
+    ));
+    assert!(matches!(
+        aseprite.get_image_by_layer_frame(2, 0),
+        Err(AsepriteError::PixelsDropped)
+    ));
+}
+
+#[test]
+fn check_frame_signature() {
+    let red = [255, 0, 0, 255];
+    let blue = [0, 0, 255, 255];
+    let linked_cel = |layer_index, frame_position| RawAsepriteChunk::Cel {
+        layer_index,
+        x: 0,
+        y: 0,
+        opacity: 255,
+        z_index: 0,
+        cel: RawAsepriteCel::Linked { frame_position },
+    };
+    // 1 的两个 cel 都链接到 0，2 的 cel 和 0 像素相同但没有链接，3 只有一个 layer 链接到 0
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        1,
+        1,
+        vec![
+            vec![
+                mock_layer_chunk("Bottom", 0),
+                mock_layer_chunk("Top", 0),
+                mock_cel_chunk(0, 0, 0, 1, 1, mock_rgba_pixels(red, 1)),
+                mock_cel_chunk(1, 0, 0, 1, 1, mock_rgba_pixels(blue, 1)),
+            ],
+            vec![linked_cel(0, 0), linked_cel(1, 0)],
+            vec![
+                mock_cel_chunk(0, 0, 0, 1, 1, mock_rgba_pixels(red, 1)),
+                mock_cel_chunk(1, 0, 0, 1, 1, mock_rgba_pixels(blue, 1)),
+            ],
+            vec![linked_cel(0, 0)],
+        ],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    let signature = |frame_index| aseprite.frame_signature(frame_index).unwrap();
+
+    // 链接的 cel 不需要读取像素就能判断相同
+    assert_eq!(signature(1), signature(0));
+    // 没有链接的相同像素 signature 不同，但渲染后的 hash 相同
+    assert_ne!(signature(2), signature(0));
+    assert_eq!(
+        aseprite.frame_content_hash(2).unwrap(),
+        aseprite.frame_content_hash(0).unwrap()
+    );
+    assert_ne!(signature(3), signature(0));
+    assert!(aseprite.frame_signature(4).is_err());
+
+    // 像素被释放后 signature 依然可用
+    let mut shrunk = aseprite.clone();
+    shrunk.shrink_pixels();
+    assert_eq!(shrunk.frame_signature(1).unwrap(), signature(0));
+
+    assert_eq!(
+        aseprite.duplicate_frames().unwrap(),
+        vec![vec![0, 1, 2], vec![3]]
+    );
+}
+
+#[test]
+fn check_deprecated_chunks() {
+    let buffer = std::fs::read("./tests/test_cases/simple.aseprite").unwrap();
+    // mask chunk: x, y, width, height, 8 bytes reserved, name, 1 bit per pixel bitmap
+    let mut mask = vec![0; 6];
+    mask[4..6].copy_from_slice(&0x2016u16.to_le_bytes());
+    mask.extend_from_slice(&[0, 0, 0, 0, 8, 0, 1, 0]);
+    mask.extend_from_slice(&[0; 8]);
+    mask.extend_from_slice(&4u16.to_le_bytes());
+    mask.extend_from_slice(b"Mask");
+    mask.push(0xFF);
+    let mask_size = mask.len() as u32;
+    mask[0..4].copy_from_slice(&mask_size.to_le_bytes());
+    // path chunk 从来没有被使用过，只有 chunk header
+    let mut path = vec![6, 0, 0, 0];
+    path.extend_from_slice(&0x2017u16.to_le_bytes());
+
+    // 插在第一帧的第一个 chunk 之前，更新文件大小、帧大小和 chunk 数量
+    let read_u32 = |buffer: &[u8], at: usize| {
+        u32::from_le_bytes([buffer[at], buffer[at + 1], buffer[at + 2], buffer[at + 3]])
+    };
+    let added = (mask.len() + path.len()) as u32;
+    let mut spliced = buffer[..144].to_vec();
+    spliced.extend_from_slice(&mask);
+    spliced.extend_from_slice(&path);
+    spliced.extend_from_slice(&buffer[144..]);
+    let file_size = read_u32(&spliced, 0) + added;
+    spliced[0..4].copy_from_slice(&file_size.to_le_bytes());
+    let frame_size = read_u32(&spliced, 128) + added;
+    spliced[128..132].copy_from_slice(&frame_size.to_le_bytes());
+    let small_chunk_count = u16::from_le_bytes([spliced[134], spliced[135]]) + 2;
+    spliced[134..136].copy_from_slice(&small_chunk_count.to_le_bytes());
+    let chunk_count = read_u32(&spliced, 140) + 2;
+    spliced[140..144].copy_from_slice(&chunk_count.to_le_bytes());
+
+    let raw = crate::raw::read_aseprite(&spliced).unwrap();
+    let deprecated: Vec<u16> = raw.frames[0]
+        .chunks
+        .iter()
+        .filter_map(|chunk| match chunk {
+            RawAsepriteChunk::Deprecated { chunk_type } => Some(*chunk_type),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(deprecated, vec![0x2016, 0x2017]);
+
+    let original = Aseprite::from_bytes(&buffer).unwrap();
+    let aseprite = Aseprite::from_bytes(&spliced).unwrap();
+    assert_eq!(aseprite.frame_count(), original.frame_count());
+    assert_eq!(aseprite.layers().count(), original.layers().count());
+    assert_eq!(
+        aseprite.get_frame_image(0).unwrap(),
+        original.get_frame_image(0).unwrap()
+    );
+}
+
+#[test]
+fn check_precise_bounds() {
+    let red = [255, 0, 0, 255];
+    let blue = [0, 0, 255, 255];
+    let cel_extra = |flags| RawAsepriteChunk::CelExtra {
+        flags,
+        x: 0.6,
+        y: 0.0,
+        width: 4.0,
+        height: 1.0,
+    };
+    let mut pixels = mock_rgba_pixels(red, 1);
+    pixels.extend(mock_rgba_pixels(blue, 1));
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        6,
+        1,
+        vec![
+            vec![
+                mock_layer_chunk("Layer", 0),
+                mock_cel_chunk(0, 0, 0, 2, 1, pixels.clone()),
+                cel_extra(1),
+            ],
+            // 没有设置 precise bounds 的 flag 时忽略
+            vec![mock_cel_chunk(0, 0, 0, 2, 1, pixels), cel_extra(0)],
+        ],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    let bounds = aseprite.get_cel(0, 0).unwrap().precise_bounds.unwrap();
+    assert_eq!((bounds.x, bounds.width), (0.6, 4.0));
+    assert!(aseprite.get_cel(0, 1).unwrap().precise_bounds.is_none());
+
+    let row = |image: RgbaImage| -> Vec<[u8; 4]> { image.pixels().map(|pixel| pixel.0).collect() };
+    let empty = [0; 4];
+    // 默认仍然按整数位置绘制
+    assert_eq!(
+        row(aseprite.get_frame_image(0).unwrap()),
+        vec![red, blue, empty, empty, empty, empty]
+    );
+    let options = FrameRenderOptions {
+        use_precise_bounds: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        row(aseprite.render_frame(0, &options).unwrap()),
+        vec![empty, red, red, blue, blue, empty]
+    );
+    assert_eq!(
+        row(aseprite.render_frame(1, &options).unwrap()),
+        vec![red, blue, empty, empty, empty, empty]
+    );
+}
+
+#[test]
+fn check_document_info() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let info = aseprite.info();
+    assert_eq!((info.width, info.height), aseprite.size());
+    assert_eq!(info.frame_count, 2);
+    assert_eq!(info.header_flags, 1);
+    assert_eq!(info.pixel_ratio, (1, 1));
+    assert_eq!(info.grid, Some(AsepriteRect::new(0, 0, 16, 16)));
+    assert_eq!(info.grid, aseprite.grid());
+    let profile = info.color_profile.unwrap();
+    assert_eq!(profile.profile_type, AsepriteColorProfileType::Srgb);
+    assert_eq!(profile.icc_profile, None);
+    assert_eq!(info.user_data, None);
+
+    let user_data = |text: &str| RawAsepriteChunk::UserData {
+        data: RawAsepriteUserData {
+            text: text.to_string(),
+            color: AsepriteColor::default(),
+            properties: Vec::new(),
+            properties_data: Vec::new(),
+        },
+    };
+    let palette = || RawAsepriteChunk::Palette {
+        palette_size: 1,
+        from_color: 0,
+        to_color: 0,
+        entries: vec![RawAsepritePaletteEntry {
+            color: AsepriteColor::default(),
+            name: None,
+        }],
+    };
+    let mut raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        1,
+        1,
+        vec![
+            vec![
+                RawAsepriteChunk::ColorProfile {
+                    profile_type: 2,
+                    flags: 1,
+                    gamma: 2.2,
+                    icc_profile: Some(RawAsepriteIccProfile {
+                        icc_profile: vec![1, 2, 3],
+                    }),
+                },
+                palette(),
+                user_data("sprite"),
+                mock_layer_chunk("Layer", 0),
+                user_data("layer"),
+            ],
+            // 只有第一帧 palette 之后的 user data 属于 sprite
+            vec![palette(), user_data("not the sprite")],
+        ],
+    );
+    raw.header.pixel_width = 2;
+    raw.header.pixel_height = 1;
+    raw.header.grid_width = 0;
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    assert_eq!(aseprite.sprite_user_data().unwrap().text, "sprite");
+    assert_eq!(aseprite.layers().next().unwrap().user_data(), "layer");
+    assert_eq!(
+        aseprite.color_profile(),
+        Some(&AsepriteColorProfile {
+            profile_type: AsepriteColorProfileType::Icc,
+            gamma: Some(2.2),
+            icc_profile: Some(vec![1, 2, 3]),
+        })
+    );
+    assert_eq!(aseprite.pixel_ratio(), (2, 1));
+    assert_eq!(aseprite.grid(), None);
+    assert_eq!(aseprite.info().user_data, aseprite.sprite_user_data());
+}
+
+#[test]
+fn check_metadata_eq() {
+    let path = "./tests/test_cases/complex.aseprite";
+    let aseprite = Aseprite::from_path(path).unwrap();
+    let mut other = Aseprite::from_path(path).unwrap();
+    assert!(aseprite.metadata_eq(&other));
+    assert_eq!(aseprite.tags().next(), other.tags().next());
+    assert_eq!(aseprite.layers().next(), other.layers().next());
+
+    // 解码的像素缓存不影响比较
+    aseprite.get_frame_image(0).unwrap();
+    assert_eq!(aseprite.get_cel(2, 0), other.get_cel(2, 0));
+
+    // 丢掉像素后元数据仍然相同，cel 本身不再相同
+    other.shrink_pixels();
+    assert!(aseprite.metadata_eq(&other));
+    assert_ne!(aseprite.get_cel(2, 0), other.get_cel(2, 0));
+    assert!(other.metadata_eq(&aseprite));
+
+    let simple = Aseprite::from_path("./tests/test_cases/simple.aseprite").unwrap();
+    assert!(!aseprite.metadata_eq(&simple));
+
+    let mut renamed = Aseprite::from_path(path).unwrap();
+    renamed.tags.values_mut().next().unwrap().name.push('!');
+    assert!(!aseprite.metadata_eq(&renamed));
+}
+
+#[test]
+fn check_iter_cels() {
+    let aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let entries: Vec<CelEntry> = aseprite.iter_cels().collect();
+    let expected: usize = aseprite
+        .layers()
+        .map(|layer| aseprite.cels_for_layer(layer.index()).count())
+        .sum();
+    assert_eq!(entries.len(), expected);
+    // 先按 layer 再按帧排序
+    assert!(entries
+        .windows(2)
+        .all(|pair| (pair[0].layer_index, pair[0].frame_index)
+            < (pair[1].layer_index, pair[1].frame_index)));
+    for entry in &entries {
+        let layer = entry.layer().unwrap();
+        assert_eq!(layer.index(), entry.layer_index);
+        assert_eq!(entry.layer_name(), layer.name());
+        assert!(std::ptr::eq(
+            entry.cel,
+            aseprite
+                .get_cel(entry.layer_index, entry.frame_index)
+                .unwrap()
+        ));
+    }
+
+    let raw = mock_raw(
+        AsepriteColorDepth::RGBA,
+        4,
+        4,
+        vec![vec![
+            mock_layer_chunk("A", 0),
+            mock_layer_chunk("B", 0),
+            mock_cel_chunk(0, 0, 0, 1, 1, mock_rgba_pixels([255, 0, 0, 255], 1)),
+            mock_cel_chunk(1, 0, 0, 1, 1, mock_rgba_pixels([255, 0, 0, 255], 1)),
+            RawAsepriteChunk::UserData {
+                data: RawAsepriteUserData {
+                    text: "note".to_string(),
+                    color: AsepriteColor::default(),
+                    properties: Vec::new(),
+                    properties_data: Vec::new(),
+                },
+            },
+        ]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+    assert_eq!(aseprite.iter_cels().count(), 2);
+    let with_user_data: Vec<CelEntry> = aseprite.cels_with_user_data().collect();
+    assert_eq!(with_user_data.len(), 1);
+    assert_eq!(with_user_data[0].layer_name(), "B");
+    assert_eq!(with_user_data[0].cel.user_data, "note");
+}
+
+#[test]
+fn check_animation_clips() {
+    let mut aseprite = Aseprite::from_path("./tests/test_cases/complex.aseprite").unwrap();
+    let clips = aseprite.animation_clips();
+    assert_eq!(clips.len(), aseprite.tags().count());
+    for (clip, tag) in clips.iter().zip(aseprite.tags()) {
+        assert_eq!(clip.name, tag.name);
+        assert_eq!(clip.direction, tag.animation_direction);
+        assert_eq!(clip.repeat, tag.repeat);
+        let expected: Vec<usize> = tag.frame_sequence().into_iter().map(usize::from).collect();
+        assert_eq!(clip.frame_indices, expected);
+        assert_eq!(clip.frame_durations.len(), clip.frame_indices.len());
+        assert_eq!(
+            clip.frame_durations.iter().sum::<Duration>(),
+            tag.duration(&aseprite)
+        );
+    }
+
+    // 空的和越界的 tag 被跳过
+    let mut tags = aseprite.tags.values_mut();
+    tags.next().unwrap().frames = 1..1;
+    tags.next().unwrap().frames = 1..3;
+    let clips = aseprite.animation_clips();
+    assert_eq!(clips.len(), 1);
+    assert_eq!(clips[0].name, aseprite.tags().nth(2).unwrap().name);
+}
+
+#[test]
+fn check_anchor() {
+    let key = |frame: u32, x: i32, pivot: Option<AsepritePivot>| RawAsepriteSlice {
+        frame,
+        x_origin: x,
+        y_origin: 0,
+        width: 5,
+        height: 3,
+        nine_patch_info: None,
+        pivot,
+    };
+    let slice = |name: &str, slices: Vec<RawAsepriteSlice>| RawAsepriteChunk::Slice {
+        flags: 0,
+        name: name.to_string(),
+        slices,
+    };
+    let pivot = Some(AsepritePivot {
+        x_pivot: 1,
+        y_pivot: 2,
+    });
+    let frames = vec![
+        vec![
+            mock_layer_chunk("Layer", 0),
+            slice("feet", vec![key(0, 2, None)]),
+            slice("Hit", vec![key(1, 4, pivot.clone())]),
+            slice("anchor", vec![key(2, 6, pivot.clone())]),
+        ],
+        vec![],
+        vec![],
+    ];
+    let aseprite = Aseprite::from_raw(mock_raw(AsepriteColorDepth::RGBA, 8, 8, frames)).unwrap();
+
+    // 没有 pivot 时使用指定 slice 的中心
+    assert_eq!(aseprite.anchor(0, &["feet"]), Some((4, 1)));
+    assert_eq!(aseprite.anchor(0, &["anchor"]), None);
+    // 任意 slice 的 pivot 优先于中心
+    assert_eq!(aseprite.anchor(1, &["feet"]), Some((5, 2)));
+    // 指定的 slice 优先于其他 slice
+    assert_eq!(aseprite.anchor(2, &["anchor", "feet"]), Some((7, 2)));
+    assert_eq!(aseprite.anchor(2, &["feet"]), Some((5, 2)));
+    assert_eq!(aseprite.anchor::<&str>(0, &[]), None);
+
+    let (_, sheet) = aseprite
+        .to_spritesheet(&SpritesheetOptions::default())
+        .unwrap();
+    let options = JsonExportOptions {
+        anchor_slices: vec!["feet".to_string()],
+        ..JsonExportOptions::default()
+    };
+    let json = aseprite.export_json(&sheet, &options);
+    assert!(json.contains(r#""duration":100,"anchor":{"x":4,"y":1}}"#));
+    assert_eq!(json.matches(r#""anchor":{"x":5,"y":2}"#).count(), 2);
+    let json = aseprite.export_texture_packer_json(&sheet, &options);
+    assert!(json.contains(r#""pivot":{"x":0.5,"y":0.125}"#), "{}", json);
+    // 默认的 slice 名称在第一帧没有 key，使用画布中心
+    let json = aseprite.export_json(&sheet, &JsonExportOptions::default());
+    assert!(json.contains(r#""duration":100},"1""#));
+}
+
+#[test]
+fn check_slice_rects_with_prefix() {
+    let key = |frame: u32, x: i32| RawAsepriteSlice {
+        frame,
+        x_origin: x,
+        y_origin: 0,
+        width: 2,
+        height: 2,
+        nine_patch_info: None,
+        pivot: None,
+    };
+    let slice = |name: &str, slices: Vec<RawAsepriteSlice>| RawAsepriteChunk::Slice {
+        flags: 0,
+        name: name.to_string(),
+        slices,
+    };
+    let frames = vec![
+        vec![
+            mock_layer_chunk("Layer", 0),
+            slice("hit_punch", vec![key(0, 0), key(1, 3)]),
+            RawAsepriteChunk::UserData {
+                data: RawAsepriteUserData {
+                    text: "10".to_string(),
+                    color: AsepriteColor::default(),
+                    properties: Vec::new(),
+                    properties_data: Vec::new(),
+                },
+            },
+            slice("hurt_body", vec![key(0, 1)]),
+            slice("hit_kick", vec![key(1, 5)]),
+        ],
+        vec![],
+    ];
+    let aseprite = Aseprite::from_raw(mock_raw(AsepriteColorDepth::RGBA, 8, 8, frames)).unwrap();
+
+    // 第一帧 hit_kick 还没有 key
+    let hits = aseprite.slice_rects_with_prefix("hit_", 0);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].0.name, "hit_punch");
+    assert_eq!(hits[0].0.user_data, "10");
+    assert_eq!(hits[0].1, AsepriteRect::new(0, 0, 2, 2));
+
+    // 使用每一帧生效的 key
+    let hits: Vec<(&str, AsepriteRect)> = aseprite
+        .slice_rects_with_prefix("hit_", 1)
+        .into_iter()
+        .map(|(slice, bounds)| (slice.name.as_str(), bounds))
+        .collect();
+    assert_eq!(
+        hits,
+        vec![
+            ("hit_punch", AsepriteRect::new(3, 0, 2, 2)),
+            ("hit_kick", AsepriteRect::new(5, 0, 2, 2)),
+        ]
+    );
+    assert_eq!(aseprite.slice_rects_with_prefix("hurt_", 1).len(), 1);
+    assert!(aseprite.slice_rects_with_prefix("block_", 1).is_empty());
+}
+
+#[test]
+fn check_render_onion_skin() {
+    let pixel = |color: [u8; 4]| mock_rgba_pixels(color, 1);
+    let frames = vec![
+        vec![
+            mock_layer_chunk("Layer", 0),
+            mock_cel_chunk(0, 0, 0, 1, 1, pixel([255, 0, 0, 255])),
+        ],
+        vec![mock_cel_chunk(0, 1, 0, 1, 1, pixel([0, 255, 0, 255]))],
+        vec![mock_cel_chunk(0, 0, 0, 1, 1, pixel([0, 0, 255, 255]))],
+    ];
+    let aseprite = Aseprite::from_raw(mock_raw(AsepriteColorDepth::RGBA, 2, 1, frames)).unwrap();
+    let render = |frame_index: usize, options: OnionSkinOptions| {
+        let image = aseprite.render_onion_skin(frame_index, &options).unwrap();
+        (image.get_pixel(0, 0).0, image.get_pixel(1, 0).0)
+    };
+
+    // 前后两帧都画在 (0, 0)，后一帧在上面
+    assert_eq!(
+        render(1, OnionSkinOptions::default()),
+        ([85, 0, 170, 192], [0, 255, 0, 255])
+    );
+    let before_only = OnionSkinOptions {
+        after: 0,
+        ..OnionSkinOptions::default()
+    };
+    assert_eq!(render(1, before_only), ([255, 0, 0, 128], [0, 255, 0, 255]));
+
+    // 超出范围的帧被跳过，除非循环
+    let first = aseprite.get_frame_image(0).unwrap();
+    assert_eq!(aseprite.render_onion_skin(0, &before_only).unwrap(), first);
+    let looping = OnionSkinOptions {
+        looping: true,
+        ..before_only
+    };
+    assert_eq!(render(0, looping), ([255, 0, 0, 255], [0, 0, 0, 0]));
+    assert_eq!(render(1, looping).0, [255, 0, 0, 128]);
+
+    // 越远的帧越透明
+    let far = OnionSkinOptions {
+        before: 2,
+        after: 0,
+        ..OnionSkinOptions::default()
+    };
+    assert_eq!(render(2, far).1, [0, 255, 0, 128]);
+    assert_eq!(render(2, far).0, [0, 0, 255, 255]);
+    let invisible = OnionSkinOptions {
+        opacity_falloff: 0.0,
+        ..far
+    };
+    assert_eq!(render(2, invisible).1, [0, 0, 0, 0]);
+
+    // 染色只改变颜色，不改变透明度
+    let tinted = OnionSkinOptions {
+        tint: true,
+        after: 0,
+        ..OnionSkinOptions::default()
+    };
+    assert_eq!(render(2, tinted).1, [127, 127, 0, 128]);
+
+    assert!(aseprite
+        .render_onion_skin(3, &OnionSkinOptions::default())
+        .is_err());
+}
+
+#[test]
+fn check_invalid_tag_range() {
+    let bytes = std::fs::read("./tests/test_cases/complex.aseprite").unwrap();
+    let tags_offset = crate::raw::scan_layout(&bytes)
+        .unwrap()
+        .frames
+        .iter()
+        .flat_map(|frame| &frame.chunks)
+        .find(|chunk| chunk.chunk_type == 0x2018)
+        .unwrap()
+        .offset;
+    // chunk 头 6 字节，tag 数量 2 字节，保留 8 字节，之后是第一个 tag 的 from 和 to
+    let with_first_tag = |from: u16, to: u16| {
+        let mut bytes = bytes.clone();
+        let start = tags_offset + 16;
+        bytes[start..start + 2].copy_from_slice(&from.to_le_bytes());
+        bytes[start + 2..start + 4].copy_from_slice(&to.to_le_bytes());
+        bytes
+    };
+
+    // 最后一帧是 1
+    let bytes = with_first_tag(0, 1);
+    let aseprite = Aseprite::from_bytes(&bytes).unwrap();
+    assert_eq!(aseprite.tags().next().unwrap().frames, 0..2);
+    assert_eq!(Aseprite::probe(&bytes).unwrap().tags[0].frames, 0..2);
+
+    for (from, to) in [(0, 0xFFFF), (0, 2), (1, 0)] {
+        let bytes = with_first_tag(from, to);
+        let is_invalid_range = |result: AsepriteError| {
+            matches!(
+                result,
+                AsepriteError::InvalidConfiguration(AsepriteInvalidError::InvalidTagRange {
+                    from: f,
+                    to: t,
+                    ..
+                }) if f == from && t == to
+            )
+        };
+        assert!(is_invalid_range(Aseprite::from_bytes(&bytes).unwrap_err()));
+        assert!(is_invalid_range(Aseprite::probe(&bytes).unwrap_err()));
+    }
+
+    let last_frame = RawAsepriteTag {
+        from: 0,
+        to: u16::MAX,
+        anim_direction: AsepriteAnimationDirection::Forward,
+        repeat: 0,
+        name: "Last".to_owned(),
+    };
+    assert!(matches!(
+        AsepriteTag::frame_range(&last_frame, usize::MAX),
+        Err(AsepriteInvalidError::InvalidTagRange { .. })
+    ));
+
+    // header 里只写了 1 帧，文件里却有 2 帧
+    let mut bytes = bytes;
+    bytes[6..8].copy_from_slice(&1u16.to_le_bytes());
+    let is_too_many_frames = |result: AsepriteError| {
+        matches!(
+            result,
+            AsepriteError::InvalidConfiguration(AsepriteInvalidError::TooManyFrames {
+                declared: 1
+            })
+        )
+    };
+    assert!(is_too_many_frames(
+        Aseprite::from_bytes(&bytes).unwrap_err()
+    ));
+    assert!(is_too_many_frames(Aseprite::probe(&bytes).unwrap_err()));
+}
+
+#[test]
+fn check_extra_tag_user_data() {
+    let user_data = || RawAsepriteChunk::UserData {
+        data: RawAsepriteUserData {
+            text: "data".to_string(),
+            color: AsepriteColor::default(),
+            properties: Vec::new(),
+            properties_data: Vec::new(),
+        },
+    };
+    let tags = || RawAsepriteChunk::Tags {
+        tags: vec![RawAsepriteTag {
+            from: 0,
+            to: 0,
+            anim_direction: AsepriteAnimationDirection::Forward,
+            repeat: 0,
+            name: "Tag".to_string(),
+        }],
+    };
+    let aseprite = Aseprite::from_raw(mock_raw(
+        AsepriteColorDepth::RGBA,
+        1,
+        1,
+        vec![vec![mock_layer_chunk("Layer", 0), tags(), user_data()]],
+    ))
+    .unwrap();
+    assert_eq!(aseprite.tags().next().unwrap().user_data, "data");
+
+    // tag 之后的 user data 比 tag 多时报错而不是 panic
+    let result = Aseprite::from_raw(mock_raw(
+        AsepriteColorDepth::RGBA,
+        1,
+        1,
+        vec![vec![
+            mock_layer_chunk("Layer", 0),
+            tags(),
+            user_data(),
+            user_data(),
+        ]],
+    ));
+    assert!(matches!(
+        result,
+        Err(AsepriteError::InvalidConfiguration(
+            AsepriteInvalidError::InvalidTag(1)
+        ))
+    ));
+}
+
+#[test]
+fn check_forged_palette_size() {
+    let palette = RawAsepriteChunk::Palette {
+        palette_size: u32::MAX,
+        from_color: 0,
+        to_color: 1,
+        entries: (0..2)
+            .map(|_| RawAsepritePaletteEntry {
+                color: AsepriteColor::default(),
+                name: None,
+            })
+            .collect(),
+    };
+    let raw = mock_raw(
+        AsepriteColorDepth::Indexed,
+        4,
+        4,
+        vec![vec![palette, mock_layer_chunk("Layer", 0)]],
+    );
+    let aseprite = Aseprite::from_raw(raw).unwrap();
+
+    assert_eq!(aseprite.palette().unwrap().len(), 2);
+}
@@ -0,0 +1,63 @@
+use crate::raw::RawAsepriteCel;
+
+use super::{Aseprite, AsepriteLayer};
+
+/// A single draw call needed to render a frame in a web frontend
+///
+/// Executing the commands of [`Aseprite::web_render_plan`] in order, e.g. with a Canvas2D
+/// `drawImage` after setting `globalAlpha` and `globalCompositeOperation`, renders the frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebDrawCommand {
+    /// The layer being drawn
+    pub layer: usize,
+    /// The `(layer, frame)` whose image should be drawn
+    ///
+    /// Linked cels point at the frame holding the pixels, so the image can be fetched with
+    /// [`Aseprite::get_image_by_layer_frame`] once and shared.
+    pub image_ref: (usize, usize),
+    /// x position of the image on the canvas
+    pub x: i16,
+    /// y position of the image on the canvas
+    pub y: i16,
+    /// Combined cel, layer and group opacity
+    pub opacity: u8,
+    /// The Canvas2D `globalCompositeOperation`, `None` if it has no equivalent
+    pub composite_op: Option<&'static str>,
+}
+
+impl Aseprite {
+    /// Flatten the layers of a frame into draw commands, from bottom to top
+    ///
+    /// Hidden layers, reference layers, group layers and layers without a cel in this frame
+    /// are left out.
+    pub fn web_render_plan(&self, frame_index: usize) -> Vec<WebDrawCommand> {
+        let mut commands = Vec::new();
+        for layer_index in self.cel_render_order(frame_index) {
+            let Some(layer) = self.layers.get(&layer_index) else {
+                continue;
+            };
+            if layer.is_reference()
+                || !self.is_layer_effectively_visible(layer_index)
+                || !matches!(layer, AsepriteLayer::Normal(..))
+            {
+                continue;
+            }
+            let Some(cel) = self.get_cel(layer_index, frame_index) else {
+                continue;
+            };
+            let image_frame = match cel.raw_cel {
+                RawAsepriteCel::Linked { frame_position } => frame_position as usize,
+                _ => frame_index,
+            };
+            commands.push(WebDrawCommand {
+                layer: layer_index,
+                image_ref: (layer_index, image_frame),
+                x: cel.x,
+                y: cel.y,
+                opacity: self.effective_cel_opacity(layer_index, frame_index),
+                composite_op: layer.blend_mode().to_canvas_composite_op(),
+            });
+        }
+        commands
+    }
+}
@@ -25,21 +25,44 @@ pub enum AsepriteParseError<I: std::fmt::Debug> {
     /// An invalid blend mode was found
     #[error("Found invalid blend mode {0}")]
     InvalidBlendMode(u16),
-    /// The pixel data could not be decompressed
-    #[error("Found invalid compressed data {0}")]
-    InvalidCompressedData(DecompressError),
-    /// There was not enough compressed data
-    #[error("Did not find enough compressed data. File invalid.")]
-    NotEnoughCompressedData,
+    /// The pixel data of a cel could not be read
+    #[error("Found invalid pixel data in the cel of layer {layer_index} in frame {frame_index}: {source}")]
+    InvalidCelData {
+        /// The layer of the cel
+        layer_index: u16,
+        /// The frame of the cel
+        frame_index: usize,
+        /// What was wrong with the pixel data
+        source: AsepriteCelDataError,
+    },
     /// An invalid cel was found while decompressing
     #[error("Found invalid cel while decompressing")]
     InvalidCel,
     /// An invalid cel type was found
     #[error("Found invalid cel type {0}")]
     InvalidCelType(u16),
+    /// The file does not start with the aseprite magic number
+    #[error("Found invalid magic number {0:#06X}. Expected 0xA5E0.")]
+    InvalidMagicNumber(u16),
+    /// A frame does not start with the frame magic number
+    #[error(
+        "Found invalid magic number {magic_number:#06X} in frame {frame_index}. Expected 0xF1FA."
+    )]
+    InvalidFrameMagicNumber {
+        /// The frame with the invalid magic number
+        frame_index: usize,
+        /// The magic number that was found
+        magic_number: u16,
+    },
     /// An invalid animation direction was found
     #[error("Found invalid animation type {0}")]
     InvalidAnimationDirection(u8),
+    /// The file has more frames than its header declares
+    #[error("Found more frames than the {declared} declared in the header")]
+    TooManyFrames {
+        /// The amount of frames in the header
+        declared: u16,
+    },
 
     /// A generic [`nom`] error was found
     #[error("Nom error: {nom:?}")]
@@ -76,6 +99,79 @@ pub enum AsepriteParseError<I: std::fmt::Debug> {
     InvalidColorProfileChunk(Box<AsepriteParseError<I>>),
 }
 
+/// Reasons the pixel data of a cel could not be read
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum AsepriteCelDataError {
+    /// The compressed data is not a valid zlib stream
+    #[error("Found invalid compressed data {0}")]
+    Decompress(#[from] DecompressError),
+    /// The zlib stream did not end where the cel size says it should
+    #[error("Did not find enough compressed data. File invalid.")]
+    NotEnoughCompressedData,
+    /// The decompressed data does not match the size of the cel
+    #[error("Expected {expected} bytes of pixel data, found {found}")]
+    SizeMismatch {
+        /// `width * height * bytes per pixel` of the cel
+        expected: usize,
+        /// The amount of bytes actually decompressed
+        found: usize,
+    },
+}
+
+/// A string is not a color, see [`AsepriteColor::from_hex`](crate::raw::AsepriteColor::from_hex)
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{0:?} is not a color written as RRGGBB or RRGGBBAA")]
+pub struct AsepriteHexColorError(pub String);
+
+/// Where [`scan_layout`](crate::raw::scan_layout) stopped before the end of a file
+///
+/// Offsets are in bytes from the start of the file.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum AsepriteLayoutError {
+    /// The file ends in the middle of a frame header
+    #[error("The header of frame {frame_index} at byte {offset} is truncated")]
+    TruncatedFrameHeader {
+        /// The frame
+        frame_index: usize,
+        /// Start of the frame
+        offset: usize,
+    },
+    /// A frame does not start with the frame magic number
+    #[error("Frame {frame_index} at byte {offset} has the magic number {magic_number:#06X} instead of 0xF1FA")]
+    BadFrameMagic {
+        /// The frame
+        frame_index: usize,
+        /// Start of the frame
+        offset: usize,
+        /// The magic number that was found
+        magic_number: u16,
+    },
+    /// A frame is smaller than its header or goes past the end of the file
+    #[error("Frame {frame_index} at byte {offset} declares an invalid size of {size} bytes")]
+    InvalidFrameSize {
+        /// The frame
+        frame_index: usize,
+        /// Start of the frame
+        offset: usize,
+        /// The declared size
+        size: usize,
+    },
+    /// A chunk is smaller than its header or goes past the end of its frame or of the file
+    #[error("Chunk {chunk_index} of frame {frame_index} at byte {offset} declares an invalid size of {size} bytes")]
+    InvalidChunkSize {
+        /// The frame of the chunk
+        frame_index: usize,
+        /// The chunk, counted from the start of the frame
+        chunk_index: usize,
+        /// Start of the chunk
+        offset: usize,
+        /// The declared size
+        size: usize,
+    },
+}
+
 impl<I: Debug> ParseError<I> for AsepriteParseError<I> {
     fn from_error_kind(input: I, kind: nom::error::ErrorKind) -> Self {
         AsepriteParseError::GenericNom { input, nom: kind }
@@ -98,14 +194,103 @@ pub enum AsepriteError {
     /// An IO error occured
     #[error("An IO error occured")]
     Io(#[from] std::io::Error),
+    /// The data is not an aseprite file at all
+    #[error("Not an aseprite file")]
+    NotAnAsepriteFile,
     /// An invalid configuration was found while decoding
     #[error("Invalid configuration of the aseprite file")]
     InvalidConfiguration(#[from] AsepriteInvalidError),
+    /// The pixel data of a cel is corrupted
+    #[error("Invalid pixel data in the cel of layer {layer_index} in frame {frame_index}")]
+    InvalidCelData {
+        /// The layer of the cel
+        layer_index: usize,
+        /// The frame of the cel
+        frame_index: usize,
+        /// What was wrong with the pixel data
+        source: AsepriteCelDataError,
+    },
+    /// An image could not be encoded
+    #[error("An error occured while encoding: {0}")]
+    Encode(String),
+    /// Pixels were requested from a file parsed with
+    /// [`ParseOptions::skip_pixels`](crate::ParseOptions::skip_pixels)
+    #[error("The pixels of the aseprite file were not loaded")]
+    PixelsNotLoaded,
+    /// Pixels were requested after they were released by
+    /// [`Aseprite::shrink_pixels`](crate::Aseprite::shrink_pixels)
+    #[error("The pixels of the aseprite file were dropped")]
+    PixelsDropped,
+    /// A frame was rendered that was left out by
+    /// [`ParseOptions::frames`](crate::ParseOptions::frames)
+    #[error("The pixels of frame {0} were not loaded")]
+    FrameNotLoaded(usize),
+    /// Two exported images would be written to the same path
+    #[error("Several images would be exported to {0:?}")]
+    ExportPathCollision(std::path::PathBuf),
+    /// Palette indices were requested from a file that does not use indexed colors
+    #[error("The aseprite file does not use indexed colors")]
+    NotIndexed,
+    /// The file is bigger than what the parse options allow
+    #[error("The {limit} of the file is {value}, above the limit of {max}")]
+    LimitExceeded {
+        /// Which limit was exceeded
+        limit: AsepriteLimit,
+        /// The value found in the file
+        value: u64,
+        /// The configured limit
+        max: u64,
+    },
+}
+
+/// The limits that can be configured when parsing a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AsepriteLimit {
+    /// `width * height` of the canvas
+    CanvasArea,
+    /// Amount of frames
+    Frames,
+    /// Bytes of pixel data all the cels decompress to
+    DecompressedBytes,
+}
+
+impl std::fmt::Display for AsepriteLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AsepriteLimit::CanvasArea => "canvas area",
+            AsepriteLimit::Frames => "frame count",
+            AsepriteLimit::DecompressedBytes => "decompressed size",
+        })
+    }
 }
 
 impl<'a> From<AsepriteParseError<&'a [u8]>> for AsepriteError {
     fn from(other: AsepriteParseError<&'a [u8]>) -> Self {
-        AsepriteError::Parse(other.to_string())
+        match other {
+            AsepriteParseError::InvalidCelChunk(inner) => match *inner {
+                AsepriteParseError::InvalidCelData {
+                    layer_index,
+                    frame_index,
+                    source,
+                } => AsepriteError::InvalidCelData {
+                    layer_index: layer_index as usize,
+                    frame_index,
+                    source,
+                },
+                inner => AsepriteError::Parse(
+                    AsepriteParseError::InvalidCelChunk(Box::new(inner)).to_string(),
+                ),
+            },
+            AsepriteParseError::InvalidMagicNumber(_) => AsepriteError::NotAnAsepriteFile,
+            AsepriteParseError::InvalidFrameMagicNumber { frame_index, .. } => {
+                AsepriteInvalidError::BadFrameMagic { frame_index }.into()
+            }
+            AsepriteParseError::TooManyFrames { declared } => {
+                AsepriteInvalidError::TooManyFrames { declared }.into()
+            }
+            other => AsepriteError::Parse(other.to_string()),
+        }
     }
 }
 
@@ -118,12 +303,63 @@ pub enum AsepriteInvalidError {
     /// An invalid layer was specified in the aseprite file
     #[error("An invalid layer was specified")]
     InvalidLayer(usize),
+    /// No layer with the given name exists
+    #[error("No layer is named {0:?}")]
+    InvalidLayerName(String),
+    /// No tag with the given name exists
+    #[error("No tag is named {0:?}")]
+    InvalidTagName(String),
+    /// The tag covers no frame
+    #[error("Tag {0:?} has no frames")]
+    EmptyTag(String),
+    /// The frames of a tag are in the wrong order or past the last frame
+    #[error("Tag {name:?} covers frames {from} to {to}, which are not in the animation")]
+    InvalidTagRange {
+        /// The name of the tag
+        name: String,
+        /// The first frame of the tag
+        from: u16,
+        /// The last frame of the tag, inclusive
+        to: u16,
+    },
+    /// A group layer was expected but the layer is a normal layer
+    #[error("Layer {0} is not a group")]
+    NotAGroup(usize),
     /// An invalid frame was specified in the frame
     #[error("An invalid frame was specified")]
     InvalidFrame(usize),
     /// An invalid palette index was specified as a color
     #[error("An invalid palette index was specified as a color")]
     InvalidPaletteIndex(usize),
+    /// An invalid tag was specified in the aseprite file, e.g. by more user data chunks
+    /// following a tags chunk than it has tags
+    #[error("An invalid tag was specified")]
+    InvalidTag(usize),
+    /// An invalid slice was specified in the aseprite file
+    #[error("An invalid slice was specified")]
+    InvalidSlice(usize),
+    /// A linked cel links to another linked cel, possibly in a cycle
+    #[error(
+        "The linked cel of layer {layer_index} in frame {frame_index} links to another linked cel"
+    )]
+    LinkedCelCycle {
+        /// The layer of the linked cel
+        layer_index: usize,
+        /// The frame of the linked cel
+        frame_index: usize,
+    },
+    /// A frame did not start with the frame magic number
+    #[error("Frame {frame_index} does not start with the frame magic number")]
+    BadFrameMagic {
+        /// The index of the broken frame
+        frame_index: usize,
+    },
+    /// The file has more frames than its header declares
+    #[error("The file has more frames than the {declared} declared in its header")]
+    TooManyFrames {
+        /// The amount of frames in the header
+        declared: u16,
+    },
 }
 
 pub(crate) type AseParseResult<'a, R> = IResult<&'a [u8], R, AsepriteParseError<&'a [u8]>>;
@@ -17,7 +17,6 @@
     clippy::print_stdout,
     clippy::todo,
     //clippy::unwrap_used, // not yet in stable
-    clippy::wrong_pub_self_convention
 )]
 #![doc = include_str!("../README.MD")]
 
@@ -32,3 +31,4 @@ pub mod raw;
 mod computed;
 
 pub use computed::*;
+pub use raw::AsepriteColorDepth;
@@ -1,24 +1,27 @@
-use std::convert::TryInto;
-
 use flate2::Decompress;
 use nom::{
-    bytes::complete::{tag, take},
+    bytes::complete::take,
     combinator::{all_consuming, cond},
-    Finish,
     multi::{count, length_data, many1},
-    number::complete::{le_i16, le_i32, le_u16, le_u32, le_u8},
+    number::complete::{
+        le_f32, le_f64, le_i16, le_i32, le_i64, le_i8, le_u16, le_u32, le_u64, le_u8,
+    },
+    Finish,
 };
 use tracing::{debug_span, error, info};
 
 use crate::{
-    AsepritePalette,
-    error::{AseParseResult, AsepriteError, AsepriteInvalidError, AsepriteParseError, AseResult},
+    error::{
+        AseParseResult, AseResult, AsepriteCelDataError, AsepriteError, AsepriteHexColorError,
+        AsepriteInvalidError, AsepriteLayoutError, AsepriteParseError,
+    },
+    AsepritePalette, LoadPhase,
 };
 
 // As specified in https://github.com/aseprite/aseprite/blob/main/docs/ase-file-specs.md
 
 /// Color depth in a single .aseprite file
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(missing_docs)]
 pub enum AsepriteColorDepth {
     RGBA,
@@ -78,7 +81,7 @@ pub struct RawAsepriteHeader {
     pub grid_height: u16,
 }
 
-fn color_depth(input: &[u8]) -> AseParseResult<AsepriteColorDepth> {
+fn color_depth(input: &[u8]) -> AseParseResult<'_, AsepriteColorDepth> {
     let (input, depth) = le_u16(input)?;
     Ok((
         input,
@@ -97,11 +100,16 @@ fn color_depth(input: &[u8]) -> AseParseResult<AsepriteColorDepth> {
 
 const ASEPRITE_MAGIC_NUMBER: u16 = 0xA5E0;
 
-fn aseprite_header(input: &[u8]) -> AseParseResult<RawAsepriteHeader> {
+fn aseprite_header(input: &[u8]) -> AseParseResult<'_, RawAsepriteHeader> {
     let input_len = input.len();
     let (input, file_size) = le_u32(input)?;
 
-    let (input, magic_number) = tag(&ASEPRITE_MAGIC_NUMBER.to_le_bytes())(input)?;
+    let (input, magic_number) = le_u16(input)?;
+    if magic_number != ASEPRITE_MAGIC_NUMBER {
+        return Err(nom::Err::Failure(AsepriteParseError::InvalidMagicNumber(
+            magic_number,
+        )));
+    }
     let (input, frames) = le_u16(input)?;
     let (input, width) = le_u16(input)?;
     let (input, height) = le_u16(input)?;
@@ -128,11 +136,7 @@ fn aseprite_header(input: &[u8]) -> AseParseResult<RawAsepriteHeader> {
         #[allow(deprecated)]
         RawAsepriteHeader {
             file_size,
-            magic_number: u16::from_le_bytes(
-                magic_number
-                    .try_into()
-                    .expect("Invalid ASEPRITE_MAGIC_NUMBER matched. This is a bug."),
-            ),
+            magic_number,
             frames,
             width,
             height,
@@ -151,7 +155,7 @@ fn aseprite_header(input: &[u8]) -> AseParseResult<RawAsepriteHeader> {
     ))
 }
 
-fn aseprite_string(input: &[u8]) -> AseParseResult<String> {
+fn aseprite_string(input: &[u8]) -> AseParseResult<'_, String> {
     let (input, name_len) = le_u16(input)?;
     let (input, name_bytes) = take(name_len as usize)(input)?;
 
@@ -175,7 +179,7 @@ pub struct RawAsepriteFrame {
 
 /// A full RGBA color
 #[allow(missing_docs)]
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
 pub struct AsepriteColor {
     pub red: u8,
     pub green: u8,
@@ -183,18 +187,82 @@ pub struct AsepriteColor {
     pub alpha: u8,
 }
 
-impl Default for AsepriteColor {
-    fn default() -> Self {
-        Self {
-            red: 0,
-            green: 0,
-            blue: 0,
-            alpha: 0,
+impl AsepriteColor {
+    /// The channels as floats from `0.0` to `1.0`, in RGBA order
+    pub fn to_f32_array(&self) -> [f32; 4] {
+        <[u8; 4]>::from(*self).map(|channel| channel as f32 / 255.0)
+    }
+
+    /// Like [`AsepriteColor::to_f32_array`], with red, green and blue multiplied by alpha
+    pub fn to_premultiplied_f32_array(&self) -> [f32; 4] {
+        let [red, green, blue, alpha] = self.to_f32_array();
+        [red * alpha, green * alpha, blue * alpha, alpha]
+    }
+
+    /// Format the color as `#RRGGBBAA`
+    pub fn to_hex(&self) -> String {
+        format!(
+            "#{:02X}{:02X}{:02X}{:02X}",
+            self.red, self.green, self.blue, self.alpha
+        )
+    }
+
+    /// Parse a color written as `RRGGBB` or `RRGGBBAA`, optionally starting with `#`
+    ///
+    /// Colors without alpha are opaque.
+    pub fn from_hex(hex: &str) -> Result<Self, AsepriteHexColorError> {
+        let error = || AsepriteHexColorError(hex.to_string());
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        // from_str_radix 也接受 `+` 开头，所以先检查每一位
+        if !matches!(digits.len(), 6 | 8) || !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(error());
+        }
+        let value = u32::from_str_radix(digits, 16).map_err(|_| error())?;
+        Ok(match digits.len() {
+            6 => (value << 8 | 0xFF).to_be_bytes().into(),
+            _ => value.to_be_bytes().into(),
+        })
+    }
+}
+
+impl std::str::FromStr for AsepriteColor {
+    type Err = AsepriteHexColorError;
+
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(hex)
+    }
+}
+
+impl From<[u8; 4]> for AsepriteColor {
+    fn from([red, green, blue, alpha]: [u8; 4]) -> Self {
+        AsepriteColor {
+            red,
+            green,
+            blue,
+            alpha,
         }
     }
 }
 
-fn aseprite_color(input: &[u8]) -> AseParseResult<AsepriteColor> {
+impl From<AsepriteColor> for [u8; 4] {
+    fn from(color: AsepriteColor) -> Self {
+        [color.red, color.green, color.blue, color.alpha]
+    }
+}
+
+impl From<image::Rgba<u8>> for AsepriteColor {
+    fn from(pixel: image::Rgba<u8>) -> Self {
+        pixel.0.into()
+    }
+}
+
+impl From<AsepriteColor> for image::Rgba<u8> {
+    fn from(color: AsepriteColor) -> Self {
+        image::Rgba(color.into())
+    }
+}
+
+fn aseprite_color(input: &[u8]) -> AseParseResult<'_, AsepriteColor> {
     let (input, colors) = take(4usize)(input)?;
 
     Ok((
@@ -209,15 +277,287 @@ fn aseprite_color(input: &[u8]) -> AseParseResult<AsepriteColor> {
 }
 
 /// Raw user data
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RawAsepriteUserData {
     /// Text, if any
     pub text: String,
     /// Color, if any
     pub color: AsepriteColor,
+    /// Properties maps, if any
+    pub properties: Vec<AsepritePropertiesMap>,
+    /// The undecoded properties block (including its size field), kept so it can be written
+    /// back byte for byte
+    pub properties_data: Vec<u8>,
+}
+
+impl RawAsepriteUserData {
+    /// Encode this user data as the content of a user data chunk
+    ///
+    /// The properties block is written back exactly as it was read, so property types this
+    /// crate does not understand survive a round trip.
+    pub fn to_chunk_data(&self) -> Vec<u8> {
+        let mut flags = 0u32;
+        if !self.text.is_empty() {
+            flags |= 0x1;
+        }
+        if self.color != AsepriteColor::default() {
+            flags |= 0x2;
+        }
+        if !self.properties_data.is_empty() {
+            flags |= 0x4;
+        }
+
+        let mut data = flags.to_le_bytes().to_vec();
+        if flags & 0x1 != 0 {
+            data.extend_from_slice(&(self.text.len() as u16).to_le_bytes());
+            data.extend_from_slice(self.text.as_bytes());
+        }
+        if flags & 0x2 != 0 {
+            let AsepriteColor {
+                red,
+                green,
+                blue,
+                alpha,
+            } = self.color;
+            data.extend_from_slice(&[red, green, blue, alpha]);
+        }
+        data.extend_from_slice(&self.properties_data);
+        data
+    }
+}
+
+/// A properties map stored in user data
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsepritePropertiesMap {
+    /// 0 for the user properties, otherwise the id of the extension owning this map
+    pub key: u32,
+    /// The properties, in file order
+    pub properties: Vec<(String, AsepritePropertyValue)>,
+}
+
+/// A single value in a properties map
+#[derive(Debug, Clone, PartialEq)]
+#[allow(missing_docs)]
+pub enum AsepritePropertyValue {
+    Bool(bool),
+    Int8(i8),
+    UInt8(u8),
+    Int16(i16),
+    UInt16(u16),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Fixed(f64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    Point {
+        x: i32,
+        y: i32,
+    },
+    Size {
+        width: i32,
+        height: i32,
+    },
+    Rect {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    Vector(Vec<AsepritePropertyValue>),
+    Properties(Vec<(String, AsepritePropertyValue)>),
+    Uuid([u8; 16]),
+    /// A value of a type this crate doesn't know
+    ///
+    /// The size of an unknown value can't be known, so `bytes` holds everything from the
+    /// value up to the end of the properties block, and nothing after it is decoded.
+    Unknown {
+        type_id: u16,
+        bytes: Vec<u8>,
+    },
+}
+
+impl AsepritePropertyValue {
+    /// 遇到未知类型后，后续的数据都会被吞进 Unknown 里
+    fn swallows_rest(&self) -> bool {
+        match self {
+            AsepritePropertyValue::Unknown { .. } => true,
+            AsepritePropertyValue::Vector(values) => values.last().is_some_and(Self::swallows_rest),
+            AsepritePropertyValue::Properties(properties) => properties
+                .last()
+                .is_some_and(|(_, value)| value.swallows_rest()),
+            _ => false,
+        }
+    }
 }
 
-fn aseprite_user_data(input: &[u8]) -> AseParseResult<RawAsepriteUserData> {
+fn aseprite_property_value(
+    input: &[u8],
+    type_id: u16,
+) -> AseParseResult<'_, AsepritePropertyValue> {
+    Ok(match type_id {
+        0x0001 => {
+            let (input, value) = le_u8(input)?;
+            (input, AsepritePropertyValue::Bool(value != 0))
+        }
+        0x0002 => {
+            let (input, value) = le_i8(input)?;
+            (input, AsepritePropertyValue::Int8(value))
+        }
+        0x0003 => {
+            let (input, value) = le_u8(input)?;
+            (input, AsepritePropertyValue::UInt8(value))
+        }
+        0x0004 => {
+            let (input, value) = le_i16(input)?;
+            (input, AsepritePropertyValue::Int16(value))
+        }
+        0x0005 => {
+            let (input, value) = le_u16(input)?;
+            (input, AsepritePropertyValue::UInt16(value))
+        }
+        0x0006 => {
+            let (input, value) = le_i32(input)?;
+            (input, AsepritePropertyValue::Int32(value))
+        }
+        0x0007 => {
+            let (input, value) = le_u32(input)?;
+            (input, AsepritePropertyValue::UInt32(value))
+        }
+        0x0008 => {
+            let (input, value) = le_i64(input)?;
+            (input, AsepritePropertyValue::Int64(value))
+        }
+        0x0009 => {
+            let (input, value) = le_u64(input)?;
+            (input, AsepritePropertyValue::UInt64(value))
+        }
+        0x000A => {
+            let (input, value) = le_i32(input)?;
+            (input, AsepritePropertyValue::Fixed(value as f64 / 65536.0))
+        }
+        0x000B => {
+            let (input, value) = le_f32(input)?;
+            (input, AsepritePropertyValue::Float(value))
+        }
+        0x000C => {
+            let (input, value) = le_f64(input)?;
+            (input, AsepritePropertyValue::Double(value))
+        }
+        0x000D => {
+            let (input, value) = aseprite_string(input)?;
+            (input, AsepritePropertyValue::String(value))
+        }
+        0x000E => {
+            let (input, x) = le_i32(input)?;
+            let (input, y) = le_i32(input)?;
+            (input, AsepritePropertyValue::Point { x, y })
+        }
+        0x000F => {
+            let (input, width) = le_i32(input)?;
+            let (input, height) = le_i32(input)?;
+            (input, AsepritePropertyValue::Size { width, height })
+        }
+        0x0010 => {
+            let (input, x) = le_i32(input)?;
+            let (input, y) = le_i32(input)?;
+            let (input, width) = le_i32(input)?;
+            let (input, height) = le_i32(input)?;
+            (
+                input,
+                AsepritePropertyValue::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                },
+            )
+        }
+        0x0011 => {
+            let (mut input, len) = le_u32(input)?;
+            let (rest, element_type) = le_u16(input)?;
+            input = rest;
+            let mut values = Vec::new();
+            for _ in 0..len {
+                // element type 为 0 时，每个元素各自带有类型
+                let (rest, value_type) = if element_type == 0 {
+                    le_u16(input)?
+                } else {
+                    (input, element_type)
+                };
+                let (rest, value) = aseprite_property_value(rest, value_type)?;
+                input = rest;
+                let stop = value.swallows_rest();
+                values.push(value);
+                if stop {
+                    break;
+                }
+            }
+            (input, AsepritePropertyValue::Vector(values))
+        }
+        0x0012 => {
+            let (input, len) = le_u32(input)?;
+            let (input, properties) = aseprite_properties(input, len)?;
+            (input, AsepritePropertyValue::Properties(properties))
+        }
+        0x0013 => {
+            let (input, uuid) = take(16usize)(input)?;
+            let mut bytes = [0; 16];
+            bytes.copy_from_slice(uuid);
+            (input, AsepritePropertyValue::Uuid(bytes))
+        }
+        type_id => (
+            &input[input.len()..],
+            AsepritePropertyValue::Unknown {
+                type_id,
+                bytes: input.to_vec(),
+            },
+        ),
+    })
+}
+
+fn aseprite_properties(
+    mut input: &[u8],
+    len: u32,
+) -> AseParseResult<'_, Vec<(String, AsepritePropertyValue)>> {
+    let mut properties = Vec::new();
+    for _ in 0..len {
+        let (rest, name) = aseprite_string(input)?;
+        let (rest, type_id) = le_u16(rest)?;
+        let (rest, value) = aseprite_property_value(rest, type_id)?;
+        input = rest;
+        let stop = value.swallows_rest();
+        properties.push((name, value));
+        if stop {
+            break;
+        }
+    }
+    Ok((input, properties))
+}
+
+fn aseprite_properties_maps(mut input: &[u8]) -> AseParseResult<'_, Vec<AsepritePropertiesMap>> {
+    let (rest, len) = le_u32(input)?;
+    input = rest;
+    let mut maps = Vec::new();
+    for _ in 0..len {
+        let (rest, key) = le_u32(input)?;
+        let (rest, properties_len) = le_u32(rest)?;
+        let (rest, properties) = aseprite_properties(rest, properties_len)?;
+        input = rest;
+        let stop = properties
+            .last()
+            .is_some_and(|(_, value)| value.swallows_rest());
+        maps.push(AsepritePropertiesMap { key, properties });
+        if stop {
+            break;
+        }
+    }
+    Ok((input, maps))
+}
+
+fn aseprite_user_data(input: &[u8]) -> AseParseResult<'_, RawAsepriteUserData> {
     let (input, kind) = le_u32(input)?;
 
     // + If flags have bit 1
@@ -233,6 +573,8 @@ fn aseprite_user_data(input: &[u8]) -> AseParseResult<RawAsepriteUserData> {
     let mut result = RawAsepriteUserData {
         text: String::new(),
         color: AsepriteColor::default(),
+        properties: Vec::new(),
+        properties_data: Vec::new(),
     };
 
     if let Some(text) = text {
@@ -243,11 +585,28 @@ fn aseprite_user_data(input: &[u8]) -> AseParseResult<RawAsepriteUserData> {
         result.color = color;
     }
 
+    // + If flags have bit 4
+    //   DWORD     Size in bytes of all properties maps stored in this chunk
+    //             The size includes the this field and the number of maps field
+    //   DWORD     Number of properties maps
+    //   + For each properties map: ...
+    let input = if kind & 0x4 != 0 {
+        let (_, size) = le_u32(input)?;
+        let (input, data) = take(size as usize)(input)?;
+        let (maps_data, _) = take(4usize)(data)?;
+        let (_, maps) = all_consuming(aseprite_properties_maps)(maps_data)?;
+        result.properties = maps;
+        result.properties_data = data.to_vec();
+        input
+    } else {
+        input
+    };
+
     Ok((input, result))
 }
 
 /// Layer type
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AsepriteLayerType {
     /// A normal layer
     Normal,
@@ -255,7 +614,7 @@ pub enum AsepriteLayerType {
     Group,
 }
 
-fn aseprite_layer_type(input: &[u8]) -> AseParseResult<AsepriteLayerType> {
+fn aseprite_layer_type(input: &[u8]) -> AseParseResult<'_, AsepriteLayerType> {
     let (input, layer_type) = le_u16(input)?;
 
     Ok((
@@ -297,7 +656,47 @@ pub enum AsepriteBlendMode {
     Divide,
 }
 
-fn aseprite_blend_mode(input: &[u8]) -> AseParseResult<AsepriteBlendMode> {
+impl AsepriteBlendMode {
+    /// The closest Canvas2D `globalCompositeOperation` for this blend mode
+    ///
+    /// Returns `None` for Addition, Subtract and Divide, which have no equivalent.
+    /// (`lighter` clamps differently than Addition, so it is not used.)
+    pub fn to_canvas_composite_op(&self) -> Option<&'static str> {
+        match self {
+            AsepriteBlendMode::Normal => Some("source-over"),
+            blend_mode => blend_mode.to_css_mix_blend_mode(),
+        }
+    }
+
+    /// The closest CSS `mix-blend-mode` for this blend mode
+    ///
+    /// Returns `None` for Addition, Subtract and Divide, which have no equivalent.
+    pub fn to_css_mix_blend_mode(&self) -> Option<&'static str> {
+        match self {
+            AsepriteBlendMode::Normal => Some("normal"),
+            AsepriteBlendMode::Multiply => Some("multiply"),
+            AsepriteBlendMode::Screen => Some("screen"),
+            AsepriteBlendMode::Overlay => Some("overlay"),
+            AsepriteBlendMode::Darken => Some("darken"),
+            AsepriteBlendMode::Lighten => Some("lighten"),
+            AsepriteBlendMode::ColorDodge => Some("color-dodge"),
+            AsepriteBlendMode::ColorBurn => Some("color-burn"),
+            AsepriteBlendMode::HardLight => Some("hard-light"),
+            AsepriteBlendMode::SoftLight => Some("soft-light"),
+            AsepriteBlendMode::Difference => Some("difference"),
+            AsepriteBlendMode::Exclusion => Some("exclusion"),
+            AsepriteBlendMode::Hue => Some("hue"),
+            AsepriteBlendMode::Saturation => Some("saturation"),
+            AsepriteBlendMode::Color => Some("color"),
+            AsepriteBlendMode::Luminosity => Some("luminosity"),
+            AsepriteBlendMode::Addition
+            | AsepriteBlendMode::Subtract
+            | AsepriteBlendMode::Divide => None,
+        }
+    }
+}
+
+fn aseprite_blend_mode(input: &[u8]) -> AseParseResult<'_, AsepriteBlendMode> {
     let (input, blend_mode) = le_u16(input)?;
 
     Ok((
@@ -331,12 +730,14 @@ fn aseprite_blend_mode(input: &[u8]) -> AseParseResult<AsepriteBlendMode> {
     ))
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// A single pixel
 pub enum AsepritePixel {
     /// Pixel in RGBA format
     RGBA(AsepriteColor),
     /// A grayscale pixel
+    ///
+    /// The file stores both values on 8 bits.
     Grayscale {
         /// Gray intensity
         intensity: u16,
@@ -355,18 +756,18 @@ impl AsepritePixel {
         transparent_palette: Option<u8>,
     ) -> AseResult<[u8; 4]> {
         match self {
-            AsepritePixel::RGBA(color) => Ok([color.red, color.green, color.blue, color.alpha]),
+            AsepritePixel::RGBA(color) => Ok((*color).into()),
             AsepritePixel::Grayscale { intensity, alpha } => Ok([
-                (*intensity / 2) as u8,
-                (*intensity / 2) as u8,
-                (*intensity / 2) as u8,
-                (*alpha / 2) as u8,
+                *intensity as u8,
+                *intensity as u8,
+                *intensity as u8,
+                *alpha as u8,
             ]),
             AsepritePixel::Indexed(idx) => {
                 if transparent_palette != Some(*idx) {
                     palette
                         .and_then(|palette| palette.entries.get(*idx as usize))
-                        .map(|color| [color.red, color.green, color.blue, color.alpha])
+                        .map(|color| (*color).into())
                         .ok_or(AsepriteError::InvalidConfiguration(
                             AsepriteInvalidError::InvalidPaletteIndex(*idx as usize),
                         ))
@@ -378,39 +779,109 @@ impl AsepritePixel {
     }
 }
 
-fn aseprite_pixel<'a>(
-    input: &'a [u8],
-    header: &'_ RawAsepriteHeader,
-) -> AseParseResult<'a, AsepritePixel> {
-    match header.color_depth {
-        AsepriteColorDepth::RGBA => {
-            let (input, color) = aseprite_color(input)?;
+/// The pixels of a cel, stored as in the file with the color depth kept once per cel
+///
+/// Every pixel takes 4 bytes (RGBA), 2 bytes (gray value and alpha) or 1 byte (palette
+/// index) depending on the color depth, instead of a whole [`AsepritePixel`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct PackedPixels {
+    color_depth: AsepriteColorDepth,
+    bytes: Vec<u8>,
+}
 
-            Ok((input, AsepritePixel::RGBA(color)))
-        }
-        AsepriteColorDepth::Grayscale => {
-            let (input, intensity) = le_u16(input)?;
-            let (input, alpha) = le_u16(input)?;
+impl std::fmt::Debug for PackedPixels {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PackedPixels")
+            .field("color_depth", &self.color_depth)
+            .field("len", &self.len())
+            .finish()
+    }
+}
 
-            Ok((input, AsepritePixel::Grayscale { intensity, alpha }))
+impl PackedPixels {
+    /// Wrap the bytes of pixels of the given color depth
+    ///
+    /// Returns `None` if the length is not a multiple of the size of a pixel.
+    pub fn new(color_depth: AsepriteColorDepth, bytes: Vec<u8>) -> Option<Self> {
+        if !bytes.len().is_multiple_of(color_depth.bytes_per_pixel()) {
+            return None;
         }
-        AsepriteColorDepth::Indexed => {
-            let (input, index) = le_u8(input)?;
+        Some(PackedPixels { color_depth, bytes })
+    }
 
-            Ok((input, AsepritePixel::Indexed(index)))
+    /// Pack pixels of the given color depth
+    ///
+    /// Returns `None` if a pixel has another color depth.
+    pub fn from_pixels(color_depth: AsepriteColorDepth, pixels: &[AsepritePixel]) -> Option<Self> {
+        let mut bytes = Vec::with_capacity(pixels.len() * color_depth.bytes_per_pixel());
+        for pixel in pixels {
+            match (color_depth, pixel) {
+                (AsepriteColorDepth::RGBA, AsepritePixel::RGBA(color)) => {
+                    bytes.extend_from_slice(&<[u8; 4]>::from(*color))
+                }
+                (AsepriteColorDepth::Grayscale, AsepritePixel::Grayscale { intensity, alpha }) => {
+                    bytes.extend_from_slice(&[*intensity as u8, *alpha as u8])
+                }
+                (AsepriteColorDepth::Indexed, AsepritePixel::Indexed(index)) => bytes.push(*index),
+                _ => return None,
+            }
         }
+        Some(PackedPixels { color_depth, bytes })
     }
-}
 
-fn aseprite_pixels<'a>(
-    input: &'a [u8],
-    header: &'_ RawAsepriteHeader,
-    amt: usize,
-) -> AseParseResult<'a, Vec<AsepritePixel>> {
-    count(|input: &'a [u8]| aseprite_pixel(input, header), amt)(input)
+    /// The color depth of the pixels
+    pub fn color_depth(&self) -> AsepriteColorDepth {
+        self.color_depth
+    }
+
+    /// The bytes of the pixels, in row-major order
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Amount of pixels
+    pub fn len(&self) -> usize {
+        self.bytes.len() / self.color_depth.bytes_per_pixel()
+    }
+
+    /// Whether there is no pixel
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Get a single pixel
+    pub fn get(&self, index: usize) -> Option<AsepritePixel> {
+        let size = self.color_depth.bytes_per_pixel();
+        let bytes = self.bytes.get(index * size..(index + 1) * size)?;
+        Some(Self::pixel(self.color_depth, bytes))
+    }
+
+    /// Iterate over the pixels
+    pub fn iter(&self) -> impl Iterator<Item = AsepritePixel> + '_ {
+        let color_depth = self.color_depth;
+        self.bytes
+            .chunks_exact(color_depth.bytes_per_pixel())
+            .map(move |bytes| Self::pixel(color_depth, bytes))
+    }
+
+    fn pixel(color_depth: AsepriteColorDepth, bytes: &[u8]) -> AsepritePixel {
+        match color_depth {
+            AsepriteColorDepth::RGBA => AsepritePixel::RGBA(AsepriteColor {
+                red: bytes[0],
+                green: bytes[1],
+                blue: bytes[2],
+                alpha: bytes[3],
+            }),
+            AsepriteColorDepth::Grayscale => AsepritePixel::Grayscale {
+                intensity: bytes[0] as u16,
+                alpha: bytes[1] as u16,
+            },
+            AsepriteColorDepth::Indexed => AsepritePixel::Indexed(bytes[0]),
+        }
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 /// Raw Cel
 pub enum RawAsepriteCel {
     /// Raw Cel Data
@@ -420,7 +891,7 @@ pub enum RawAsepriteCel {
         /// Height in pixels
         height: u16,
         /// The pixels themselves
-        pixels: Vec<AsepritePixel>,
+        pixels: PackedPixels,
     },
     /// Linked Cel Data
     Linked {
@@ -434,7 +905,38 @@ pub enum RawAsepriteCel {
         /// Height in pixels
         height: u16,
         /// The decompressed pixels
-        pixels: Vec<AsepritePixel>,
+        pixels: PackedPixels,
+    },
+    /// Compressed Cel Data that is only decompressed when its pixels are needed
+    ///
+    /// Produced when loading an [`Aseprite`](crate::Aseprite), so that only the cels
+    /// which are rendered get decompressed.
+    Deferred {
+        /// Width in pixels
+        width: u16,
+        /// Height in pixels
+        height: u16,
+        /// The compressed pixels
+        data: DeferredCelData,
+    },
+    /// Raw or compressed cel data whose pixels were not read
+    ///
+    /// Only produced when parsing with
+    /// [`ParseOptions::skip_pixels`](crate::ParseOptions::skip_pixels).
+    Skipped {
+        /// Width in pixels
+        width: u16,
+        /// Height in pixels
+        height: u16,
+    },
+    /// Raw or compressed cel data whose pixels were released
+    ///
+    /// Only produced by [`Aseprite::shrink_pixels`](crate::Aseprite::shrink_pixels).
+    Dropped {
+        /// Width in pixels
+        width: u16,
+        /// Height in pixels
+        height: u16,
     },
 }
 
@@ -465,20 +967,150 @@ impl std::fmt::Debug for RawAsepriteCel {
                 .field("height", &height)
                 .field("pixels_len", &pixels.len())
                 .finish(),
+            Self::Deferred {
+                width,
+                height,
+                data,
+            } => f
+                .debug_struct("RawAsepriteCel::Deferred")
+                .field("width", &width)
+                .field("height", &height)
+                .field("compressed_len", &data.data.len())
+                .finish(),
+            Self::Skipped { width, height } => f
+                .debug_struct("RawAsepriteCel::Skipped")
+                .field("width", &width)
+                .field("height", &height)
+                .finish(),
+            Self::Dropped { width, height } => f
+                .debug_struct("RawAsepriteCel::Dropped")
+                .field("width", &width)
+                .field("height", &height)
+                .finish(),
+        }
+    }
+}
+
+/// The zlib stream of a compressed cel, see [`RawAsepriteCel::Deferred`]
+#[derive(Clone, PartialEq)]
+pub struct DeferredCelData {
+    data: Vec<u8>,
+    color_depth: AsepriteColorDepth,
+    pixel_count: usize,
+    /// 解压失败时报告错误的位置
+    layer_index: u16,
+    frame_index: usize,
+}
+
+impl DeferredCelData {
+    /// Decompress the pixels of the cel
+    ///
+    /// Corrupted data is reported as [`AsepriteError::InvalidCelData`], like it would have been
+    /// when reading the file with [`read_aseprite`].
+    pub fn decompress(&self) -> Result<PackedPixels, AsepriteError> {
+        let expected = self.pixel_count * self.color_depth.bytes_per_pixel();
+        let bytes =
+            inflate_cel(&self.data, expected).map_err(|source| AsepriteError::InvalidCelData {
+                layer_index: self.layer_index as usize,
+                frame_index: self.frame_index,
+                source,
+            })?;
+        Ok(PackedPixels {
+            color_depth: self.color_depth,
+            bytes,
+        })
+    }
+
+    /// 压缩后的字节数
+    pub(crate) fn compressed_len(&self) -> usize {
+        self.data.len()
+    }
+}
+
+/// 解压 cel 的 zlib 数据，解压后的大小必须正好是 expected
+fn inflate_cel(input: &[u8], expected: usize) -> Result<Vec<u8>, AsepriteCelDataError> {
+    let mut pixel_data = vec![0; expected];
+
+    let mut zlib_decompressor = Decompress::new(true);
+    let status =
+        zlib_decompressor.decompress(input, &mut pixel_data, flate2::FlushDecompress::Finish)?;
+
+    match status {
+        flate2::Status::Ok | flate2::Status::BufError => {
+            return Err(AsepriteCelDataError::NotEnoughCompressedData);
         }
+        flate2::Status::StreamEnd => (),
     }
+
+    let decompressed = zlib_decompressor.total_out() as usize;
+    if decompressed != expected {
+        return Err(AsepriteCelDataError::SizeMismatch {
+            expected,
+            found: decompressed,
+        });
+    }
+    Ok(pixel_data)
 }
 
 fn aseprite_cel<'a>(
     input: &'a [u8],
     header: &'_ RawAsepriteHeader,
     cel_type: u16,
+    layer_index: u16,
+    frame_index: usize,
+    cel_pixels: CelPixels,
+    progress: &mut ParseProgress<'_>,
 ) -> AseParseResult<'a, RawAsepriteCel> {
+    let cel_data_error = |source| {
+        nom::Err::Failure(AsepriteParseError::InvalidCelData {
+            layer_index,
+            frame_index,
+            source,
+        })
+    };
+
+    match (cel_pixels, cel_type) {
+        (CelPixels::Skip, 0 | 2) => {
+            let (input, width) = le_u16(input)?;
+            let (input, height) = le_u16(input)?;
+            return Ok((
+                &input[input.len()..],
+                RawAsepriteCel::Skipped { width, height },
+            ));
+        }
+        (CelPixels::Defer, 2) => {
+            let (input, width) = le_u16(input)?;
+            let (input, height) = le_u16(input)?;
+            let data = DeferredCelData {
+                data: input.to_vec(),
+                color_depth: header.color_depth,
+                pixel_count: width as usize * height as usize,
+                layer_index,
+                frame_index,
+            };
+            return Ok((
+                &input[input.len()..],
+                RawAsepriteCel::Deferred {
+                    width,
+                    height,
+                    data,
+                },
+            ));
+        }
+        _ => {}
+    }
+
     match cel_type {
         0 => {
             let (input, width) = le_u16(input)?;
             let (input, height) = le_u16(input)?;
-            let (input, pixels) = aseprite_pixels(input, header, width as usize * height as usize)?;
+            let size = width as usize * height as usize * header.color_depth.bytes_per_pixel();
+            let (input, bytes) = take(size)(input)?;
+            progress.cel_done(size as u64);
+            let pixels = PackedPixels {
+                color_depth: header.color_depth,
+                bytes: bytes.to_vec(),
+            };
 
             Ok((
                 input,
@@ -504,28 +1136,15 @@ fn aseprite_cel<'a>(
             // assert_eq!(outer_width, width);
             // assert_eq!(outer_height, height);
 
-            let mut pixel_data =
-                vec![0; width as usize * height as usize * header.color_depth.bytes_per_pixel()];
-
-            let mut zlib_decompressor = Decompress::new(true);
-            let status = zlib_decompressor
-                .decompress(input, &mut pixel_data, flate2::FlushDecompress::Finish)
-                .map_err(|flate_err| {
-                    nom::Err::Failure(AsepriteParseError::InvalidCompressedData(flate_err))
-                })?;
-
-            match status {
-                flate2::Status::Ok | flate2::Status::BufError => {
-                    return Err(nom::Err::Failure(
-                        AsepriteParseError::NotEnoughCompressedData,
-                    ));
-                }
-                flate2::Status::StreamEnd => (),
-            }
+            let pixel_count = width as usize * height as usize;
+            let pixel_data = inflate_cel(input, pixel_count * header.color_depth.bytes_per_pixel())
+                .map_err(cel_data_error)?;
 
-            let (_, pixels) =
-                aseprite_pixels(&pixel_data, header, width as usize * height as usize)
-                    .map_err(|_| nom::Err::Failure(AsepriteParseError::InvalidCel))?;
+            progress.cel_done(pixel_data.len() as u64);
+            let pixels = PackedPixels {
+                color_depth: header.color_depth,
+                bytes: pixel_data,
+            };
 
             Ok((
                 &input[input.len()..],
@@ -542,8 +1161,9 @@ fn aseprite_cel<'a>(
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// Animation Direction
+#[non_exhaustive]
 pub enum AsepriteAnimationDirection {
     /// Forward animation direction
     ///
@@ -557,9 +1177,13 @@ pub enum AsepriteAnimationDirection {
     ///
     /// Starts at beginning and reverses direction whenever it hits either end or beginning
     PingPong,
+    /// Ping-Pong Reverse animation direction
+    ///
+    /// Starts at the end and reverses direction whenever it hits either beginning or end
+    PingPongReverse,
 }
 
-fn aseprite_anim_direction(input: &[u8]) -> AseParseResult<AsepriteAnimationDirection> {
+fn aseprite_anim_direction(input: &[u8]) -> AseParseResult<'_, AsepriteAnimationDirection> {
     let (input, dir) = le_u8(input)?;
 
     Ok((
@@ -568,6 +1192,7 @@ fn aseprite_anim_direction(input: &[u8]) -> AseParseResult<AsepriteAnimationDire
             0 => AsepriteAnimationDirection::Forward,
             1 => AsepriteAnimationDirection::Reverse,
             2 => AsepriteAnimationDirection::PingPong,
+            3 => AsepriteAnimationDirection::PingPongReverse,
             unknown => {
                 return Err(nom::Err::Failure(
                     AsepriteParseError::InvalidAnimationDirection(unknown),
@@ -586,15 +1211,20 @@ pub struct RawAsepriteTag {
     pub to: u16,
     /// animation direction
     pub anim_direction: AsepriteAnimationDirection,
+    /// How many times the tag is played, 0 means no limit
+    ///
+    /// Added in Aseprite 1.3, older files store 0 here.
+    pub repeat: u16,
     /// name of the tag
     pub name: String,
 }
 
-fn aseprite_tag(input: &[u8]) -> AseParseResult<RawAsepriteTag> {
+fn aseprite_tag(input: &[u8]) -> AseParseResult<'_, RawAsepriteTag> {
     let (input, from) = le_u16(input)?;
     let (input, to) = le_u16(input)?;
     let (input, anim_direction) = aseprite_anim_direction(input)?;
-    let (input, _) = take(8usize)(input)?;
+    let (input, repeat) = le_u16(input)?;
+    let (input, _) = take(6usize)(input)?;
     let (input, _) = take(3usize)(input)?;
     let (input, _) = take(1usize)(input)?;
     let (input, name) = aseprite_string(input)?;
@@ -605,6 +1235,7 @@ fn aseprite_tag(input: &[u8]) -> AseParseResult<RawAsepriteTag> {
             from,
             to,
             anim_direction,
+            repeat,
             name,
         },
     ))
@@ -630,7 +1261,9 @@ pub enum RawAsepriteChunkType {
     // /// UserData Chunk Type
     // UserData,
     /// Slice Chunk Type
-    Slice,
+    ///
+    /// 记录 slice 的索引，后面的 user data 属于这个 slice
+    Slice(usize),
     /// ColorProfile Chunk Type
     ColorProfile,
 }
@@ -672,6 +1305,8 @@ pub enum RawAsepriteChunk {
         opacity: u8,
         /// The name of the layer
         name: String,
+        /// The UUID of the layer, only present when the header flags have bit 4
+        uuid: Option<[u8; 16]>,
     },
     /// A Cel is a container of pixel
     Cel {
@@ -733,6 +1368,14 @@ pub enum RawAsepriteChunk {
         /// the individual slices
         slices: Vec<RawAsepriteSlice>,
     },
+    /// A deprecated chunk, only its type is kept
+    ///
+    /// Very old files may contain Mask (`0x2016`) and Path (`0x2017`) chunks, their content
+    /// is skipped.
+    Deprecated {
+        /// The chunk type as stored in the file
+        chunk_type: u16,
+    },
     /// An embedded color profile
     ColorProfile {
         /// The type of color profile
@@ -755,7 +1398,7 @@ pub struct RawAsepriteIccProfile {
     pub icc_profile: Vec<u8>,
 }
 
-fn aseprite_icc_profile(input: &[u8]) -> AseParseResult<RawAsepriteIccProfile> {
+fn aseprite_icc_profile(input: &[u8]) -> AseParseResult<'_, RawAsepriteIccProfile> {
     let (input, icc_profile) = length_data(le_u32)(input)?;
 
     Ok((
@@ -766,7 +1409,7 @@ fn aseprite_icc_profile(input: &[u8]) -> AseParseResult<RawAsepriteIccProfile> {
     ))
 }
 
-fn color_profile_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
+fn color_profile_chunk(input: &[u8]) -> AseParseResult<'_, RawAsepriteChunk> {
     let (input, profile_type) = le_u16(input)?;
     let (input, flags) = le_u16(input)?;
     let (input, gamma) = aseprite_fixed(input)?;
@@ -786,7 +1429,7 @@ fn color_profile_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
 }
 
 /// Raw Slice
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct RawAsepriteSlice {
     /// For which frame this slice is valid from (to the end of the animation)
     pub frame: u32,
@@ -806,7 +1449,7 @@ pub struct RawAsepriteSlice {
     pub pivot: Option<AsepritePivot>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// 9-Patch slice info
 pub struct AsepriteNinePatchInfo {
     /// x center, relative to slice bounds
@@ -819,7 +1462,7 @@ pub struct AsepriteNinePatchInfo {
     pub height: u32,
 }
 
-fn aseprite_nine_patch_info(input: &[u8]) -> AseParseResult<AsepriteNinePatchInfo> {
+fn aseprite_nine_patch_info(input: &[u8]) -> AseParseResult<'_, AsepriteNinePatchInfo> {
     let (input, x_center) = le_i32(input)?;
     let (input, y_center) = le_i32(input)?;
     let (input, width) = le_u32(input)?;
@@ -837,7 +1480,7 @@ fn aseprite_nine_patch_info(input: &[u8]) -> AseParseResult<AsepriteNinePatchInf
 }
 
 /// A raw pivot inside a slice
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AsepritePivot {
     /// x position, relative to origin
     pub x_pivot: i32,
@@ -845,14 +1488,14 @@ pub struct AsepritePivot {
     pub y_pivot: i32,
 }
 
-fn aseprite_pivot(input: &[u8]) -> AseParseResult<AsepritePivot> {
+fn aseprite_pivot(input: &[u8]) -> AseParseResult<'_, AsepritePivot> {
     let (input, x_pivot) = le_i32(input)?;
     let (input, y_pivot) = le_i32(input)?;
 
     Ok((input, AsepritePivot { x_pivot, y_pivot }))
 }
 
-fn aseprite_slice(input: &[u8], flags: u32) -> AseParseResult<RawAsepriteSlice> {
+fn aseprite_slice(input: &[u8], flags: u32) -> AseParseResult<'_, RawAsepriteSlice> {
     let (input, frame) = le_u32(input)?;
     let (input, x_origin) = le_i32(input)?;
     let (input, y_origin) = le_i32(input)?;
@@ -879,11 +1522,11 @@ fn aseprite_slices(
     input: &[u8],
     slice_count: usize,
     flags: u32,
-) -> AseParseResult<Vec<RawAsepriteSlice>> {
+) -> AseParseResult<'_, Vec<RawAsepriteSlice>> {
     count(|input| aseprite_slice(input, flags), slice_count)(input)
 }
 
-fn slice_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
+fn slice_chunk(input: &[u8]) -> AseParseResult<'_, RawAsepriteChunk> {
     let (input, slice_count) = le_u32(input)?;
     let (input, flags) = le_u32(input)?;
     let (input, _) = le_u32(input)?;
@@ -900,7 +1543,7 @@ fn slice_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
     ))
 }
 
-fn user_data_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
+fn user_data_chunk(input: &[u8]) -> AseParseResult<'_, RawAsepriteChunk> {
     let (input, data) = aseprite_user_data(input)?;
 
     Ok((input, RawAsepriteChunk::UserData { data }))
@@ -915,7 +1558,7 @@ pub struct RawAsepritePaletteEntry {
     pub name: Option<String>,
 }
 
-fn aseprite_palette(input: &[u8]) -> AseParseResult<RawAsepritePaletteEntry> {
+fn aseprite_palette(input: &[u8]) -> AseParseResult<'_, RawAsepritePaletteEntry> {
     let (input, flags) = le_u16(input)?;
     let (input, color) = aseprite_color(input)?;
 
@@ -927,17 +1570,23 @@ fn aseprite_palette(input: &[u8]) -> AseParseResult<RawAsepritePaletteEntry> {
 fn aseprite_palettes(
     input: &[u8],
     palette_count: usize,
-) -> AseParseResult<Vec<RawAsepritePaletteEntry>> {
+) -> AseParseResult<'_, Vec<RawAsepritePaletteEntry>> {
     count(aseprite_palette, palette_count)(input)
 }
 
-fn palette_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
+fn palette_chunk(input: &[u8]) -> AseParseResult<'_, RawAsepriteChunk> {
     let (input, palette_size) = le_u32(input)?;
     let (input, from_color) = le_u32(input)?;
     let (input, to_color) = le_u32(input)?;
     let (input, _) = take(8usize)(input)?;
 
-    let (input, entries) = aseprite_palettes(input, (to_color - from_color + 1) as usize)?;
+    let entry_count = to_color.checked_sub(from_color).ok_or(nom::Err::Failure(
+        AsepriteParseError::GenericNom {
+            input,
+            nom: nom::error::ErrorKind::Verify,
+        },
+    ))?;
+    let (input, entries) = aseprite_palettes(input, entry_count as usize + 1)?;
 
     Ok((
         input,
@@ -950,11 +1599,11 @@ fn palette_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
     ))
 }
 
-fn tags(input: &[u8], tag_count: u16) -> AseParseResult<Vec<RawAsepriteTag>> {
+fn tags(input: &[u8], tag_count: u16) -> AseParseResult<'_, Vec<RawAsepriteTag>> {
     count(aseprite_tag, tag_count as usize)(input)
 }
 
-fn tags_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
+fn tags_chunk(input: &[u8]) -> AseParseResult<'_, RawAsepriteChunk> {
     let (input, tag_count) = le_u16(input)?;
     let (input, _) = take(8usize)(input)?;
     let (input, tags) = tags(input, tag_count)?;
@@ -962,13 +1611,13 @@ fn tags_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
     Ok((input, RawAsepriteChunk::Tags { tags }))
 }
 
-fn aseprite_fixed(input: &[u8]) -> AseParseResult<f64> {
+fn aseprite_fixed(input: &[u8]) -> AseParseResult<'_, f64> {
     let (input, whole) = le_u32(input)?;
 
     Ok((input, whole as f64 / 0x10000 as f64))
 }
 
-fn cel_extra_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
+fn cel_extra_chunk(input: &[u8]) -> AseParseResult<'_, RawAsepriteChunk> {
     let (input, flags) = le_u32(input)?;
     let (input, x) = aseprite_fixed(input)?;
     let (input, y) = aseprite_fixed(input)?;
@@ -990,6 +1639,9 @@ fn cel_extra_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
 fn cel_chunk<'a>(
     input: &'a [u8],
     header: &'_ RawAsepriteHeader,
+    frame_index: usize,
+    progress: &mut ParseProgress<'_>,
+    visitor: &mut dyn AsepriteChunkVisitor,
 ) -> AseParseResult<'a, RawAsepriteChunk> {
     let (input, layer_index) = le_u16(input)?;
     let (input, x) = le_i16(input)?;
@@ -998,8 +1650,32 @@ fn cel_chunk<'a>(
     let (input, cel_type) = le_u16(input)?;
     let (input, z_index) = le_i16(input)?;
     let (input, _) = take(5usize)(input)?;
+    // raw 和 compressed cel 接下来是宽高，不消耗 input，让 visitor 先决定怎么处理像素
+    let size = match (cel_type, read_u16_at(input, 0), read_u16_at(input, 2)) {
+        (0 | 2, Some(width), Some(height)) => Some((width as u16, height as u16)),
+        _ => None,
+    };
+    let cel_pixels = visitor.on_cel_header(
+        frame_index,
+        &RawAsepriteCelHeader {
+            layer_index,
+            x,
+            y,
+            opacity,
+            z_index,
+            size,
+        },
+    );
     // We do not immediately try to load the cel, as the reserved bytes are decoupled from the type itself
-    let (input, cel) = aseprite_cel(input, header, cel_type)?;
+    let (input, cel) = aseprite_cel(
+        input,
+        header,
+        cel_type,
+        layer_index,
+        frame_index,
+        cel_pixels,
+        progress,
+    )?;
 
     Ok((
         input,
@@ -1014,7 +1690,10 @@ fn cel_chunk<'a>(
     ))
 }
 
-fn layer_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
+fn layer_chunk<'a>(
+    input: &'a [u8],
+    header: &'_ RawAsepriteHeader,
+) -> AseParseResult<'a, RawAsepriteChunk> {
     let (input, flags) = le_u16(input)?;
     let (input, layer_type) = aseprite_layer_type(input)?;
     let (input, layer_child) = le_u16(input)?;
@@ -1024,6 +1703,14 @@ fn layer_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
     let (input, opacity) = le_u8(input)?;
     let (input, _) = take(3usize)(input)?;
     let (input, name) = aseprite_string(input)?;
+    // + If file header flags have bit 4:
+    //   UUID      Layer's universally unique identifier
+    let (input, uuid) = cond(header.flags & 0x4 != 0, take(16usize))(input)?;
+    let uuid = uuid.map(|bytes| {
+        let mut uuid = [0; 16];
+        uuid.copy_from_slice(bytes);
+        uuid
+    });
 
     Ok((
         input,
@@ -1036,6 +1723,7 @@ fn layer_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
             blend_mode,
             opacity,
             name,
+            uuid,
         },
     ))
 }
@@ -1043,15 +1731,31 @@ fn layer_chunk(input: &[u8]) -> AseParseResult<RawAsepriteChunk> {
 fn aseprite_chunk<'a>(
     input: &'a [u8],
     header: &'_ RawAsepriteHeader,
+    frame_index: usize,
+    skim: bool,
+    progress: &mut ParseProgress<'_>,
+    visitor: &mut dyn AsepriteChunkVisitor,
 ) -> AseParseResult<'a, Option<RawAsepriteChunk>> {
     let input_len = input.len();
     let (input, chunk_size) = le_u32(input)?;
     let (input, chunk_type) = le_u16(input)?;
     // Get the remaining data of this chunk and parse it as the corresponding type
-    let (input, chunk_data) = take(chunk_size as usize - (input_len - input.len()))(input)?;
+    let chunk_data_size = (chunk_size as usize)
+        .checked_sub(input_len - input.len())
+        .ok_or(nom::Err::Failure(AsepriteParseError::GenericNom {
+            input,
+            nom: nom::error::ErrorKind::Verify,
+        }))?;
+    let (input, chunk_data) = take(chunk_data_size)(input)?;
 
     let _span = debug_span!("chunk", chunk_type);
 
+    // When skimming, only the chunks describing the document structure are parsed,
+    // everything else (most notably the cel pixels) is skipped using the chunk size
+    if skim && !matches!(chunk_type, 0x2004 | 0x2018 | 0x2022) {
+        return Ok((input, None));
+    }
+
     let res =
         match chunk_type {
             0x0004 => {
@@ -1062,23 +1766,26 @@ fn aseprite_chunk<'a>(
                 info!("Ignoring chunk of kind {} (Old palette chunk)", chunk_type);
                 None
             }
-            0x2004 => Some(all_consuming(layer_chunk)(chunk_data).map_err(|err| {
-                err.map(|err| AsepriteParseError::InvalidLayerChunk(Box::new(err)))
-            })?),
-            0x2005 => Some(
-                all_consuming(|input: &'a [u8]| cel_chunk(input, header))(chunk_data).map_err(
-                    |err| err.map(|err| AsepriteParseError::InvalidCelChunk(Box::new(err))),
+            0x2004 => Some(
+                all_consuming(|input: &'a [u8]| layer_chunk(input, header))(chunk_data).map_err(
+                    |err| err.map(|err| AsepriteParseError::InvalidLayerChunk(Box::new(err))),
                 )?,
             ),
+            0x2005 => Some(
+                all_consuming(|input: &'a [u8]| {
+                    cel_chunk(input, header, frame_index, progress, visitor)
+                })(chunk_data)
+                .map_err(|err| err.map(|err| AsepriteParseError::InvalidCelChunk(Box::new(err))))?,
+            ),
             0x2006 => Some(all_consuming(cel_extra_chunk)(chunk_data).map_err(|err| {
                 err.map(|err| AsepriteParseError::InvalidCelExtraChunk(Box::new(err)))
             })?),
             0x2007 => Some(color_profile_chunk(chunk_data).map_err(|err| {
                 err.map(|err| AsepriteParseError::InvalidColorProfileChunk(Box::new(err)))
             })?),
-            0x2016 => {
-                info!("Got a deprecated profile chunk");
-                None
+            0x2016 | 0x2017 => {
+                info!("Skipping deprecated chunk of kind {}", chunk_type);
+                Some((chunk_data, RawAsepriteChunk::Deprecated { chunk_type }))
             }
             0x2018 => Some(all_consuming(tags_chunk)(chunk_data).map_err(|err| {
                 err.map(|err| AsepriteParseError::InvalidTagsChunk(Box::new(err)))
@@ -1106,8 +1813,20 @@ const ASEPRITE_FRAME_MAGIC_NUMBER: u16 = 0xF1FA;
 fn aseprite_frame<'a>(
     input: &'a [u8],
     header: &'_ RawAsepriteHeader,
-) -> AseParseResult<'a, RawAsepriteFrame> {
-    let (input, magic_number) = tag(&ASEPRITE_FRAME_MAGIC_NUMBER.to_le_bytes())(input)?;
+    frame_index: usize,
+    skim: bool,
+    progress: &mut ParseProgress<'_>,
+    visitor: &mut dyn AsepriteChunkVisitor,
+) -> AseParseResult<'a, ()> {
+    let (input, magic_number) = le_u16(input)?;
+    if magic_number != ASEPRITE_FRAME_MAGIC_NUMBER {
+        return Err(nom::Err::Failure(
+            AsepriteParseError::InvalidFrameMagicNumber {
+                frame_index,
+                magic_number,
+            },
+        ));
+    }
     let (input, small_chunk_count) = le_u16(input)?;
     let (input, duration_ms) = le_u16(input)?;
     let (input, _) = take(2usize)(input)?;
@@ -1120,37 +1839,48 @@ fn aseprite_frame<'a>(
         chunk_count as usize
     };
 
-    let (input, chunks) = count(
-        |input: &'a [u8]| aseprite_chunk(input, header),
-        actual_count,
-    )(input)?;
-
-    let chunks = chunks.into_iter().flatten().collect();
-
-    Ok((
-        input,
-        RawAsepriteFrame {
-            magic_number: u16::from_le_bytes(
-                magic_number
-                    .try_into()
-                    .expect("Invalid ASEPRITE_FRAME_MAGIC_NUMBER matched. This is a bug."),
-            ),
+    visitor.on_frame(
+        frame_index,
+        &RawAsepriteFrameHeader {
+            magic_number,
             duration_ms,
-            chunks,
+            chunk_count: actual_count,
         },
-    ))
+    );
+    let mut input = input;
+    for _ in 0..actual_count {
+        let (rest, chunk) = aseprite_chunk(input, header, frame_index, skim, progress, visitor)?;
+        if let Some(chunk) = chunk {
+            visitor.on_chunk(frame_index, chunk);
+        }
+        input = rest;
+    }
+
+    Ok((input, ()))
 }
 
 fn aseprite_frames<'a>(
     input: &'a [u8],
     header: &'_ RawAsepriteHeader,
-) -> AseParseResult<'a, Vec<RawAsepriteFrame>> {
-    all_consuming(many1(
-        |input: &'a [u8]| -> AseParseResult<RawAsepriteFrame> {
-            let (input, _length) = le_u32(input)?;
-            aseprite_frame(input, header)
-        },
-    ))(input)
+    skim: bool,
+    progress: &mut ParseProgress<'_>,
+    visitor: &mut dyn AsepriteChunkVisitor,
+) -> AseParseResult<'a, ()> {
+    let mut frame_index = 0;
+    let (input, _) = all_consuming(many1(move |input: &'a [u8]| -> AseParseResult<'a, ()> {
+        let (input, _length) = le_u32(input)?;
+        // 多出来的帧没有算进 header 的帧数，后面按帧数处理的地方都不会考虑它们
+        if frame_index >= header.frames as usize {
+            return Err(nom::Err::Failure(AsepriteParseError::TooManyFrames {
+                declared: header.frames,
+            }));
+        }
+        let (input, ()) = aseprite_frame(input, header, frame_index, skim, progress, visitor)?;
+        frame_index += 1;
+        progress.frame_done(frame_index);
+        Ok((input, ()))
+    }))(input)?;
+    Ok((input, ()))
 }
 
 /// A raw .aseprite file
@@ -1162,16 +1892,521 @@ pub struct RawAseprite {
     pub frames: Vec<RawAsepriteFrame>,
 }
 
-fn aseprite(input: &[u8]) -> AseParseResult<RawAseprite> {
+fn aseprite<'a>(
+    input: &'a [u8],
+    skim: bool,
+    progress: &mut ParseProgress<'_>,
+    mut builder: RawAsepriteBuilder,
+) -> AseParseResult<'a, RawAseprite> {
     let (input, header) = aseprite_header(input)?;
-    let (input, frames) = aseprite_frames(input, &header)?;
+    let (input, ()) = aseprite_frames(input, &header, skim, progress, &mut builder)?;
+
+    Ok((
+        input,
+        RawAseprite {
+            header,
+            frames: builder.frames,
+        },
+    ))
+}
 
-    Ok((input, RawAseprite { header, frames }))
+/// Keeps track of the parsing progress for [`read_aseprite_with_progress`]
+///
+/// Without a callback, this only costs a few additions per frame and cel.
+#[derive(Default)]
+pub(crate) struct ParseProgress<'p> {
+    callback: Option<&'p mut dyn FnMut(LoadPhase, f32)>,
+    total_frames: usize,
+    total_cel_bytes: u64,
+    cel_bytes: u64,
+    cels: usize,
+}
+
+/// 只读取部分 cel 的像素，其他 image cel 都当作 [`CelPixels::Skip`]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CelFilter {
+    /// 每一帧是否读取，为 None 时读取所有帧
+    pub(crate) frames: Option<Vec<bool>>,
+    /// 每个 layer 是否读取，为 None 时读取所有 layer
+    pub(crate) layers: Option<Vec<bool>>,
+}
+
+impl CelFilter {
+    fn loads(&self, layer_index: usize, frame_index: usize) -> bool {
+        let selected = |filter: &Option<Vec<bool>>, index: usize| {
+            filter
+                .as_ref()
+                .is_none_or(|selected| selected.get(index).copied().unwrap_or(false))
+        };
+        selected(&self.frames, frame_index) && selected(&self.layers, layer_index)
+    }
+}
+
+/// What happens to the pixels of a cel, see [`AsepriteChunkVisitor::on_cel_header`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CelPixels {
+    /// Decompress the pixels while reading the cel
+    #[default]
+    Decode,
+    /// Keep compressed pixels as [`RawAsepriteCel::Deferred`], to decompress them later
+    Defer,
+    /// Only read the size of the cel, giving [`RawAsepriteCel::Skipped`]
+    Skip,
+}
+
+/// The header of a frame, see [`AsepriteChunkVisitor::on_frame`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawAsepriteFrameHeader {
+    /// The magic frame number, always `0xF1FA`
+    pub magic_number: u16,
+    /// Duration of this frame, in ms
+    pub duration_ms: u16,
+    /// Number of chunks in this frame
+    pub chunk_count: usize,
+}
+
+/// The fields of a cel chunk before its content, see [`AsepriteChunkVisitor::on_cel_header`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawAsepriteCelHeader {
+    /// Which layer this cel corresponds to (0 based)
+    pub layer_index: u16,
+    /// x position
+    pub x: i16,
+    /// y position
+    pub y: i16,
+    /// Opacity of the cel
+    pub opacity: u8,
+    /// Z-Index
+    pub z_index: i16,
+    /// Width and height of raw and compressed cels, `None` for linked cels
+    pub size: Option<(u16, u16)>,
+}
+
+/// Callbacks receiving the content of a file, see [`visit_chunks`]
+///
+/// Every method does nothing by default, only implement the ones you need.
+#[allow(unused_variables)]
+pub trait AsepriteChunkVisitor {
+    /// The file header, before any frame
+    fn on_header(&mut self, header: &RawAsepriteHeader) {}
+
+    /// The start of a frame, its chunks follow
+    fn on_frame(&mut self, frame_index: usize, header: &RawAsepriteFrameHeader) {}
+
+    /// A cel chunk is about to be read, returns what to do with its pixels
+    ///
+    /// Pixels are skipped by default, return [`CelPixels::Decode`] to get them in the
+    /// [`RawAsepriteChunk::Cel`] given to [`AsepriteChunkVisitor::on_chunk`].
+    fn on_cel_header(&mut self, frame_index: usize, header: &RawAsepriteCelHeader) -> CelPixels {
+        CelPixels::Skip
+    }
+
+    /// A chunk of the current frame, in the order of the file
+    ///
+    /// Layers, cels, tags and every other known chunk arrive here, old palette and unknown
+    /// chunks are left out.
+    fn on_chunk(&mut self, frame_index: usize, chunk: RawAsepriteChunk) {}
+}
+
+/// 每帧按 chunk 数预分配的上限
+const MAX_PREALLOCATED_CHUNKS: usize = 256;
+
+/// 把 chunk 收集成 [`RawAseprite`] 的 visitor，[`read_aseprite`] 等都通过它读取
+#[derive(Default)]
+struct RawAsepriteBuilder {
+    cel_pixels: CelPixels,
+    cel_filter: CelFilter,
+    frames: Vec<RawAsepriteFrame>,
+}
+
+impl AsepriteChunkVisitor for RawAsepriteBuilder {
+    fn on_frame(&mut self, _frame_index: usize, header: &RawAsepriteFrameHeader) {
+        self.frames.push(RawAsepriteFrame {
+            magic_number: header.magic_number,
+            duration_ms: header.duration_ms,
+            // chunk 数来自文件，不可信，只按上限预分配
+            chunks: Vec::with_capacity(header.chunk_count.min(MAX_PREALLOCATED_CHUNKS)),
+        });
+    }
+
+    fn on_cel_header(&mut self, frame_index: usize, header: &RawAsepriteCelHeader) -> CelPixels {
+        if self
+            .cel_filter
+            .loads(header.layer_index as usize, frame_index)
+        {
+            self.cel_pixels
+        } else {
+            CelPixels::Skip
+        }
+    }
+
+    fn on_chunk(&mut self, _frame_index: usize, chunk: RawAsepriteChunk) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.chunks.push(chunk);
+        }
+    }
+}
+
+impl ParseProgress<'_> {
+    /// 每解压这么多个 cel 才通知一次
+    const CELS_PER_REPORT: usize = 16;
+
+    fn report(&mut self, phase: LoadPhase, done: u64, total: u64) {
+        if let Some(callback) = &mut self.callback {
+            let fraction = if total == 0 {
+                1.0
+            } else {
+                (done as f64 / total as f64).min(1.0) as f32
+            };
+            callback(phase, fraction);
+        }
+    }
+
+    fn frame_done(&mut self, frames: usize) {
+        self.report(LoadPhase::Frames, frames as u64, self.total_frames as u64);
+    }
+
+    fn cel_done(&mut self, bytes: u64) {
+        self.cel_bytes += bytes;
+        self.cels += 1;
+        if self.cels.is_multiple_of(Self::CELS_PER_REPORT) {
+            self.report(LoadPhase::Cels, self.cel_bytes, self.total_cel_bytes);
+        }
+    }
+
+    fn finish(&mut self) {
+        self.report(LoadPhase::Frames, 1, 1);
+        self.report(LoadPhase::Cels, 1, 1);
+    }
+}
+
+/// Cheaply check whether a buffer looks like an .aseprite file
+///
+/// Only the header size and magic numbers are checked, useful to pre-filter files
+/// before fully parsing them.
+pub fn sniff(input: &[u8]) -> bool {
+    let magic_at = |offset: usize| u16::from_le_bytes([input[offset], input[offset + 1]]);
+    // 128 bytes header, followed by the frame size and frame magic number
+    if input.len() < 128 || magic_at(4) != ASEPRITE_MAGIC_NUMBER {
+        return false;
+    }
+    input.len() < 134 || magic_at(132) == ASEPRITE_FRAME_MAGIC_NUMBER
 }
 
 /// Read a [`RawAseprite`] from memory
 pub fn read_aseprite(input: &[u8]) -> Result<RawAseprite, AsepriteError> {
-    let (_, ase) = aseprite(input).finish()?;
+    if !sniff(input) {
+        return Err(AsepriteError::NotAnAsepriteFile);
+    }
+    let (_, ase) = aseprite(
+        input,
+        false,
+        &mut ParseProgress::default(),
+        RawAsepriteBuilder::default(),
+    )
+    .finish()?;
+
+    Ok(ase)
+}
+
+/// Read a file without building a [`RawAseprite`], giving its content to the visitor
+///
+/// The visitor receives the header, then every frame followed by its chunks, in the order
+/// of the file. Cel pixels are only read when [`AsepriteChunkVisitor::on_cel_header`] asks
+/// for them. [`read_aseprite`] is built on this.
+pub fn visit_chunks<V: AsepriteChunkVisitor>(input: &[u8], visitor: &mut V) -> AseResult<()> {
+    if !sniff(input) {
+        return Err(AsepriteError::NotAnAsepriteFile);
+    }
+    let (body, header) = aseprite_header(input).finish()?;
+    visitor.on_header(&header);
+    aseprite_frames(body, &header, false, &mut ParseProgress::default(), visitor).finish()?;
+
+    Ok(())
+}
+
+/// Like [`read_aseprite`], reporting the progress to the given callback
+pub(crate) fn read_aseprite_with_progress(
+    input: &[u8],
+    callback: &mut dyn FnMut(LoadPhase, f32),
+) -> Result<RawAseprite, AsepriteError> {
+    callback(LoadPhase::Header, 0.0);
+    let header = read_header(input)?;
+    callback(LoadPhase::Header, 1.0);
+
+    let mut progress = ParseProgress {
+        total_frames: header.frames as usize,
+        total_cel_bytes: declared_cel_bytes(input, &header),
+        callback: Some(callback),
+        ..Default::default()
+    };
+    let (_, ase) = aseprite(input, false, &mut progress, RawAsepriteBuilder::default()).finish()?;
+    progress.finish();
+
+    Ok(ase)
+}
+
+/// Read only the [`RawAsepriteHeader`] from memory, without looking at any frame
+pub fn read_header(input: &[u8]) -> Result<RawAsepriteHeader, AsepriteError> {
+    if !sniff(input) {
+        return Err(AsepriteError::NotAnAsepriteFile);
+    }
+    let (_, header) = aseprite_header(input).finish()?;
+
+    Ok(header)
+}
+
+/// Sum of the pixel bytes declared by every image cel, without decompressing anything
+///
+/// Walking stops at the first malformed frame or chunk, reporting those is left to the parser.
+pub(crate) fn declared_cel_bytes(input: &[u8], header: &RawAsepriteHeader) -> u64 {
+    let bytes_per_pixel = header.color_depth.bytes_per_pixel() as u64;
+
+    let mut total = 0;
+    walk_cel_chunks(input, |_, chunk| {
+        // then width and height for the raw and compressed cels
+        if matches!(read_u16_at(chunk, 13), Some(0 | 2)) {
+            if let (Some(width), Some(height)) = (read_u16_at(chunk, 22), read_u16_at(chunk, 24)) {
+                total += width as u64 * height as u64 * bytes_per_pixel;
+            }
+        }
+    });
+    total
+}
+
+/// Frames holding the pixels of a linked cel of one of the `frames`, without parsing anything
+pub(crate) fn linked_cel_sources(input: &[u8], frames: &[bool]) -> Vec<usize> {
+    let mut sources = Vec::new();
+    walk_cel_chunks(input, |frame_index, chunk| {
+        if !frames.get(frame_index).copied().unwrap_or(false) {
+            return;
+        }
+        // then the frame position for linked cels
+        if read_u16_at(chunk, 13) == Some(1) {
+            if let Some(frame_position) = read_u16_at(chunk, 22) {
+                sources.push(frame_position);
+            }
+        }
+    });
+    sources
+}
+
+/// 用声明的大小遍历每一帧的 cel chunk，遇到格式有误的帧或 chunk 就停止
+///
+/// chunk 从 chunk header (6 bytes) 开始，之后是 layer index, x, y, opacity, cel type,
+/// z-index 和 reserved
+fn walk_cel_chunks<F: FnMut(usize, &[u8])>(input: &[u8], mut visit: F) {
+    for (frame_index, frame) in layout(input).frames.iter().enumerate() {
+        for chunk in &frame.chunks {
+            if chunk.chunk_type == 0x2005 {
+                visit(frame_index, &input[chunk.offset..chunk.offset + chunk.size]);
+            }
+        }
+    }
+}
+
+/// Where the frames and chunks of a file are, see [`scan_layout`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileLayout {
+    /// Size of the file header, frames start right after it
+    pub header_size: usize,
+    /// The frames, in the order of the file
+    pub frames: Vec<FrameLayout>,
+    /// Why scanning stopped before the end of the file, `None` if the whole file was scanned
+    pub error: Option<AsepriteLayoutError>,
+}
+
+/// Where a frame and its chunks are, see [`scan_layout`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameLayout {
+    /// Start of the frame header, in bytes from the start of the file
+    pub offset: usize,
+    /// Size of the frame including its header, as declared in the file
+    pub size: usize,
+    /// The chunks of the frame
+    pub chunks: Vec<ChunkLayout>,
+}
+
+/// Where a chunk is, see [`scan_layout`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkLayout {
+    /// The chunk type as stored in the file, e.g. `0x2005` for a cel
+    pub chunk_type: u16,
+    /// Start of the chunk header, in bytes from the start of the file
+    pub offset: usize,
+    /// Size of the chunk including its header, as declared in the file
+    pub size: usize,
+}
+
+impl ChunkLayout {
+    /// Name of the chunk type as used by the file format specification
+    pub fn type_name(&self) -> &'static str {
+        match self.chunk_type {
+            0x0004 => "Old palette chunk (0x0004)",
+            0x0011 => "Old palette chunk (0x0011)",
+            0x2004 => "Layer Chunk",
+            0x2005 => "Cel Chunk",
+            0x2006 => "Cel Extra Chunk",
+            0x2007 => "Color Profile Chunk",
+            0x2008 => "External Files Chunk",
+            0x2016 => "Mask Chunk",
+            0x2017 => "Path Chunk",
+            0x2018 => "Tags Chunk",
+            0x2019 => "Palette Chunk",
+            0x2020 => "User Data Chunk",
+            0x2022 => "Slice Chunk",
+            0x2023 => "Tileset Chunk",
+            _ => "Unknown Chunk",
+        }
+    }
+}
+
+/// Find the frames and chunks of a file without parsing or decompressing them
+///
+/// Only the declared sizes and the frame magic numbers are read. When the file is truncated
+/// or a size is invalid, the layout found so far is returned with [`FileLayout::error`]
+/// saying where scanning stopped. A frame going past the end of the file is still listed,
+/// with the chunks which fit in the file.
+pub fn scan_layout(input: &[u8]) -> AseResult<FileLayout> {
+    if !sniff(input) {
+        return Err(AsepriteError::NotAnAsepriteFile);
+    }
+    Ok(layout(input))
+}
+
+/// [`scan_layout`]，不检查文件头
+fn layout(input: &[u8]) -> FileLayout {
+    // 128 bytes of file header, then every frame starts with 16 bytes of frame header
+    const HEADER_SIZE: usize = 128;
+    const FRAME_HEADER_SIZE: usize = 16;
+    const CHUNK_HEADER_SIZE: usize = 6;
+
+    let mut layout = FileLayout {
+        header_size: HEADER_SIZE,
+        frames: Vec::new(),
+        error: None,
+    };
+    let mut frame_offset = HEADER_SIZE;
+    while frame_offset < input.len() {
+        let frame_index = layout.frames.len();
+        let (Some(size), Some(magic_number)) = (
+            read_u32_at(input, frame_offset),
+            read_u16_at(input, frame_offset + 4),
+        ) else {
+            layout.error = Some(AsepriteLayoutError::TruncatedFrameHeader {
+                frame_index,
+                offset: frame_offset,
+            });
+            break;
+        };
+        if magic_number as u16 != ASEPRITE_FRAME_MAGIC_NUMBER {
+            layout.error = Some(AsepriteLayoutError::BadFrameMagic {
+                frame_index,
+                offset: frame_offset,
+                magic_number: magic_number as u16,
+            });
+            break;
+        }
+        if size < FRAME_HEADER_SIZE {
+            layout.error = Some(AsepriteLayoutError::InvalidFrameSize {
+                frame_index,
+                offset: frame_offset,
+                size,
+            });
+            break;
+        }
+        // 帧超出文件时只看文件中剩下的部分
+        let frame_end = frame_offset.saturating_add(size);
+        let available_end = frame_end.min(input.len());
+        let mut frame = FrameLayout {
+            offset: frame_offset,
+            size,
+            chunks: Vec::new(),
+        };
+        let mut chunk_offset = frame_offset + FRAME_HEADER_SIZE;
+        while chunk_offset < available_end {
+            // 文件在 chunk header 中间结束时也当作大小无效
+            let chunk_size = read_u32_at(input, chunk_offset).unwrap_or(0);
+            let chunk_end = chunk_offset.saturating_add(chunk_size);
+            if chunk_size < CHUNK_HEADER_SIZE || chunk_end > available_end {
+                layout.error = Some(AsepriteLayoutError::InvalidChunkSize {
+                    frame_index,
+                    chunk_index: frame.chunks.len(),
+                    offset: chunk_offset,
+                    size: chunk_size,
+                });
+                break;
+            }
+            frame.chunks.push(ChunkLayout {
+                chunk_type: read_u16_at(input, chunk_offset + 4).unwrap_or_default() as u16,
+                offset: chunk_offset,
+                size: chunk_size,
+            });
+            chunk_offset = chunk_end;
+        }
+        layout.frames.push(frame);
+        if layout.error.is_none() && frame_end > input.len() {
+            layout.error = Some(AsepriteLayoutError::InvalidFrameSize {
+                frame_index,
+                offset: frame_offset,
+                size,
+            });
+        }
+        if layout.error.is_some() {
+            break;
+        }
+        frame_offset = frame_end;
+    }
+    layout
+}
+
+fn read_u16_at(data: &[u8], at: usize) -> Option<usize> {
+    data.get(at..at + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+}
+
+fn read_u32_at(data: &[u8], at: usize) -> Option<usize> {
+    data.get(at..at + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
+
+/// Read a [`RawAseprite`] keeping only the layer, tags and slice chunks
+///
+/// All other chunks are skipped using their declared size, so no pixel data is decompressed.
+pub(crate) fn skim_aseprite(input: &[u8]) -> Result<RawAseprite, AsepriteError> {
+    if !sniff(input) {
+        return Err(AsepriteError::NotAnAsepriteFile);
+    }
+    let (_, ase) = aseprite(
+        input,
+        true,
+        &mut ParseProgress::default(),
+        RawAsepriteBuilder::default(),
+    )
+    .finish()?;
+
+    Ok(ase)
+}
+
+/// Read a [`RawAseprite`], choosing what happens to the pixels of the cels
+///
+/// With [`CelPixels::Defer`] compressed cels are stored as [`RawAsepriteCel::Deferred`], with
+/// [`CelPixels::Skip`] image cels are stored as [`RawAsepriteCel::Skipped`]. Image cels
+/// left out by `cel_filter` are always skipped.
+pub(crate) fn read_aseprite_with(
+    input: &[u8],
+    cel_pixels: CelPixels,
+    cel_filter: CelFilter,
+) -> Result<RawAseprite, AsepriteError> {
+    if !sniff(input) {
+        return Err(AsepriteError::NotAnAsepriteFile);
+    }
+    let builder = RawAsepriteBuilder {
+        cel_pixels,
+        cel_filter,
+        ..Default::default()
+    };
+    let (_, ase) = aseprite(input, false, &mut ParseProgress::default(), builder).finish()?;
 
     Ok(ase)
 }
@@ -1179,7 +2414,33 @@ pub fn read_aseprite(input: &[u8]) -> Result<RawAseprite, AsepriteError> {
 #[cfg(test)]
 #[allow(deprecated)]
 mod test {
-    use super::{aseprite_frames, aseprite_header, ASEPRITE_MAGIC_NUMBER, RawAsepriteHeader};
+    use super::{
+        aseprite_anim_direction, aseprite_frames, aseprite_header, aseprite_tag,
+        aseprite_user_data, AsepriteAnimationDirection, AsepriteChunkVisitor, AsepriteColor,
+        AsepriteColorDepth, AsepritePixel, AsepritePropertyValue, CelPixels, PackedPixels,
+        RawAsepriteBuilder, RawAsepriteCel, RawAsepriteCelHeader, RawAsepriteChunk,
+        RawAsepriteFrameHeader, RawAsepriteHeader, ASEPRITE_MAGIC_NUMBER,
+    };
+    use crate::error::{
+        AsepriteCelDataError, AsepriteError, AsepriteHexColorError, AsepriteInvalidError,
+        AsepriteLayoutError,
+    };
+    use std::convert::TryInto;
+
+    /// Offset of the first cel chunk in the first frame of the file
+    fn first_cel_chunk_offset(ase_file: &[u8]) -> usize {
+        // 128 bytes of file header, 16 bytes of frame header
+        let mut offset = 128 + 16;
+        loop {
+            let size = u32::from_le_bytes(ase_file[offset..offset + 4].try_into().unwrap());
+            let chunk_type =
+                u16::from_le_bytes(ase_file[offset + 4..offset + 6].try_into().unwrap());
+            if chunk_type == 0x2005 {
+                return offset;
+            }
+            offset += size as usize;
+        }
+    }
 
     #[test]
     fn check_valid_file_header() {
@@ -1215,12 +2476,446 @@ mod test {
 
         let (body, raw_header) = aseprite_header(&ase_file).unwrap();
 
-        let (rest, raw_body) = aseprite_frames(body, &raw_header).unwrap();
+        let mut builder = RawAsepriteBuilder::default();
+        let (rest, ()) = aseprite_frames(
+            body,
+            &raw_header,
+            false,
+            &mut Default::default(),
+            &mut builder,
+        )
+        .unwrap();
 
         assert_eq!(rest.len(), 0);
-        assert_eq!(raw_body.len(), 1);
-        let frame = &raw_body[0];
+        assert_eq!(builder.frames.len(), 1);
+        let frame = &builder.frames[0];
 
         assert_eq!(frame.duration_ms, 125);
     }
+
+    #[test]
+    fn check_sniff() {
+        let ase_file = std::fs::read("./tests/test_cases/simple.aseprite").unwrap();
+        let png_file = std::fs::read("./tests/test_cases/images/complex_BG1.png").unwrap();
+
+        assert!(super::sniff(&ase_file));
+        assert!(!super::sniff(&png_file));
+        assert!(!super::sniff(&[]));
+
+        assert!(matches!(
+            super::read_aseprite(&png_file),
+            Err(AsepriteError::NotAnAsepriteFile)
+        ));
+        assert!(matches!(
+            super::read_aseprite(&[]),
+            Err(AsepriteError::NotAnAsepriteFile)
+        ));
+
+        // 破坏第二帧的 magic number
+        let ase_file = std::fs::read("./tests/test_cases/multiple_frames_layers.aseprite").unwrap();
+        let first_frame_size = u32::from_le_bytes(ase_file[128..132].try_into().unwrap()) as usize;
+        let mut broken = ase_file;
+        broken[128 + first_frame_size + 4] = 0;
+        assert!(matches!(
+            super::read_aseprite(&broken),
+            Err(AsepriteError::InvalidConfiguration(
+                AsepriteInvalidError::BadFrameMagic { frame_index: 1 }
+            ))
+        ));
+    }
+
+    #[test]
+    fn check_deferred_cel_data() {
+        let ase_file = std::fs::read("./tests/test_cases/simple.aseprite").unwrap();
+        let first_cel = |raw: super::RawAseprite| {
+            raw.frames
+                .into_iter()
+                .flat_map(|frame| frame.chunks)
+                .find_map(|chunk| match chunk {
+                    RawAsepriteChunk::Cel { cel, .. } => Some(cel),
+                    _ => None,
+                })
+                .unwrap()
+        };
+
+        let RawAsepriteCel::Compressed { pixels, .. } =
+            first_cel(super::read_aseprite(&ase_file).unwrap())
+        else {
+            unreachable!()
+        };
+        let RawAsepriteCel::Deferred {
+            width,
+            height,
+            data,
+        } = first_cel(
+            super::read_aseprite_with(&ase_file, CelPixels::Defer, Default::default()).unwrap(),
+        )
+        else {
+            unreachable!()
+        };
+        assert_eq!((width, height), (123, 456));
+        assert_eq!(data.decompress().unwrap(), pixels);
+
+        // 损坏的数据在解压时才报错
+        let cel_data_offset = first_cel_chunk_offset(&ase_file) + 6 + 16 + 4;
+        let mut flipped = ase_file;
+        flipped[cel_data_offset + 8] ^= 0xFF;
+        let RawAsepriteCel::Deferred { data, .. } = first_cel(
+            super::read_aseprite_with(&flipped, CelPixels::Defer, Default::default()).unwrap(),
+        ) else {
+            unreachable!()
+        };
+        assert!(matches!(
+            data.decompress(),
+            Err(AsepriteError::InvalidCelData {
+                layer_index: 0,
+                frame_index: 0,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn check_corrupted_cel_data() {
+        let ase_file = std::fs::read("./tests/test_cases/simple.aseprite").unwrap();
+        // chunk header (6 bytes) + cel header (16 bytes) + width and height (4 bytes)
+        let cel_data_offset = first_cel_chunk_offset(&ase_file) + 6 + 16 + 4;
+
+        let mut flipped = ase_file.clone();
+        flipped[cel_data_offset + 8] ^= 0xFF;
+        assert!(matches!(
+            super::read_aseprite(&flipped),
+            Err(AsepriteError::InvalidCelData {
+                layer_index: 0,
+                frame_index: 0,
+                ..
+            })
+        ));
+
+        // Claiming a bigger cel than what was compressed must not be silently zero-filled
+        let mut resized = ase_file;
+        resized[cel_data_offset - 4] += 1;
+        assert!(matches!(
+            super::read_aseprite(&resized),
+            Err(AsepriteError::InvalidCelData {
+                layer_index: 0,
+                frame_index: 0,
+                source: AsepriteCelDataError::SizeMismatch {
+                    expected: 226_176,
+                    found: 224_352,
+                },
+            })
+        ));
+    }
+
+    #[test]
+    fn check_forged_chunk_count() {
+        let mut ase_file = std::fs::read("./tests/test_cases/simple.aseprite").unwrap();
+        // 128 bytes of file header, then the u32 chunk count at byte 12 of the frame header
+        ase_file[128 + 12..128 + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        assert!(super::read_aseprite(&ase_file).is_err());
+    }
+
+    #[test]
+    fn check_user_data_properties_round_trip() {
+        let mut properties = Vec::new();
+        // number of maps, key, number of properties
+        properties.extend_from_slice(&1u32.to_le_bytes());
+        properties.extend_from_slice(&0u32.to_le_bytes());
+        properties.extend_from_slice(&2u32.to_le_bytes());
+        // "speed": uint8 7
+        properties.extend_from_slice(&5u16.to_le_bytes());
+        properties.extend_from_slice(b"speed");
+        properties.extend_from_slice(&0x0003u16.to_le_bytes());
+        properties.push(7);
+        // "future": a type that doesn't exist (yet)
+        properties.extend_from_slice(&6u16.to_le_bytes());
+        properties.extend_from_slice(b"future");
+        properties.extend_from_slice(&0x0100u16.to_le_bytes());
+        properties.extend_from_slice(&[1, 2, 3, 4]);
+
+        let mut chunk = 0x5u32.to_le_bytes().to_vec();
+        chunk.extend_from_slice(&2u16.to_le_bytes());
+        chunk.extend_from_slice(b"hi");
+        chunk.extend_from_slice(&(properties.len() as u32 + 4).to_le_bytes());
+        chunk.extend_from_slice(&properties);
+
+        let (rest, user_data) = aseprite_user_data(&chunk).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(user_data.text, "hi");
+        assert_eq!(user_data.properties.len(), 1);
+        assert_eq!(
+            user_data.properties[0].properties,
+            vec![
+                ("speed".to_owned(), AsepritePropertyValue::UInt8(7)),
+                (
+                    "future".to_owned(),
+                    AsepritePropertyValue::Unknown {
+                        type_id: 0x0100,
+                        bytes: vec![1, 2, 3, 4],
+                    }
+                ),
+            ]
+        );
+        assert_eq!(user_data.to_chunk_data(), chunk);
+    }
+
+    #[test]
+    fn check_tag_repeat() {
+        let mut tag = Vec::new();
+        tag.extend_from_slice(&1u16.to_le_bytes());
+        tag.extend_from_slice(&3u16.to_le_bytes());
+        tag.push(2);
+        // repeat
+        tag.extend_from_slice(&4u16.to_le_bytes());
+        tag.extend_from_slice(&[0; 6 + 3 + 1]);
+        tag.extend_from_slice(&4u16.to_le_bytes());
+        tag.extend_from_slice(b"Walk");
+
+        let (rest, tag) = aseprite_tag(&tag).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!((tag.from, tag.to), (1, 3));
+        assert_eq!(tag.anim_direction, AsepriteAnimationDirection::PingPong);
+        assert_eq!(tag.repeat, 4);
+        assert_eq!(tag.name, "Walk");
+
+        let (_, direction) = aseprite_anim_direction(&[3]).unwrap();
+        assert_eq!(direction, AsepriteAnimationDirection::PingPongReverse);
+        assert!(aseprite_anim_direction(&[4]).is_err());
+    }
+
+    #[test]
+    fn check_packed_pixels() {
+        let pixels = [
+            AsepritePixel::Grayscale {
+                intensity: 200,
+                alpha: 255,
+            },
+            AsepritePixel::Grayscale {
+                intensity: 10,
+                alpha: 0,
+            },
+        ];
+        let packed = PackedPixels::from_pixels(AsepriteColorDepth::Grayscale, &pixels).unwrap();
+        // 灰度每个像素 2 个字节
+        assert_eq!(packed.as_bytes(), &[200, 255, 10, 0]);
+        assert_eq!(packed.len(), 2);
+        assert_eq!(packed.get(1), Some(pixels[1].clone()));
+        assert_eq!(packed.get(2), None);
+        assert_eq!(packed.iter().collect::<Vec<_>>(), pixels);
+        assert_eq!(
+            pixels[0].get_rgba(None, None).unwrap(),
+            [200, 200, 200, 255]
+        );
+
+        let indexed = PackedPixels::new(AsepriteColorDepth::Indexed, vec![1, 2, 3]).unwrap();
+        assert_eq!(indexed.get(2), Some(AsepritePixel::Indexed(3)));
+        assert!(PackedPixels::new(AsepriteColorDepth::RGBA, vec![0; 6]).is_none());
+        assert!(PackedPixels::from_pixels(AsepriteColorDepth::RGBA, &pixels).is_none());
+        assert!(PackedPixels::new(AsepriteColorDepth::RGBA, Vec::new())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn check_visit_chunks() {
+        #[derive(Default)]
+        struct Counter {
+            frames: Vec<u16>,
+            cel_sizes: Vec<Option<(u16, u16)>>,
+            layers: usize,
+            decode_layer: Option<u16>,
+            decoded: usize,
+            skipped: usize,
+        }
+        impl AsepriteChunkVisitor for Counter {
+            fn on_frame(&mut self, frame_index: usize, header: &RawAsepriteFrameHeader) {
+                assert_eq!(frame_index, self.frames.len());
+                self.frames.push(header.duration_ms);
+            }
+            fn on_cel_header(
+                &mut self,
+                _frame_index: usize,
+                header: &RawAsepriteCelHeader,
+            ) -> CelPixels {
+                self.cel_sizes.push(header.size);
+                match self.decode_layer {
+                    Some(layer_index) if layer_index == header.layer_index => CelPixels::Decode,
+                    _ => CelPixels::Skip,
+                }
+            }
+            fn on_chunk(&mut self, _frame_index: usize, chunk: RawAsepriteChunk) {
+                match chunk {
+                    RawAsepriteChunk::Layer { .. } => self.layers += 1,
+                    RawAsepriteChunk::Cel { cel, .. } => match cel {
+                        RawAsepriteCel::Compressed { .. } | RawAsepriteCel::Raw { .. } => {
+                            self.decoded += 1
+                        }
+                        RawAsepriteCel::Skipped { .. } => self.skipped += 1,
+                        _ => {}
+                    },
+                    _ => {}
+                }
+            }
+        }
+
+        let ase_file = std::fs::read("./tests/test_cases/complex.aseprite").unwrap();
+        let raw = super::read_aseprite(&ase_file).unwrap();
+        let cels: Vec<&RawAsepriteCel> = raw
+            .frames
+            .iter()
+            .flat_map(|frame| &frame.chunks)
+            .filter_map(|chunk| match chunk {
+                RawAsepriteChunk::Cel { cel, .. } => Some(cel),
+                _ => None,
+            })
+            .collect();
+
+        // 默认不读取像素
+        let mut counter = Counter::default();
+        super::visit_chunks(&ase_file, &mut counter).unwrap();
+        let durations: Vec<u16> = raw.frames.iter().map(|frame| frame.duration_ms).collect();
+        assert_eq!(counter.frames, durations);
+        assert_eq!(counter.layers, 20);
+        assert_eq!(counter.cel_sizes.len(), cels.len());
+        for (size, cel) in counter.cel_sizes.iter().zip(&cels) {
+            match cel {
+                RawAsepriteCel::Compressed { width, height, .. }
+                | RawAsepriteCel::Raw { width, height, .. } => {
+                    assert_eq!(*size, Some((*width, *height)))
+                }
+                _ => assert_eq!(*size, None),
+            }
+        }
+        assert_eq!(counter.decoded, 0);
+        assert_eq!(counter.skipped, counter.cel_sizes.iter().flatten().count());
+
+        // 只解压选中的 cel
+        let mut counter = Counter {
+            decode_layer: Some(2),
+            ..Counter::default()
+        };
+        super::visit_chunks(&ase_file, &mut counter).unwrap();
+        assert_eq!(counter.decoded, 2);
+        assert_eq!(
+            counter.decoded + counter.skipped,
+            counter.cel_sizes.iter().flatten().count()
+        );
+
+        assert!(matches!(
+            super::visit_chunks(b"not an aseprite file", &mut Counter::default()),
+            Err(AsepriteError::NotAnAsepriteFile)
+        ));
+    }
+
+    #[test]
+    fn check_scan_layout() {
+        let ase_file = std::fs::read("./tests/test_cases/complex.aseprite").unwrap();
+        let layout = super::scan_layout(&ase_file).unwrap();
+        assert_eq!(layout.error, None);
+        assert_eq!(layout.frames.len(), 2);
+
+        // 帧和 chunk 首尾相连，覆盖整个文件
+        let mut offset = layout.header_size;
+        for frame in &layout.frames {
+            assert_eq!(frame.offset, offset);
+            let mut chunk_offset = frame.offset + 16;
+            for chunk in &frame.chunks {
+                assert_eq!(chunk.offset, chunk_offset);
+                chunk_offset += chunk.size;
+            }
+            assert_eq!(chunk_offset, frame.offset + frame.size);
+            offset += frame.size;
+        }
+        assert_eq!(offset, ase_file.len());
+
+        let raw = super::read_aseprite(&ase_file).unwrap();
+        for (frame, raw_frame) in layout.frames.iter().zip(&raw.frames) {
+            let cels = frame
+                .chunks
+                .iter()
+                .filter(|chunk| chunk.chunk_type == 0x2005)
+                .count();
+            let raw_cels = raw_frame
+                .chunks
+                .iter()
+                .filter(|chunk| matches!(chunk, RawAsepriteChunk::Cel { .. }))
+                .count();
+            assert_eq!(cels, raw_cels);
+        }
+        let first = layout.frames[0].chunks[0];
+        assert_eq!(first.type_name(), "Color Profile Chunk");
+
+        // 截断的文件返回截断之前的部分
+        let second = &layout.frames[1];
+        let cut = second.chunks[2].offset + 3;
+        let truncated = super::scan_layout(&ase_file[..cut]).unwrap();
+        assert_eq!(truncated.frames[0], layout.frames[0]);
+        assert_eq!(truncated.frames[1].chunks, second.chunks[..2]);
+        assert_eq!(
+            truncated.error,
+            Some(AsepriteLayoutError::InvalidChunkSize {
+                frame_index: 1,
+                chunk_index: 2,
+                offset: second.chunks[2].offset,
+                size: 0,
+            })
+        );
+
+        let mut bad_magic = ase_file.clone();
+        bad_magic[second.offset + 4] ^= 0xFF;
+        let bad = super::scan_layout(&bad_magic).unwrap();
+        assert_eq!(bad.frames.len(), 1);
+        assert!(matches!(
+            bad.error,
+            Some(AsepriteLayoutError::BadFrameMagic { frame_index: 1, .. })
+        ));
+
+        assert!(matches!(
+            super::scan_layout(b"not an aseprite file"),
+            Err(AsepriteError::NotAnAsepriteFile)
+        ));
+    }
+
+    #[test]
+    fn check_color_conversions() {
+        let color = AsepriteColor {
+            red: 255,
+            green: 128,
+            blue: 0,
+            alpha: 51,
+        };
+        assert_eq!(<[u8; 4]>::from(color), [255, 128, 0, 51]);
+        assert_eq!(AsepriteColor::from([255, 128, 0, 51]), color);
+        assert_eq!(image::Rgba::from(color), image::Rgba([255, 128, 0, 51]));
+        assert_eq!(AsepriteColor::from(image::Rgba([255, 128, 0, 51])), color);
+
+        assert_eq!(color.to_f32_array(), [1.0, 128.0 / 255.0, 0.0, 0.2]);
+        let [red, green, blue, alpha] = color.to_premultiplied_f32_array();
+        assert_eq!((red, blue, alpha), (0.2, 0.0, 0.2));
+        assert!((green - 128.0 / 255.0 * 0.2).abs() < 1e-6);
+
+        assert_eq!(color.to_hex(), "#FF800033");
+        assert_eq!(AsepriteColor::from_hex("#FF800033"), Ok(color));
+        assert_eq!(AsepriteColor::from_hex("ff800033"), Ok(color));
+        // 没有 alpha 时不透明
+        assert_eq!(
+            "#ff8000".parse::<AsepriteColor>(),
+            Ok(AsepriteColor::from([255, 128, 0, 255]))
+        );
+        for invalid in [
+            "", "#", "#FF80", "#FF80003", "+F800033", "#GG8000", "##FF8000",
+        ] {
+            assert_eq!(
+                AsepriteColor::from_hex(invalid),
+                Err(AsepriteHexColorError(invalid.to_string()))
+            );
+        }
+
+        // 可以作为 HashMap 的 key
+        let counts: std::collections::HashMap<AsepriteColor, usize> =
+            vec![(color, 1)].into_iter().collect();
+        assert_eq!(counts[&color], 1);
+    }
 }